@@ -3,46 +3,131 @@ use cosmwasm_std::entry_point;
 
 use alliance_protocol::alliance_oracle_types::QueryMsg as OracleQueryMsg;
 use alliance_protocol::alliance_protocol::{
-    AllianceDelegateMsg, AllianceRedelegateMsg, AllianceUndelegateMsg, AssetDistribution,
-    MigrateMsg,
+    AllianceDelegateMsg, AllianceDelegation, AllianceRedelegateMsg, AllianceRedelegation,
+    AllianceUndelegateMsg, AssetDistribution,
 };
 use alliance_protocol::{
     alliance_oracle_types::{AssetStaked, ChainId, EmissionsDistribution},
     error::ContractError,
     token_factory::{CustomExecuteMsg, DenomUnit, Metadata, TokenExecuteMsg},
 };
+use bech32::Variant;
 use cosmwasm_std::{
-    to_json_binary, Addr, Binary, Coin as CwCoin, CosmosMsg, Decimal, DepsMut, Empty, Env,
-    MessageInfo, Order, Reply, Response, StdError, StdResult, Storage, SubMsg, Timestamp, Uint128,
-    WasmMsg,
+    to_json_binary, Addr, BankMsg, Binary, Coin as CwCoin, CosmosMsg, Decimal, Deps, DepsMut, Env,
+    MessageInfo, Order, Reply, Response, StdResult, Storage, SubMsg, SubMsgResponse, SubMsgResult,
+    Timestamp, Uint128, WasmMsg,
 };
 use cw2::set_contract_version;
 use cw_asset::{Asset, AssetInfo, AssetInfoBase, AssetInfoKey, AssetInfoUnchecked};
-use cw_utils::parse_instantiate_response_data;
-use std::collections::{HashMap, HashSet};
+use cw_storage_plus::PrefixBound;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use terra_proto_rs::alliance::alliance::{
     MsgClaimDelegationRewards, MsgDelegate, MsgRedelegate, MsgUndelegate,
 };
 use terra_proto_rs::cosmos::base::v1beta1::Coin;
+use terra_proto_rs::osmosis::tokenfactory::v1beta1::MsgCreateDenomResponse;
 use terra_proto_rs::traits::Message;
 
-use crate::models::{Config, ExecuteMsg, InstantiateMsg};
+use crate::delegations::{compute_rebalance, validate_strategy};
+use crate::lockup::{
+    add_lock_expiry, due_lock_expiries, expire_total_boost, expire_user_boost, lock_boost,
+    locked_balance, remove_lock_expiry, total_effective_balance, update_boost, validate_lock_tiers,
+};
+use crate::models::{
+    ClaimFailure, Config, DelegationStrategy, ExecuteMsg, InstantiateMsg, LockTier, LockedPosition,
+    MigrateMsg, ProtocolFee, ProtocolFeeParams, ReceiptDenomParams, RewardStream,
+    StakeAllowanceParams, TransferStakeParams, UnlockParams,
+};
+use crate::reward_index::{
+    accrue_user_rewards, accrued_rewards, index_from_rate, index_increase, load_user_reward_index,
+    released_rewards, save_user_reward_index,
+};
 use crate::state::{
-    ASSET_REWARD_DISTRIBUTION, ASSET_REWARD_RATE, BALANCES, CONFIG, TEMP_BALANCE, TOTAL_BALANCES,
-    UNCLAIMED_REWARDS, USER_ASSET_REWARD_RATE, VALIDATORS, WHITELIST,
+    ALLIANCE_TOKEN_PARAMS, ALLOWED_VALIDATORS, ASSET_REWARD_DISTRIBUTION, ASSET_REWARD_INDEX,
+    BALANCES, CLAIM_FAILURES, CONFIG, DELEGATIONS, DELEGATION_STRATEGY, DUST, DUST_RECIPIENT,
+    EXPIRED_LOCK_INDEXES, FEES_COLLECTED, ISSUED_RECEIPTS, LEGACY_ASSET_REWARD_RATE,
+    LOCKED_POSITIONS, LOCK_TIERS, PENDING_CLAIMS, PENDING_RECEIPT_ASSET, PROTOCOL_FEE,
+    RECEIPT_ASSETS, RECEIPT_DENOMS, RECEIPT_REWARDS, RECEIPT_REWARD_INDEX, RESERVED_REWARDS,
+    REWARD_STREAMS, REWARD_STREAM_PERIOD, STAKE_ALLOWANCES, TOTAL_BALANCES, UNBONDINGS,
+    UNBONDING_PERIOD, UNCLAIMED_REWARDS, VALIDATORS, WHITELIST,
 };
 
 // version info for migration info
 const CONTRACT_NAME: &str = "crates.io:terra-alliance-protocol";
 const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION");
 const CREATE_REPLY_ID: u64 = 1;
-const CLAIM_REWARD_ERROR_REPLY_ID: u64 = 2;
+const CLAIM_REWARD_REPLY_ID: u64 = 2;
+const RECEIPT_REPLY_ID: u64 = 3;
+const VALIDATOR_ADDRESS_PREFIX: &str = "terravaloper";
+const DEFAULT_UNBONDING_PERIOD: u64 = 21 * 24 * 60 * 60;
+const MAX_TAKE_RATE: Decimal = Decimal::percent(20);
 
 #[cfg_attr(not(feature = "library"), entry_point)]
-pub fn migrate(_deps: DepsMut, _env: Env, _msg: MigrateMsg) -> StdResult<Response> {
+pub fn migrate(deps: DepsMut, env: Env, msg: MigrateMsg) -> Result<Response, ContractError> {
+    // Contracts that tracked rewards with a balance snapshot hold only the staked
+    // principal and rewards of the reward denom, so the rest is reserved for stakers
+    if RESERVED_REWARDS.may_load(deps.storage)?.is_none() {
+        let config = CONFIG.load(deps.storage)?;
+        let reward_asset = AssetInfo::native(config.reward_denom);
+        let balance = reward_asset.query_balance(&deps.querier, env.contract.address.clone())?;
+        let principal = TOTAL_BALANCES
+            .may_load(deps.storage, AssetInfoKey::from(&reward_asset))?
+            .unwrap_or_default();
+        RESERVED_REWARDS.save(deps.storage, &balance.saturating_sub(principal))?;
+    }
+    // Asset reward rates are moved to the reward indexes while the
+    // rates of the users are moved when their rewards are updated
+    let legacy_rates = LEGACY_ASSET_REWARD_RATE
+        .range(deps.storage, None, None, Order::Ascending)
+        .collect::<StdResult<Vec<_>>>()?;
+    for (asset, rate) in legacy_rates {
+        let asset_key = AssetInfoKey::from(asset.check(deps.api, None)?);
+        ASSET_REWARD_INDEX.save(deps.storage, asset_key.clone(), &index_from_rate(rate))?;
+        LEGACY_ASSET_REWARD_RATE.remove(deps.storage, asset_key);
+    }
+    // Contracts instantiated before the unbondings were tracked use the default period
+    if UNBONDING_PERIOD.may_load(deps.storage)?.is_none() {
+        UNBONDING_PERIOD.save(deps.storage, &DEFAULT_UNBONDING_PERIOD)?;
+    }
+    if let Some(delegations) = msg.delegations {
+        seed_delegations(deps, env, delegations)?;
+    }
     Ok(Response::default())
 }
 
+// Contracts that did not track the delegated amounts get them from the migration.
+// They cannot be more than the alliance tokens that are not held by the contract.
+fn seed_delegations(
+    deps: DepsMut,
+    env: Env,
+    delegations: Vec<AllianceDelegation>,
+) -> Result<(), ContractError> {
+    if !DELEGATIONS.is_empty(deps.storage) {
+        return Err(ContractError::DelegationsAlreadyTracked {});
+    }
+    let config = CONFIG.load(deps.storage)?;
+    ensure_alliance_token_denom(&config)?;
+    let mut validators = VALIDATORS.load(deps.storage)?;
+    let mut total = Uint128::zero();
+    for delegation in delegations {
+        if delegation.amount.is_zero() {
+            return Err(ContractError::AmountCannotBeZero {});
+        }
+        increase_delegation(deps.storage, &delegation.validator, delegation.amount)?;
+        total = total.checked_add(delegation.amount)?;
+        validators.insert(delegation.validator);
+    }
+    let free = deps
+        .querier
+        .query_balance(env.contract.address, &config.alliance_token_denom)?
+        .amount;
+    if total.checked_add(free)? > config.alliance_token_supply {
+        return Err(ContractError::InsufficientBalance {});
+    }
+    VALIDATORS.save(deps.storage, &validators)?;
+    Ok(())
+}
+
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn instantiate(
     deps: DepsMut,
@@ -54,13 +139,12 @@ pub fn instantiate(
     let governance_address = deps.api.addr_validate(msg.governance.as_str())?;
     let controller_address = deps.api.addr_validate(msg.controller.as_str())?;
     let oracle_address = deps.api.addr_validate(msg.oracle.as_str())?;
-    let create_msg = TokenExecuteMsg::CreateDenom {
-        subdenom: "ualliance".to_string(),
-    };
-    let sub_msg = SubMsg::reply_on_success(
-        CosmosMsg::Custom(CustomExecuteMsg::Token(create_msg)),
-        CREATE_REPLY_ID,
-    );
+    let alliance_token = msg.alliance_token.unwrap_or_default();
+    if alliance_token.initial_supply.is_zero() {
+        return Err(ContractError::AmountCannotBeZero {});
+    }
+    ALLIANCE_TOKEN_PARAMS.save(deps.storage, &alliance_token)?;
+    let sub_msg = create_denom_msg("ualliance".to_string());
     let config = Config {
         governance: governance_address,
         controller: controller_address,
@@ -73,6 +157,12 @@ pub fn instantiate(
     CONFIG.save(deps.storage, &config)?;
 
     VALIDATORS.save(deps.storage, &HashSet::new())?;
+    ALLOWED_VALIDATORS.save(deps.storage, &HashSet::new())?;
+    RESERVED_REWARDS.save(deps.storage, &Uint128::zero())?;
+    UNBONDING_PERIOD.save(
+        deps.storage,
+        &msg.unbonding_period.unwrap_or(DEFAULT_UNBONDING_PERIOD),
+    )?;
     Ok(Response::new()
         .add_attributes(vec![("action", "instantiate")])
         .add_submessage(sub_msg))
@@ -84,24 +174,57 @@ pub fn execute(
     env: Env,
     info: MessageInfo,
     msg: ExecuteMsg,
-) -> Result<Response, ContractError> {
+) -> Result<Response<CustomExecuteMsg>, ContractError> {
     match msg {
         ExecuteMsg::WhitelistAssets(assets) => whitelist_assets(deps, info, assets),
         ExecuteMsg::RemoveAssets(assets) => remove_assets(deps, info, assets),
+        ExecuteMsg::AddAllowedValidators(validators) => {
+            add_allowed_validators(deps, info, validators)
+        }
+        ExecuteMsg::RemoveAllowedValidators(validators) => {
+            remove_allowed_validators(deps, info, validators)
+        }
 
-        ExecuteMsg::Stake {} => stake(deps, env, info),
-        ExecuteMsg::Unstake(asset) => unstake(deps, info, asset),
-        ExecuteMsg::ClaimRewards(asset) => claim_rewards(deps, info, asset),
+        ExecuteMsg::Stake { recipient } => stake(deps, env, info, recipient),
+        ExecuteMsg::StakeLocked { duration } => stake_locked(deps, env, info, duration),
+        ExecuteMsg::Unstake(asset) => unstake(deps, env, info, asset),
+        ExecuteMsg::Unlock(params) => unlock(deps, env, params),
+        ExecuteMsg::TransferStake(params) => transfer_stake(deps, env, info, params),
+        ExecuteMsg::SetStakeAllowance(params) => set_stake_allowance(deps, info, params),
+        ExecuteMsg::WithdrawReceipts(asset) => withdraw_receipts(deps, env, info, asset),
+        ExecuteMsg::ClaimRewards(asset) => claim_rewards(deps, env, info, asset),
+        ExecuteMsg::RebalanceDelegations {} => rebalance_delegations(deps, env),
 
         ExecuteMsg::AllianceDelegate(msg) => alliance_delegate(deps, env, info, msg),
         ExecuteMsg::AllianceUndelegate(msg) => alliance_undelegate(deps, env, info, msg),
         ExecuteMsg::AllianceRedelegate(msg) => alliance_redelegate(deps, env, info, msg),
+        ExecuteMsg::UpdateDelegationStrategy(strategy) => {
+            update_delegation_strategy(deps, info, strategy)
+        }
 
         ExecuteMsg::UpdateRewards {} => update_rewards(deps, env, info),
         ExecuteMsg::RebalanceEmissions {} => rebalance_emissions(deps, env, info),
 
         ExecuteMsg::UpdateRewardsCallback {} => update_reward_callback(deps, env, info),
         ExecuteMsg::RebalanceEmissionsCallback {} => rebalance_emissions_callback(deps, env, info),
+
+        ExecuteMsg::MintAllianceTokens { amount } => mint_alliance_tokens(deps, env, info, amount),
+        ExecuteMsg::BurnAllianceTokens { amount } => burn_alliance_tokens(deps, env, info, amount),
+        ExecuteMsg::SetAllianceTokenDenom { denom } => {
+            set_alliance_token_denom(deps, env, info, denom)
+        }
+        ExecuteMsg::RecreateDenom { subdenom } => recreate_denom(deps, info, subdenom),
+        ExecuteMsg::SetDustRecipient { recipient } => set_dust_recipient(deps, info, recipient),
+        ExecuteMsg::UpdateProtocolFee(params) => update_protocol_fee(deps, info, params),
+        ExecuteMsg::UpdateRewardStreamPeriod { seconds } => {
+            update_reward_stream_period(deps, info, seconds)
+        }
+        ExecuteMsg::UpdateUnbondingPeriod { seconds } => {
+            update_unbonding_period(deps, info, seconds)
+        }
+        ExecuteMsg::UpdateLockTiers(tiers) => update_lock_tiers(deps, info, tiers),
+        ExecuteMsg::CreateReceiptDenom(params) => create_receipt_denom(deps, info, params),
+        ExecuteMsg::SweepDust {} => sweep_dust(deps, info),
     }
 }
 
@@ -109,7 +232,7 @@ fn whitelist_assets(
     deps: DepsMut,
     info: MessageInfo,
     assets_request: HashMap<ChainId, Vec<AssetInfo>>,
-) -> Result<Response, ContractError> {
+) -> Result<Response<CustomExecuteMsg>, ContractError> {
     let config = CONFIG.load(deps.storage)?;
     is_governance(&info, &config)?;
     let mut attrs = vec![("action".to_string(), "whitelist_assets".to_string())];
@@ -117,8 +240,8 @@ fn whitelist_assets(
         for asset in assets {
             let asset_key = AssetInfoKey::from(asset.clone());
             WHITELIST.save(deps.storage, asset_key.clone(), chain_id)?;
-            ASSET_REWARD_RATE.update(deps.storage, asset_key, |rate| -> StdResult<_> {
-                Ok(rate.unwrap_or(Decimal::zero()))
+            ASSET_REWARD_INDEX.update(deps.storage, asset_key, |index| -> StdResult<_> {
+                Ok(index.unwrap_or_default())
             })?;
         }
         attrs.push(("chain_id".to_string(), chain_id.to_string()));
@@ -137,7 +260,7 @@ fn remove_assets(
     deps: DepsMut,
     info: MessageInfo,
     assets: Vec<AssetInfo>,
-) -> Result<Response, ContractError> {
+) -> Result<Response<CustomExecuteMsg>, ContractError> {
     let config = CONFIG.load(deps.storage)?;
     // Only allow the governance address to update whitelisted assets
     is_governance(&info, &config)?;
@@ -153,95 +276,384 @@ fn remove_assets(
     Ok(Response::new().add_attributes(vec![("action", "remove_assets"), ("assets", &assets_str)]))
 }
 
-fn stake(deps: DepsMut, _env: Env, info: MessageInfo) -> Result<Response, ContractError> {
-    if info.funds.len() != 1 {
-        return Err(ContractError::OnlySingleAssetAllowed {});
+fn add_allowed_validators(
+    deps: DepsMut,
+    info: MessageInfo,
+    validators: Vec<String>,
+) -> Result<Response<CustomExecuteMsg>, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    is_governance(&info, &config)?;
+    let mut allowed_validators = ALLOWED_VALIDATORS
+        .may_load(deps.storage)?
+        .unwrap_or_default();
+    for validator in &validators {
+        validate_validator(deps.as_ref(), validator)?;
+        allowed_validators.insert(validator.clone());
+    }
+    ALLOWED_VALIDATORS.save(deps.storage, &allowed_validators)?;
+    Ok(Response::new().add_attributes(vec![
+        ("action", "add_allowed_validators"),
+        ("validators", &validators.join(",")),
+    ]))
+}
+
+// Removed validators keep their delegations until they are
+// redelegated or undelegated by the controller
+fn remove_allowed_validators(
+    deps: DepsMut,
+    info: MessageInfo,
+    validators: Vec<String>,
+) -> Result<Response<CustomExecuteMsg>, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    is_governance(&info, &config)?;
+    let mut allowed_validators = ALLOWED_VALIDATORS
+        .may_load(deps.storage)?
+        .unwrap_or_default();
+    for validator in &validators {
+        allowed_validators.remove(validator);
     }
-    if info.funds[0].amount.is_zero() {
+    ALLOWED_VALIDATORS.save(deps.storage, &allowed_validators)?;
+    Ok(Response::new().add_attributes(vec![
+        ("action", "remove_allowed_validators"),
+        ("validators", &validators.join(",")),
+    ]))
+}
+
+fn stake(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    recipient: Option<String>,
+) -> Result<Response<CustomExecuteMsg>, ContractError> {
+    if info.funds.is_empty() {
         return Err(ContractError::AmountCannotBeZero {});
     }
-    let asset = AssetInfo::native(&info.funds[0].denom);
-    let asset_key = AssetInfoKey::from(&asset);
-    WHITELIST
-        .load(deps.storage, asset_key.clone())
-        .map_err(|_| ContractError::AssetNotWhitelisted(asset.to_string()))?;
+    // Every coin is validated before any balance changes
+    let assets = info
+        .funds
+        .iter()
+        .map(|coin| staked_coin(deps.storage, coin))
+        .collect::<Result<Vec<_>, _>>()?;
+    let user = match recipient {
+        Some(recipient) => deps.api.addr_validate(&recipient)?,
+        None => info.sender,
+    };
+
+    let mut res =
+        Response::new().add_attributes(vec![("action", "stake"), ("user", user.as_ref())]);
+    for (asset, receipts_sent) in assets {
+        increase_stake(deps.storage, &env, user.clone(), &asset)?;
+        let receipt_msgs = stake_receipts_msgs(deps.storage, &env, &user, &asset, receipts_sent)?;
+        res = res
+            .add_attributes(vec![
+                ("asset", asset.info.to_string()),
+                ("amount", asset.amount.to_string()),
+            ])
+            .add_messages(receipt_msgs);
+    }
+    Ok(res)
+}
+
+fn stake_locked(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    duration: u64,
+) -> Result<Response<CustomExecuteMsg>, ContractError> {
+    let (asset, receipts_sent) = staked_funds(deps.storage, &info)?;
+    let tier = LOCK_TIERS
+        .may_load(deps.storage)?
+        .unwrap_or_default()
+        .into_iter()
+        .find(|tier| tier.duration == duration)
+        .ok_or(ContractError::LockTierNotFound(duration))?;
     let sender = info.sender.clone();
+    let asset_key = AssetInfoKey::from(&asset.info);
+    increase_stake(deps.storage, &env, sender.clone(), &asset)?;
 
-    let rewards = _claim_reward(deps.storage, sender.clone(), asset.clone())?;
-    if !rewards.is_zero() {
-        UNCLAIMED_REWARDS.update(
-            deps.storage,
-            (sender.clone(), asset_key.clone()),
-            |balance| -> Result<_, ContractError> {
-                Ok(balance.unwrap_or(Uint128::zero()) + rewards)
-            },
-        )?;
+    // Locking again adds to the position and restarts its lock with the current multiplier
+    let position_key = (sender.clone(), asset_key.clone(), duration);
+    let mut position = match LOCKED_POSITIONS.may_load(deps.storage, position_key.clone())? {
+        Some(position) => {
+            remove_lock_expiry(deps.storage, asset_key.clone(), &position)?;
+            position
+        }
+        None => LockedPosition {
+            amount: Uint128::zero(),
+            multiplier: Decimal::one(),
+            unlock_time: env.block.time,
+        },
+    };
+    let old_boost = lock_boost(position.amount, position.multiplier);
+    position.amount += asset.amount;
+    position.multiplier = tier.multiplier;
+    position.unlock_time = env.block.time.plus_seconds(duration);
+    let new_boost = lock_boost(position.amount, position.multiplier);
+    add_lock_expiry(deps.storage, asset_key.clone(), &position)?;
+    update_boost(deps.storage, (sender, asset_key), old_boost, new_boost)?;
+    LOCKED_POSITIONS.save(deps.storage, position_key, &position)?;
+    let receipt_msgs =
+        stake_receipts_msgs(deps.storage, &env, &info.sender, &asset, receipts_sent)?;
+
+    Ok(Response::new()
+        .add_attributes(vec![
+            ("action", "stake_locked"),
+            ("user", info.sender.as_ref()),
+            ("asset", &asset.info.to_string()),
+            ("amount", &asset.amount.to_string()),
+            ("duration", &duration.to_string()),
+            ("unlock_time", &position.unlock_time.seconds().to_string()),
+        ])
+        .add_messages(receipt_msgs))
+}
+
+// The single whitelisted coin sent to be staked, receipts are staked as their
+// asset and are flagged so that they are not minted again
+fn staked_funds(storage: &dyn Storage, info: &MessageInfo) -> Result<(Asset, bool), ContractError> {
+    if info.funds.len() != 1 {
+        return Err(ContractError::OnlySingleAssetAllowed {});
+    }
+    staked_coin(storage, &info.funds[0])
+}
+
+// Receipts sent back are staked as their underlying asset even if it is not whitelisted
+fn staked_coin(storage: &dyn Storage, coin: &CwCoin) -> Result<(Asset, bool), ContractError> {
+    if coin.amount.is_zero() {
+        return Err(ContractError::AmountCannotBeZero {});
+    }
+    let receipt_asset = RECEIPT_ASSETS.may_load(storage, &coin.denom)?;
+    let receipts_sent = receipt_asset.is_some();
+    let asset = receipt_asset.unwrap_or_else(|| AssetInfo::native(&coin.denom));
+    // Receipts can be staked back after their asset is removed from the whitelist
+    if !receipts_sent {
+        WHITELIST
+            .load(storage, AssetInfoKey::from(&asset))
+            .map_err(|_| ContractError::AssetNotWhitelisted(asset.to_string()))?;
     }
+    Ok((Asset::new(asset, coin.amount), receipts_sent))
+}
 
+// Tokens of the locked positions cannot be moved out of the balance of the user
+fn decrease_unlocked_balance(
+    storage: &mut dyn Storage,
+    user: Addr,
+    asset: &Asset,
+) -> Result<(), ContractError> {
+    let asset_key = AssetInfoKey::from(&asset.info);
+    let locked = locked_balance(storage, user.clone(), asset_key.clone())?;
     BALANCES.update(
-        deps.storage,
-        (sender.clone(), asset_key.clone()),
+        storage,
+        (user, asset_key),
         |balance| -> Result<_, ContractError> {
             match balance {
-                Some(balance) => Ok(balance + info.funds[0].amount),
-                None => Ok(info.funds[0].amount),
+                Some(balance) => {
+                    if balance < asset.amount + locked {
+                        return Err(ContractError::InsufficientBalance {});
+                    }
+                    Ok(balance - asset.amount)
+                }
+                None => Err(ContractError::AssetNotStaked {}),
             }
         },
     )?;
-    TOTAL_BALANCES.update(
-        deps.storage,
-        asset_key.clone(),
-        |balance| -> Result<_, ContractError> {
-            Ok(balance.unwrap_or(Uint128::zero()) + info.funds[0].amount)
-        },
-    )?;
+    Ok(())
+}
 
-    let asset_reward_rate = ASSET_REWARD_RATE
-        .load(deps.storage, asset_key.clone())
-        .unwrap_or(Decimal::zero());
-    USER_ASSET_REWARD_RATE.save(deps.storage, (sender, asset_key), &asset_reward_rate)?;
+// Receipts of the staked tokens are minted to the contract, which keeps them until
+// they are withdrawn. Receipts staked back are already held by the contract and
+// their share of the rewards owed to the issued receipts goes to the user.
+fn stake_receipts_msgs(
+    storage: &mut dyn Storage,
+    env: &Env,
+    user: &Addr,
+    asset: &Asset,
+    receipts_sent: bool,
+) -> Result<Vec<CosmosMsg<CustomExecuteMsg>>, ContractError> {
+    let asset_key = AssetInfoKey::from(&asset.info);
+    let denom = match RECEIPT_DENOMS.may_load(storage, asset_key.clone())? {
+        Some(denom) => denom,
+        None => return Ok(vec![]),
+    };
+    if receipts_sent {
+        let (issued, owed) = settle_receipt_rewards(storage, asset_key.clone())?;
+        let remaining = issued.checked_sub(asset.amount)?;
+        let rewards = owed.mul_floor((asset.amount, issued));
+        RECEIPT_REWARDS.save(storage, asset_key.clone(), &(owed - rewards))?;
+        ISSUED_RECEIPTS.save(storage, asset_key.clone(), &remaining)?;
+        // The tokens of the receipts were already part of the total balance
+        TOTAL_BALANCES.update(storage, asset_key.clone(), |balance| -> StdResult<_> {
+            Ok(balance.unwrap_or_default().checked_sub(asset.amount)?)
+        })?;
+        if !rewards.is_zero() {
+            UNCLAIMED_REWARDS.update(
+                storage,
+                (user.clone(), asset_key),
+                |balance| -> StdResult<_> { Ok(balance.unwrap_or_default() + rewards) },
+            )?;
+        }
+        return Ok(vec![]);
+    }
+    Ok(vec![CosmosMsg::Custom(CustomExecuteMsg::Token(
+        TokenExecuteMsg::MintTokens {
+            denom,
+            amount: asset.amount,
+            mint_to_address: env.contract.address.to_string(),
+        },
+    ))])
+}
 
-    Ok(Response::new().add_attributes(vec![
-        ("action", "stake"),
-        ("user", info.sender.as_ref()),
-        ("asset", &asset.to_string()),
-        ("amount", &info.funds[0].amount.to_string()),
-    ]))
+fn burn_receipts_msgs(
+    storage: &dyn Storage,
+    env: &Env,
+    asset: &Asset,
+) -> Result<Vec<CosmosMsg<CustomExecuteMsg>>, ContractError> {
+    let denom = match RECEIPT_DENOMS.may_load(storage, AssetInfoKey::from(&asset.info))? {
+        Some(denom) => denom,
+        None => return Ok(vec![]),
+    };
+    Ok(vec![CosmosMsg::Custom(CustomExecuteMsg::Token(
+        TokenExecuteMsg::BurnTokens {
+            denom,
+            amount: asset.amount,
+            burn_from_address: env.contract.address.to_string(),
+        },
+    ))])
 }
 
-fn unstake(deps: DepsMut, info: MessageInfo, asset: Asset) -> Result<Response, ContractError> {
-    let asset_key = AssetInfoKey::from(asset.info.clone());
-    let sender = info.sender.clone();
+// Sends receipts of the staked tokens to the user, the withdrawn stake keeps earning
+// rewards that are paid to the holder of the receipts when they are staked back
+fn withdraw_receipts(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    asset: Asset,
+) -> Result<Response<CustomExecuteMsg>, ContractError> {
+    let asset_key = AssetInfoKey::from(&asset.info);
+    let denom = RECEIPT_DENOMS
+        .may_load(deps.storage, asset_key.clone())?
+        .ok_or_else(|| ContractError::ReceiptDenomNotSet(asset.info.to_string()))?;
     if asset.amount.is_zero() {
         return Err(ContractError::AmountCannotBeZero {});
     }
+    let config = CONFIG.load(deps.storage)?;
+    let mut sent = Uint128::zero();
+    for coin in &info.funds {
+        if coin.denom != config.reward_denom {
+            return Err(ContractError::InvalidBuyInFunds(config.reward_denom));
+        }
+        sent += coin.amount;
+    }
+    checkpoint_reward_stream(deps.storage, &env, asset_key.clone())?;
+    settle_rewards(deps.storage, info.sender.clone(), &asset.info)?;
 
-    let rewards = _claim_reward(deps.storage, sender.clone(), asset.info.clone())?;
-    if !rewards.is_zero() {
-        UNCLAIMED_REWARDS.update(
+    // New receipts buy into the rewards owed to the issued ones, so that the holders
+    // of the issued receipts are not diluted. The buy-in is paid with the unclaimed
+    // rewards of the user and then with the reward tokens sent along
+    let (issued, owed) = settle_receipt_rewards(deps.storage, asset_key.clone())?;
+    let buy_in = if issued.is_zero() {
+        Uint128::zero()
+    } else {
+        owed.mul_ceil((asset.amount, issued))
+    };
+    let unclaimed_rewards = UNCLAIMED_REWARDS
+        .may_load(deps.storage, (info.sender.clone(), asset_key.clone()))?
+        .unwrap_or_default();
+    let paid_with_rewards = buy_in.min(unclaimed_rewards);
+    let paid_with_funds = buy_in - paid_with_rewards;
+    if sent < paid_with_funds {
+        return Err(ContractError::InsufficientRewards {
+            missing: paid_with_funds - sent,
+        });
+    }
+    decrease_unlocked_balance(deps.storage, info.sender.clone(), &asset)?;
+    if !paid_with_rewards.is_zero() {
+        UNCLAIMED_REWARDS.save(
             deps.storage,
-            (sender.clone(), asset_key.clone()),
-            |balance| -> Result<_, ContractError> {
-                Ok(balance.unwrap_or(Uint128::zero()) + rewards)
-            },
+            (info.sender.clone(), asset_key.clone()),
+            &(unclaimed_rewards - paid_with_rewards),
         )?;
     }
+    // The reward tokens sent are owed to the receipts from now on
+    if !paid_with_funds.is_zero() {
+        let reserved_rewards = RESERVED_REWARDS.may_load(deps.storage)?.unwrap_or_default();
+        RESERVED_REWARDS.save(
+            deps.storage,
+            &reserved_rewards.checked_add(paid_with_funds)?,
+        )?;
+    }
+    RECEIPT_REWARDS.save(deps.storage, asset_key.clone(), &(owed + buy_in))?;
+    ISSUED_RECEIPTS.save(deps.storage, asset_key, &(issued + asset.amount))?;
+    let receipts = Asset::native(denom, asset.amount);
+    let mut msgs = vec![transfer_msg(&receipts, &info.sender)?];
+    let refund = Asset::native(config.reward_denom, sent - paid_with_funds);
+    if !refund.amount.is_zero() {
+        msgs.push(transfer_msg(&refund, &info.sender)?);
+    }
+
+    Ok(Response::new()
+        .add_attributes(vec![
+            ("action", "withdraw_receipts"),
+            ("user", info.sender.as_ref()),
+            ("asset", &asset.info.to_string()),
+            ("amount", &asset.amount.to_string()),
+            ("buy_in", &buy_in.to_string()),
+        ])
+        .add_messages(msgs))
+}
+
+// Settles the rewards of the user before adding the asset to their balance
+fn increase_stake(
+    storage: &mut dyn Storage,
+    env: &Env,
+    user: Addr,
+    asset: &Asset,
+) -> Result<(), ContractError> {
+    let asset_key = AssetInfoKey::from(&asset.info);
+    checkpoint_reward_stream(storage, env, asset_key.clone())?;
+
+    settle_rewards(storage, user.clone(), &asset.info)?;
 
     BALANCES.update(
-        deps.storage,
-        (sender, asset_key.clone()),
+        storage,
+        (user.clone(), asset_key.clone()),
         |balance| -> Result<_, ContractError> {
             match balance {
-                Some(balance) => {
-                    if balance < asset.amount {
-                        return Err(ContractError::InsufficientBalance {});
-                    }
-                    Ok(balance - asset.amount)
-                }
-                None => Err(ContractError::AssetNotStaked {}),
+                Some(balance) => Ok(balance + asset.amount),
+                None => Ok(asset.amount),
             }
         },
     )?;
+    TOTAL_BALANCES.update(
+        storage,
+        asset_key.clone(),
+        |balance| -> Result<_, ContractError> {
+            Ok(balance.unwrap_or(Uint128::zero()) + asset.amount)
+        },
+    )?;
+
+    let asset_reward_index = ASSET_REWARD_INDEX
+        .load(storage, asset_key.clone())
+        .unwrap_or_default();
+    save_user_reward_index(storage, (user, asset_key), &asset_reward_index)?;
+    Ok(())
+}
+
+fn unstake(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    asset: Asset,
+) -> Result<Response<CustomExecuteMsg>, ContractError> {
+    let asset_key = AssetInfoKey::from(asset.info.clone());
+    let sender = info.sender.clone();
+    if asset.amount.is_zero() {
+        return Err(ContractError::AmountCannotBeZero {});
+    }
+    checkpoint_reward_stream(deps.storage, &env, asset_key.clone())?;
+
+    settle_rewards(deps.storage, sender.clone(), &asset.info)?;
+
+    decrease_unlocked_balance(deps.storage, sender, &asset)?;
     TOTAL_BALANCES.update(
         deps.storage,
         asset_key,
@@ -254,7 +666,8 @@ fn unstake(deps: DepsMut, info: MessageInfo, asset: Asset) -> Result<Response, C
         },
     )?;
 
-    let msg = asset.transfer_msg(&info.sender)?;
+    let msg = transfer_msg(&asset, &info.sender)?;
+    let receipt_msgs = burn_receipts_msgs(deps.storage, &env, &asset)?;
 
     Ok(Response::new()
         .add_attributes(vec![
@@ -263,16 +676,108 @@ fn unstake(deps: DepsMut, info: MessageInfo, asset: Asset) -> Result<Response, C
             ("asset", &asset.info.to_string()),
             ("amount", &asset.amount.to_string()),
         ])
-        .add_message(msg))
+        .add_message(msg)
+        .add_messages(receipt_msgs))
+}
+
+fn transfer_stake(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    params: TransferStakeParams,
+) -> Result<Response<CustomExecuteMsg>, ContractError> {
+    let asset = params.asset;
+    if asset.amount.is_zero() {
+        return Err(ContractError::AmountCannotBeZero {});
+    }
+    let asset_key = AssetInfoKey::from(&asset.info);
+    let recipient = deps.api.addr_validate(&params.recipient)?;
+    let owner = match params.owner {
+        Some(owner) => deps.api.addr_validate(&owner)?,
+        None => info.sender.clone(),
+    };
+    if owner != info.sender {
+        let allowance_key = (owner.clone(), info.sender.clone(), asset_key.clone());
+        let allowance = STAKE_ALLOWANCES
+            .may_load(deps.storage, allowance_key.clone())?
+            .unwrap_or_default();
+        if allowance < asset.amount {
+            return Err(ContractError::InsufficientAllowance {});
+        }
+        if allowance == asset.amount {
+            STAKE_ALLOWANCES.remove(deps.storage, allowance_key);
+        } else {
+            STAKE_ALLOWANCES.save(deps.storage, allowance_key, &(allowance - asset.amount))?;
+        }
+    }
+    checkpoint_reward_stream(deps.storage, &env, asset_key.clone())?;
+
+    // Both parties keep the rewards accrued until the transfer
+    settle_rewards(deps.storage, owner.clone(), &asset.info)?;
+    settle_rewards(deps.storage, recipient.clone(), &asset.info)?;
+
+    decrease_unlocked_balance(deps.storage, owner.clone(), &asset)?;
+    BALANCES.update(
+        deps.storage,
+        (recipient.clone(), asset_key.clone()),
+        |balance| -> Result<_, ContractError> {
+            Ok(balance.unwrap_or(Uint128::zero()) + asset.amount)
+        },
+    )?;
+    let asset_reward_index = ASSET_REWARD_INDEX
+        .load(deps.storage, asset_key.clone())
+        .unwrap_or_default();
+    save_user_reward_index(
+        deps.storage,
+        (recipient.clone(), asset_key),
+        &asset_reward_index,
+    )?;
+
+    Ok(Response::new().add_attributes(vec![
+        ("action", "transfer_stake"),
+        ("sender", info.sender.as_ref()),
+        ("owner", owner.as_ref()),
+        ("recipient", recipient.as_ref()),
+        ("asset", &asset.info.to_string()),
+        ("amount", &asset.amount.to_string()),
+    ]))
+}
+
+// Replaces the allowance of the spender, a zero amount revokes it
+fn set_stake_allowance(
+    deps: DepsMut,
+    info: MessageInfo,
+    params: StakeAllowanceParams,
+) -> Result<Response<CustomExecuteMsg>, ContractError> {
+    let spender = deps.api.addr_validate(&params.spender)?;
+    let key = (
+        info.sender.clone(),
+        spender.clone(),
+        AssetInfoKey::from(&params.asset.info),
+    );
+    if params.asset.amount.is_zero() {
+        STAKE_ALLOWANCES.remove(deps.storage, key);
+    } else {
+        STAKE_ALLOWANCES.save(deps.storage, key, &params.asset.amount)?;
+    }
+    Ok(Response::new().add_attributes(vec![
+        ("action", "set_stake_allowance"),
+        ("owner", info.sender.as_ref()),
+        ("spender", spender.as_ref()),
+        ("asset", &params.asset.info.to_string()),
+        ("amount", &params.asset.amount.to_string()),
+    ]))
 }
 
 fn claim_rewards(
     deps: DepsMut,
+    env: Env,
     info: MessageInfo,
     asset: AssetInfo,
-) -> Result<Response, ContractError> {
+) -> Result<Response<CustomExecuteMsg>, ContractError> {
     let user = info.sender;
     let config = CONFIG.load(deps.storage)?;
+    checkpoint_reward_stream(deps.storage, &env, AssetInfoKey::from(&asset))?;
     let rewards = _claim_reward(deps.storage, user.clone(), asset.clone())?;
     let unclaimed_rewards = UNCLAIMED_REWARDS
         .load(
@@ -285,6 +790,12 @@ fn claim_rewards(
         deps.storage,
         (user.clone(), AssetInfoKey::from(asset.clone())),
     );
+    // Claimed rewards are no longer reserved
+    let reserved_rewards = RESERVED_REWARDS.may_load(deps.storage)?.unwrap_or_default();
+    RESERVED_REWARDS.save(
+        deps.storage,
+        &reserved_rewards.saturating_sub(final_rewards),
+    )?;
     let response = Response::new().add_attributes(vec![
         ("action", "claim_rewards"),
         ("user", user.as_ref()),
@@ -296,74 +807,289 @@ fn claim_rewards(
             info: AssetInfo::Native(config.reward_denom),
             amount: final_rewards,
         };
-        Ok(response.add_message(rewards_asset.transfer_msg(&user)?))
+        Ok(response.add_message(transfer_msg(&rewards_asset, &user)?))
     } else {
         Ok(response)
     }
 }
 
-fn _claim_reward(
-    storage: &mut dyn Storage,
-    user: Addr,
-    asset: AssetInfo,
-) -> Result<Uint128, ContractError> {
-    let asset_key = AssetInfoKey::from(&asset);
-    // If the user did not stake the asset, do nothing and return 0
-    if BALANCES
-        .load(storage, (user.clone(), asset_key.clone()))
-        .is_err()
-    {
-        return Ok(Uint128::zero());
+// Expired positions can be unlocked by anyone so that they stop being boosted,
+// there is no early unlock so the boost is only earned by keeping the lock
+fn unlock(
+    deps: DepsMut,
+    env: Env,
+    params: UnlockParams,
+) -> Result<Response<CustomExecuteMsg>, ContractError> {
+    let user = deps.api.addr_validate(&params.address)?;
+    let asset_key = AssetInfoKey::from(&params.asset);
+    let position_key = (user.clone(), asset_key.clone(), params.duration);
+    let position = LOCKED_POSITIONS
+        .may_load(deps.storage, position_key.clone())?
+        .ok_or(ContractError::LockedPositionNotFound {})?;
+    if env.block.time < position.unlock_time {
+        return Err(ContractError::PositionLocked(position.unlock_time));
     }
+    // The boost of the position already expired at its unlock time
+    checkpoint_reward_stream(deps.storage, &env, asset_key)?;
+    settle_rewards(deps.storage, user.clone(), &params.asset)?;
+    LOCKED_POSITIONS.remove(deps.storage, position_key);
 
-    let user_reward_rate = USER_ASSET_REWARD_RATE.load(storage, (user.clone(), asset_key.clone()));
-    let asset_reward_rate = ASSET_REWARD_RATE.load(storage, asset_key.clone())?;
-
-    if let Ok(user_reward_rate) = user_reward_rate {
-        let user_staked = BALANCES.load(storage, (user.clone(), asset_key.clone()))?;
-        let rewards = ((asset_reward_rate - user_reward_rate)
-            * Decimal::from_atomics(user_staked, 0)?)
-        .to_uint_floor();
-        if rewards.is_zero() {
-            Ok(Uint128::zero())
-        } else {
-            USER_ASSET_REWARD_RATE.save(storage, (user, asset_key), &asset_reward_rate)?;
-            Ok(rewards)
-        }
+    Ok(Response::new().add_attributes(vec![
+        ("action", "unlock"),
+        ("user", user.as_ref()),
+        ("asset", &params.asset.to_string()),
+        ("amount", &position.amount.to_string()),
+        ("duration", &params.duration.to_string()),
+    ]))
+}
+
+// Positions keep the multiplier of their tier until they are locked again
+fn update_lock_tiers(
+    deps: DepsMut,
+    info: MessageInfo,
+    tiers: Vec<LockTier>,
+) -> Result<Response<CustomExecuteMsg>, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    is_governance(&info, &config)?;
+    validate_lock_tiers(&tiers)?;
+    LOCK_TIERS.save(deps.storage, &tiers)?;
+    let durations = tiers
+        .iter()
+        .map(|tier| tier.duration.to_string())
+        .collect::<Vec<String>>()
+        .join(",");
+    Ok(Response::new().add_attributes(vec![
+        ("action", "update_lock_tiers"),
+        ("durations", &durations),
+    ]))
+}
+
+// Moves the rewards accrued by the user to the unclaimed rewards, it must be
+// called before the effective balance of the user changes
+fn settle_rewards(
+    storage: &mut dyn Storage,
+    user: Addr,
+    asset: &AssetInfo,
+) -> Result<(), ContractError> {
+    let rewards = _claim_reward(storage, user.clone(), asset.clone())?;
+    if !rewards.is_zero() {
+        UNCLAIMED_REWARDS.update(
+            storage,
+            (user, AssetInfoKey::from(asset)),
+            |balance| -> Result<_, ContractError> {
+                Ok(balance.unwrap_or(Uint128::zero()) + rewards)
+            },
+        )?;
+    }
+    Ok(())
+}
+
+fn _claim_reward(
+    storage: &mut dyn Storage,
+    user: Addr,
+    asset: AssetInfo,
+) -> Result<Uint128, ContractError> {
+    let asset_key = AssetInfoKey::from(&asset);
+    // If the user did not stake the asset, do nothing and return 0
+    if BALANCES
+        .load(storage, (user.clone(), asset_key.clone()))
+        .is_err()
+    {
+        return Ok(Uint128::zero());
+    }
+
+    let user_reward_index = load_user_reward_index(storage, (user.clone(), asset_key.clone()))?;
+    let asset_reward_index = ASSET_REWARD_INDEX.load(storage, asset_key.clone())?;
+
+    if let Some(user_reward_index) = user_reward_index {
+        let accrual = accrue_user_rewards(
+            storage,
+            (user.clone(), asset_key.clone()),
+            user_reward_index,
+            asset_reward_index,
+            |unlock_time| EXPIRED_LOCK_INDEXES.may_load(storage, (asset_key.clone(), unlock_time)),
+        )?;
+        for (duration, position) in accrual.expired {
+            expire_user_boost(
+                storage,
+                (user.clone(), asset_key.clone()),
+                duration,
+                position,
+            )?;
+        }
+        // The fraction of a token that cannot be paid out is kept as dust of the asset
+        add_dust(storage, asset_key.clone(), accrual.fraction)?;
+        save_user_reward_index(storage, (user, asset_key), &asset_reward_index)?;
+        Ok(accrual.rewards)
     } else {
-        // If cannot find user_reward_rate, assume this is the first time they are staking and set it to the current asset_reward_rate
-        USER_ASSET_REWARD_RATE.save(storage, (user, asset_key), &asset_reward_rate)?;
+        // If cannot find user_reward_index, assume this is the first time they are staking and set it to the current asset_reward_index
+        save_user_reward_index(storage, (user, asset_key), &asset_reward_index)?;
 
         Ok(Uint128::zero())
     }
 }
 
+// Adds the rewards accrued by the issued receipts to the rewards they are owed,
+// returns the issued receipts and their rewards
+fn settle_receipt_rewards(
+    storage: &mut dyn Storage,
+    asset_key: AssetInfoKey,
+) -> Result<(Uint128, Uint128), ContractError> {
+    let issued = ISSUED_RECEIPTS
+        .may_load(storage, asset_key.clone())?
+        .unwrap_or_default();
+    let owed = RECEIPT_REWARDS
+        .may_load(storage, asset_key.clone())?
+        .unwrap_or_default();
+    let asset_reward_index = ASSET_REWARD_INDEX
+        .may_load(storage, asset_key.clone())?
+        .unwrap_or_default();
+    let receipt_reward_index = RECEIPT_REWARD_INDEX
+        .may_load(storage, asset_key.clone())?
+        .unwrap_or(asset_reward_index);
+    let (rewards, fraction) = accrued_rewards(asset_reward_index, receipt_reward_index, issued)?;
+    add_dust(storage, asset_key.clone(), fraction)?;
+    RECEIPT_REWARD_INDEX.save(storage, asset_key, &asset_reward_index)?;
+    Ok((issued, owed + rewards))
+}
+
+fn add_dust(
+    storage: &mut dyn Storage,
+    asset_key: AssetInfoKey,
+    amount: Decimal,
+) -> Result<(), ContractError> {
+    if !amount.is_zero() {
+        DUST.update(storage, asset_key, |dust| -> StdResult<_> {
+            Ok(dust.unwrap_or_default() + amount)
+        })?;
+    }
+    Ok(())
+}
+
+// Increases the reward index of the asset with the rewards split among its stakers
+fn distribute_rewards(
+    storage: &mut dyn Storage,
+    asset_key: AssetInfoKey,
+    rewards: Decimal,
+) -> Result<(), ContractError> {
+    let total_balance = total_effective_balance(storage, asset_key.clone())?;
+    if total_balance.is_zero() {
+        return add_dust(storage, asset_key, rewards);
+    }
+    let (increase, remainder) = index_increase(rewards, total_balance)?;
+    add_dust(storage, asset_key.clone(), remainder)?;
+    if !increase.is_zero() {
+        ASSET_REWARD_INDEX.update(storage, asset_key, |index| -> StdResult<_> {
+            Ok(index.unwrap_or_default() + increase)
+        })?;
+    }
+    Ok(())
+}
+
+// Distributes the streamed rewards of the asset released up to the current block time and
+// stops boosting the positions that expired meanwhile, it must be called before the staked
+// balances of the asset change
+fn checkpoint_reward_stream(
+    storage: &mut dyn Storage,
+    env: &Env,
+    asset_key: AssetInfoKey,
+) -> Result<(), ContractError> {
+    for (unlock_time, boost) in due_lock_expiries(storage, asset_key.clone(), env.block.time)? {
+        release_reward_stream(
+            storage,
+            asset_key.clone(),
+            Timestamp::from_seconds(unlock_time),
+        )?;
+        let index = ASSET_REWARD_INDEX
+            .may_load(storage, asset_key.clone())?
+            .unwrap_or_default();
+        expire_total_boost(storage, asset_key.clone(), unlock_time, boost, index)?;
+    }
+    release_reward_stream(storage, asset_key, env.block.time)
+}
+
+fn release_reward_stream(
+    storage: &mut dyn Storage,
+    asset_key: AssetInfoKey,
+    now: Timestamp,
+) -> Result<(), ContractError> {
+    let mut stream = match REWARD_STREAMS.may_load(storage, asset_key.clone())? {
+        Some(stream) if now > stream.last_update => stream,
+        _ => return Ok(()),
+    };
+    let released = released_rewards(&stream, now);
+    stream.amount -= released;
+    stream.last_update = now;
+    if stream.amount.is_zero() {
+        REWARD_STREAMS.remove(storage, asset_key.clone());
+    } else {
+        REWARD_STREAMS.save(storage, asset_key.clone(), &stream)?;
+    }
+    distribute_rewards(storage, asset_key, released)
+}
+
+fn update_reward_stream_period(
+    deps: DepsMut,
+    info: MessageInfo,
+    seconds: u64,
+) -> Result<Response<CustomExecuteMsg>, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    is_governance(&info, &config)?;
+    REWARD_STREAM_PERIOD.save(deps.storage, &seconds)?;
+    Ok(Response::new().add_attributes(vec![
+        ("action", "update_reward_stream_period"),
+        ("seconds", &seconds.to_string()),
+    ]))
+}
+
+// Unbondings started before the update keep their completion time
+fn update_unbonding_period(
+    deps: DepsMut,
+    info: MessageInfo,
+    seconds: u64,
+) -> Result<Response<CustomExecuteMsg>, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    is_governance(&info, &config)?;
+    UNBONDING_PERIOD.save(deps.storage, &seconds)?;
+    Ok(Response::new().add_attributes(vec![
+        ("action", "update_unbonding_period"),
+        ("seconds", &seconds.to_string()),
+    ]))
+}
+
 fn alliance_delegate(
     deps: DepsMut,
     env: Env,
     info: MessageInfo,
     msg: AllianceDelegateMsg,
-) -> Result<Response, ContractError> {
+) -> Result<Response<CustomExecuteMsg>, ContractError> {
     let config = CONFIG.load(deps.storage)?;
     is_controller(&info, &config)?;
+    ensure_alliance_token_denom(&config)?;
     if msg.delegations.is_empty() {
         return Err(ContractError::EmptyDelegation {});
     }
+    // Only the tokens held by the contract can be delegated
+    let free = deps
+        .querier
+        .query_balance(env.contract.address.clone(), &config.alliance_token_denom)?
+        .amount;
+    let mut requested = Uint128::zero();
+    for delegation in &msg.delegations {
+        requested = requested.checked_add(delegation.amount)?;
+    }
+    if requested > free {
+        return Err(ContractError::InsufficientBalance {});
+    }
+    let allowed_validators = ALLOWED_VALIDATORS
+        .may_load(deps.storage)?
+        .unwrap_or_default();
     let mut validators = VALIDATORS.load(deps.storage)?;
-    let mut msgs: Vec<CosmosMsg<Empty>> = vec![];
+    let mut msgs: Vec<CosmosMsg<CustomExecuteMsg>> = vec![];
     for delegation in msg.delegations {
-        let delegate_msg = MsgDelegate {
-            amount: Some(Coin {
-                denom: config.alliance_token_denom.clone(),
-                amount: delegation.amount.to_string(),
-            }),
-            delegator_address: env.contract.address.to_string(),
-            validator_address: delegation.validator.to_string(),
-        };
-        msgs.push(CosmosMsg::Stargate {
-            type_url: "/alliance.alliance.MsgDelegate".to_string(),
-            value: Binary::from(delegate_msg.encode_to_vec()),
-        });
+        is_allowed_validator(&allowed_validators, &delegation.validator)?;
+        msgs.push(delegate_msg(&env, &config, &delegation));
+        increase_delegation(deps.storage, &delegation.validator, delegation.amount)?;
         validators.insert(delegation.validator);
     }
     VALIDATORS.save(deps.storage, &validators)?;
@@ -377,27 +1103,19 @@ fn alliance_undelegate(
     env: Env,
     info: MessageInfo,
     msg: AllianceUndelegateMsg,
-) -> Result<Response, ContractError> {
+) -> Result<Response<CustomExecuteMsg>, ContractError> {
     let config = CONFIG.load(deps.storage)?;
     is_controller(&info, &config)?;
+    ensure_alliance_token_denom(&config)?;
     if msg.undelegations.is_empty() {
         return Err(ContractError::EmptyDelegation {});
     }
+    remove_completed_unbondings(deps.storage, &env)?;
     let mut msgs = vec![];
     for delegation in msg.undelegations {
-        let undelegate_msg = MsgUndelegate {
-            amount: Some(Coin {
-                denom: config.alliance_token_denom.clone(),
-                amount: delegation.amount.to_string(),
-            }),
-            delegator_address: env.contract.address.to_string(),
-            validator_address: delegation.validator.to_string(),
-        };
-        let msg = CosmosMsg::Stargate {
-            type_url: "/alliance.alliance.MsgUndelegate".to_string(),
-            value: Binary::from(undelegate_msg.encode_to_vec()),
-        };
-        msgs.push(msg);
+        msgs.push(undelegate_msg(&env, &config, &delegation));
+        decrease_delegation(deps.storage, &delegation.validator, delegation.amount)?;
+        add_unbonding(deps.storage, &env, &delegation.validator, delegation.amount)?;
     }
     Ok(Response::new()
         .add_attributes(vec![("action", "alliance_undelegate")])
@@ -409,32 +1127,32 @@ fn alliance_redelegate(
     env: Env,
     info: MessageInfo,
     msg: AllianceRedelegateMsg,
-) -> Result<Response, ContractError> {
+) -> Result<Response<CustomExecuteMsg>, ContractError> {
     let config = CONFIG.load(deps.storage)?;
     is_controller(&info, &config)?;
+    ensure_alliance_token_denom(&config)?;
     if msg.redelegations.is_empty() {
         return Err(ContractError::EmptyDelegation {});
     }
+    let allowed_validators = ALLOWED_VALIDATORS
+        .may_load(deps.storage)?
+        .unwrap_or_default();
     let mut msgs = vec![];
     let mut validators = VALIDATORS.load(deps.storage)?;
     for redelegation in msg.redelegations {
-        let src_validator = redelegation.src_validator;
-        let dst_validator = redelegation.dst_validator;
-        let redelegate_msg = MsgRedelegate {
-            amount: Some(Coin {
-                denom: config.alliance_token_denom.clone(),
-                amount: redelegation.amount.to_string(),
-            }),
-            delegator_address: env.contract.address.to_string(),
-            validator_src_address: src_validator.to_string(),
-            validator_dst_address: dst_validator.to_string(),
-        };
-        let msg = CosmosMsg::Stargate {
-            type_url: "/alliance.alliance.MsgRedelegate".to_string(),
-            value: Binary::from(redelegate_msg.encode_to_vec()),
-        };
-        msgs.push(msg);
-        validators.insert(dst_validator);
+        is_allowed_validator(&allowed_validators, &redelegation.dst_validator)?;
+        msgs.push(redelegate_msg(&env, &config, &redelegation));
+        decrease_delegation(
+            deps.storage,
+            &redelegation.src_validator,
+            redelegation.amount,
+        )?;
+        increase_delegation(
+            deps.storage,
+            &redelegation.dst_validator,
+            redelegation.amount,
+        )?;
+        validators.insert(redelegation.dst_validator);
     }
     VALIDATORS.save(deps.storage, &validators)?;
     Ok(Response::new()
@@ -442,25 +1160,232 @@ fn alliance_redelegate(
         .add_messages(msgs))
 }
 
-fn update_rewards(deps: DepsMut, env: Env, info: MessageInfo) -> Result<Response, ContractError> {
+fn update_delegation_strategy(
+    deps: DepsMut,
+    info: MessageInfo,
+    strategy: DelegationStrategy,
+) -> Result<Response<CustomExecuteMsg>, ContractError> {
     let config = CONFIG.load(deps.storage)?;
+    if is_governance(&info, &config).is_err() {
+        is_controller(&info, &config)?;
+    }
+    validate_strategy(&strategy)?;
+    let allowed_validators = ALLOWED_VALIDATORS
+        .may_load(deps.storage)?
+        .unwrap_or_default();
+    for target in &strategy.targets {
+        is_allowed_validator(&allowed_validators, &target.validator)?;
+    }
+    DELEGATION_STRATEGY.save(deps.storage, &strategy)?;
+    Ok(Response::new().add_attributes(vec![("action", "update_delegation_strategy")]))
+}
 
-    let reward_sent_in_tx: Option<&CwCoin> =
-        info.funds.iter().find(|c| c.denom == config.reward_denom);
-    let sent_balance = if let Some(coin) = reward_sent_in_tx {
-        coin.amount
-    } else {
-        Uint128::zero()
+// Anyone can move the alliance tokens towards the delegation strategy targets
+fn rebalance_delegations(
+    deps: DepsMut,
+    env: Env,
+) -> Result<Response<CustomExecuteMsg>, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    ensure_alliance_token_denom(&config)?;
+    let strategy = DELEGATION_STRATEGY
+        .may_load(deps.storage)?
+        .ok_or(ContractError::DelegationStrategyNotSet {})?;
+    let current = DELEGATIONS
+        .range(deps.storage, None, None, Order::Ascending)
+        .collect::<StdResult<BTreeMap<String, Uint128>>>()?;
+    remove_completed_unbondings(deps.storage, &env)?;
+    let unbonding = UNBONDINGS
+        .range(deps.storage, None, None, Order::Ascending)
+        .map(|item| item.map(|(_, amount)| amount))
+        .sum::<StdResult<Uint128>>()?;
+    let available = deps
+        .querier
+        .query_balance(env.contract.address.clone(), &config.alliance_token_denom)?
+        .amount;
+    let plan = compute_rebalance(&strategy, &current, unbonding, available)?;
+
+    // Validators can be removed from the allowlist after the targets are set
+    let allowed_validators = ALLOWED_VALIDATORS
+        .may_load(deps.storage)?
+        .unwrap_or_default();
+    let mut msgs = vec![];
+    let mut validators = VALIDATORS.load(deps.storage)?;
+    for redelegation in plan.redelegations {
+        is_allowed_validator(&allowed_validators, &redelegation.dst_validator)?;
+        msgs.push(redelegate_msg(&env, &config, &redelegation));
+        decrease_delegation(
+            deps.storage,
+            &redelegation.src_validator,
+            redelegation.amount,
+        )?;
+        increase_delegation(
+            deps.storage,
+            &redelegation.dst_validator,
+            redelegation.amount,
+        )?;
+        validators.insert(redelegation.dst_validator);
+    }
+    for undelegation in plan.undelegations {
+        msgs.push(undelegate_msg(&env, &config, &undelegation));
+        decrease_delegation(deps.storage, &undelegation.validator, undelegation.amount)?;
+        add_unbonding(
+            deps.storage,
+            &env,
+            &undelegation.validator,
+            undelegation.amount,
+        )?;
+    }
+    for delegation in plan.delegations {
+        is_allowed_validator(&allowed_validators, &delegation.validator)?;
+        msgs.push(delegate_msg(&env, &config, &delegation));
+        increase_delegation(deps.storage, &delegation.validator, delegation.amount)?;
+        validators.insert(delegation.validator);
+    }
+    VALIDATORS.save(deps.storage, &validators)?;
+    Ok(Response::new()
+        .add_attributes(vec![("action", "rebalance_delegations")])
+        .add_messages(msgs))
+}
+
+fn delegate_msg(
+    env: &Env,
+    config: &Config,
+    delegation: &AllianceDelegation,
+) -> CosmosMsg<CustomExecuteMsg> {
+    let delegate_msg = MsgDelegate {
+        amount: Some(Coin {
+            denom: config.alliance_token_denom.clone(),
+            amount: delegation.amount.to_string(),
+        }),
+        delegator_address: env.contract.address.to_string(),
+        validator_address: delegation.validator.to_string(),
+    };
+    CosmosMsg::Stargate {
+        type_url: "/alliance.alliance.MsgDelegate".to_string(),
+        value: Binary::from(delegate_msg.encode_to_vec()),
+    }
+}
+
+fn undelegate_msg(
+    env: &Env,
+    config: &Config,
+    undelegation: &AllianceDelegation,
+) -> CosmosMsg<CustomExecuteMsg> {
+    let undelegate_msg = MsgUndelegate {
+        amount: Some(Coin {
+            denom: config.alliance_token_denom.clone(),
+            amount: undelegation.amount.to_string(),
+        }),
+        delegator_address: env.contract.address.to_string(),
+        validator_address: undelegation.validator.to_string(),
     };
-    let reward_asset = AssetInfo::native(config.reward_denom.clone());
-    let contract_balance =
-        reward_asset.query_balance(&deps.querier, env.contract.address.clone())?;
+    CosmosMsg::Stargate {
+        type_url: "/alliance.alliance.MsgUndelegate".to_string(),
+        value: Binary::from(undelegate_msg.encode_to_vec()),
+    }
+}
+
+fn redelegate_msg(
+    env: &Env,
+    config: &Config,
+    redelegation: &AllianceRedelegation,
+) -> CosmosMsg<CustomExecuteMsg> {
+    let redelegate_msg = MsgRedelegate {
+        amount: Some(Coin {
+            denom: config.alliance_token_denom.clone(),
+            amount: redelegation.amount.to_string(),
+        }),
+        delegator_address: env.contract.address.to_string(),
+        validator_src_address: redelegation.src_validator.to_string(),
+        validator_dst_address: redelegation.dst_validator.to_string(),
+    };
+    CosmosMsg::Stargate {
+        type_url: "/alliance.alliance.MsgRedelegate".to_string(),
+        value: Binary::from(redelegate_msg.encode_to_vec()),
+    }
+}
+
+fn increase_delegation(
+    storage: &mut dyn Storage,
+    validator: &str,
+    amount: Uint128,
+) -> Result<(), ContractError> {
+    let delegated = DELEGATIONS
+        .may_load(storage, validator)?
+        .unwrap_or_default();
+    DELEGATIONS.save(storage, validator, &delegated.checked_add(amount)?)?;
+    Ok(())
+}
+
+fn add_unbonding(
+    storage: &mut dyn Storage,
+    env: &Env,
+    validator: &str,
+    amount: Uint128,
+) -> Result<(), ContractError> {
+    let unbonding_period = UNBONDING_PERIOD.load(storage)?;
+    let completion_time = env.block.time.plus_seconds(unbonding_period).seconds();
+    UNBONDINGS.update(
+        storage,
+        (completion_time, validator),
+        |unbonding| -> Result<_, ContractError> {
+            Ok(unbonding.unwrap_or_default().checked_add(amount)?)
+        },
+    )?;
+    Ok(())
+}
+
+// Completed unbondings are already part of the contract balance
+fn remove_completed_unbondings(storage: &mut dyn Storage, env: &Env) -> StdResult<()> {
+    let completed = UNBONDINGS
+        .prefix_range(
+            storage,
+            None,
+            Some(PrefixBound::inclusive(env.block.time.seconds())),
+            Order::Ascending,
+        )
+        .map(|item| item.map(|(key, _)| key))
+        .collect::<StdResult<Vec<(u64, String)>>>()?;
+    for (completion_time, validator) in completed {
+        UNBONDINGS.remove(storage, (completion_time, &validator));
+    }
+    Ok(())
+}
+
+fn decrease_delegation(
+    storage: &mut dyn Storage,
+    validator: &str,
+    amount: Uint128,
+) -> Result<(), ContractError> {
+    let delegated = DELEGATIONS
+        .may_load(storage, validator)?
+        .unwrap_or_default();
+    let delegated =
+        delegated
+            .checked_sub(amount)
+            .map_err(|_| ContractError::InsufficientDelegation {
+                validator: validator.to_string(),
+                delegated,
+            })?;
+    if delegated.is_zero() {
+        DELEGATIONS.remove(storage, validator);
+    } else {
+        DELEGATIONS.save(storage, validator, &delegated)?;
+    }
+    Ok(())
+}
+
+fn update_rewards(
+    deps: DepsMut,
+    env: Env,
+    _info: MessageInfo,
+) -> Result<Response<CustomExecuteMsg>, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    ensure_alliance_token_denom(&config)?;
 
-    // Contract balance is guaranteed to be greater than sent balance
-    // since contract balance = previous contract balance + sent balance > sent balance
-    TEMP_BALANCE.save(deps.storage, &(contract_balance - sent_balance))?;
     let validators = VALIDATORS.load(deps.storage)?;
-    let sub_msgs: Vec<SubMsg> = validators
+    PENDING_CLAIMS.save(deps.storage, &validators.iter().cloned().collect())?;
+    let sub_msgs: Vec<SubMsg<CustomExecuteMsg>> = validators
         .iter()
         .map(|v| {
             let msg = MsgClaimDelegationRewards {
@@ -472,8 +1397,9 @@ fn update_rewards(deps: DepsMut, env: Env, info: MessageInfo) -> Result<Response
                 type_url: "/alliance.alliance.MsgClaimDelegationRewards".to_string(),
                 value: Binary::from(msg.encode_to_vec()),
             };
-            // Reply on error here is used to ignore errors from claiming rewards with validators that we did not delegate to
-            SubMsg::reply_on_error(msg, CLAIM_REWARD_ERROR_REPLY_ID)
+            // Errors from claiming rewards are recorded in the reply instead of failing the update,
+            // every claim replies so that the reply can be matched with its validator
+            SubMsg::reply_always(msg, CLAIM_REWARD_REPLY_ID)
         })
         .collect();
     let msg = CosmosMsg::Wasm(WasmMsg::Execute {
@@ -492,54 +1418,208 @@ fn update_reward_callback(
     deps: DepsMut,
     env: Env,
     info: MessageInfo,
-) -> Result<Response, ContractError> {
+) -> Result<Response<CustomExecuteMsg>, ContractError> {
     if info.sender != env.contract.address {
         return Err(ContractError::Unauthorized {});
     }
     let config = CONFIG.load(deps.storage)?;
     let reward_asset = AssetInfo::native(config.reward_denom);
-    let current_balance = reward_asset.query_balance(&deps.querier, env.contract.address)?;
-    let previous_balance = TEMP_BALANCE.load(deps.storage)?;
-    let rewards_collected = current_balance - previous_balance;
+    let current_balance = reward_asset.query_balance(&deps.querier, &env.contract.address)?;
+    // Rewards are the reward denom held on top of the staked principal and the rewards
+    // already distributed to stakers, so tokens staked or claimed in between are not counted
+    let principal = TOTAL_BALANCES
+        .may_load(deps.storage, AssetInfoKey::from(&reward_asset))?
+        .unwrap_or_default();
+    let reserved_rewards = RESERVED_REWARDS.may_load(deps.storage)?.unwrap_or_default();
+    let mut rewards_collected =
+        current_balance.saturating_sub(principal.checked_add(reserved_rewards)?);
 
     let asset_reward_distribution = ASSET_REWARD_DISTRIBUTION.load(deps.storage)?;
     let total_distribution = asset_reward_distribution
         .iter()
         .map(|a| a.distribution)
         .fold(Decimal::zero(), |acc, v| acc + v);
+    // Without a distribution the rewards are not reserved and are collected by the next update
+    if total_distribution.is_zero() {
+        return Ok(Response::new()
+            .add_attribute("action", "update_rewards_callback")
+            .add_attribute("rewards_kept", rewards_collected.to_string()));
+    }
+
+    // The protocol fee is taken before the rewards are distributed to the assets
+    let mut fee_msgs = vec![];
+    if let Some(protocol_fee) = PROTOCOL_FEE.may_load(deps.storage)? {
+        let fee = Asset {
+            info: reward_asset.clone(),
+            amount: rewards_collected.mul_floor(protocol_fee.take_rate),
+        };
+        if !fee.amount.is_zero() {
+            rewards_collected -= fee.amount;
+            let fees_collected = FEES_COLLECTED.may_load(deps.storage)?.unwrap_or_default();
+            FEES_COLLECTED.save(deps.storage, &fees_collected.checked_add(fee.amount)?)?;
+            fee_msgs.push(transfer_msg(&fee, &protocol_fee.fee_collector)?);
+        }
+    }
 
+    // Without a dust recipient the dust of each asset is carried forward to its stakers
+    let carry_dust = DUST_RECIPIENT.may_load(deps.storage)?.is_none();
+    let stream_period = REWARD_STREAM_PERIOD
+        .may_load(deps.storage)?
+        .unwrap_or_default();
     for asset_distribution in asset_reward_distribution {
         let asset_key = AssetInfoKey::from(asset_distribution.asset);
-        let total_reward_distributed = Decimal::from_atomics(rewards_collected, 0)?
+        let mut total_reward_distributed = Decimal::from_atomics(rewards_collected, 0)?
             * asset_distribution.distribution
             / total_distribution;
+        checkpoint_reward_stream(deps.storage, &env, asset_key.clone())?;
 
-        // If there are no balances, we stop updating the rate. This means that the emissions are not directed to any stakers.
+        // If there are no balances the rewards are not directed to any stakers and kept as dust
         let total_balance = TOTAL_BALANCES
             .load(deps.storage, asset_key.clone())
             .unwrap_or(Uint128::zero());
-        if !total_balance.is_zero() {
-            let rate_to_update =
-                total_reward_distributed / Decimal::from_atomics(total_balance, 0)?;
-            if rate_to_update > Decimal::zero() {
-                ASSET_REWARD_RATE.update(
-                    deps.storage,
-                    asset_key.clone(),
-                    |rate| -> StdResult<_> { Ok(rate.unwrap_or(Decimal::zero()) + rate_to_update) },
-                )?;
+        if total_balance.is_zero() {
+            add_dust(deps.storage, asset_key, total_reward_distributed)?;
+            continue;
+        }
+        if carry_dust {
+            if let Some(dust) = DUST.may_load(deps.storage, asset_key.clone())? {
+                total_reward_distributed += dust;
+                DUST.remove(deps.storage, asset_key.clone());
             }
         }
+        if stream_period == 0 {
+            distribute_rewards(deps.storage, asset_key, total_reward_distributed)?;
+            continue;
+        }
+        // The rewards that are still streamed are released along with the new ones
+        let mut stream = REWARD_STREAMS
+            .may_load(deps.storage, asset_key.clone())?
+            .unwrap_or(RewardStream {
+                amount: Decimal::zero(),
+                last_update: env.block.time,
+                end: env.block.time,
+            });
+        stream.amount += total_reward_distributed;
+        stream.end = env.block.time.plus_seconds(stream_period);
+        if !stream.amount.is_zero() {
+            REWARD_STREAMS.save(deps.storage, asset_key, &stream)?;
+        }
     }
-    TEMP_BALANCE.remove(deps.storage);
+    // Collected rewards are either owed to the stakers or kept as dust
+    RESERVED_REWARDS.save(
+        deps.storage,
+        &reserved_rewards.checked_add(rewards_collected)?,
+    )?;
+
+    Ok(Response::new()
+        .add_attributes(vec![("action", "update_rewards_callback")])
+        .add_messages(fee_msgs))
+}
 
-    Ok(Response::new().add_attributes(vec![("action", "update_rewards_callback")]))
+fn update_protocol_fee(
+    deps: DepsMut,
+    info: MessageInfo,
+    params: ProtocolFeeParams,
+) -> Result<Response<CustomExecuteMsg>, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    is_governance(&info, &config)?;
+    if params.take_rate > MAX_TAKE_RATE {
+        return Err(ContractError::InvalidTakeRate(
+            params.take_rate,
+            MAX_TAKE_RATE,
+        ));
+    }
+    let protocol_fee = ProtocolFee {
+        take_rate: params.take_rate,
+        fee_collector: deps.api.addr_validate(&params.fee_collector)?,
+    };
+    PROTOCOL_FEE.save(deps.storage, &protocol_fee)?;
+    Ok(Response::new().add_attributes(vec![
+        ("action", "update_protocol_fee"),
+        ("take_rate", &protocol_fee.take_rate.to_string()),
+        ("fee_collector", protocol_fee.fee_collector.as_str()),
+    ]))
+}
+
+fn set_dust_recipient(
+    deps: DepsMut,
+    info: MessageInfo,
+    recipient: Option<String>,
+) -> Result<Response<CustomExecuteMsg>, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    is_governance(&info, &config)?;
+    let recipient = match recipient {
+        Some(recipient) => {
+            let recipient = deps.api.addr_validate(&recipient)?;
+            DUST_RECIPIENT.save(deps.storage, &recipient)?;
+            recipient.to_string()
+        }
+        None => {
+            DUST_RECIPIENT.remove(deps.storage);
+            "".to_string()
+        }
+    };
+    Ok(Response::new().add_attributes(vec![
+        ("action", "set_dust_recipient"),
+        ("recipient", &recipient),
+    ]))
+}
+
+fn sweep_dust(
+    deps: DepsMut,
+    info: MessageInfo,
+) -> Result<Response<CustomExecuteMsg>, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    is_governance(&info, &config)?;
+    let recipient = DUST_RECIPIENT
+        .may_load(deps.storage)?
+        .ok_or(ContractError::DustRecipientNotSet {})?;
+
+    // Only whole tokens are swept, the remaining fractions are kept as dust
+    let dust: Vec<(AssetInfoKey, Decimal)> = DUST
+        .range(deps.storage, None, None, Order::Ascending)
+        .map(|item| {
+            let (asset, amount) = item?;
+            Ok((AssetInfoKey::from(asset.check(deps.api, None)?), amount))
+        })
+        .collect::<StdResult<_>>()?;
+    let mut swept = Uint128::zero();
+    for (asset_key, amount) in dust {
+        let whole = amount.to_uint_floor();
+        if whole.is_zero() {
+            continue;
+        }
+        let remaining = amount - Decimal::from_atomics(whole, 0)?;
+        if remaining.is_zero() {
+            DUST.remove(deps.storage, asset_key);
+        } else {
+            DUST.save(deps.storage, asset_key, &remaining)?;
+        }
+        swept = swept.checked_add(whole)?;
+    }
+    let reserved_rewards = RESERVED_REWARDS.may_load(deps.storage)?.unwrap_or_default();
+    RESERVED_REWARDS.save(deps.storage, &reserved_rewards.saturating_sub(swept))?;
+
+    let response = Response::new().add_attributes(vec![
+        ("action", "sweep_dust"),
+        ("recipient", recipient.as_str()),
+        ("amount", &swept.to_string()),
+    ]);
+    if swept.is_zero() {
+        return Ok(response);
+    }
+    let dust_asset = Asset {
+        info: AssetInfo::Native(config.reward_denom),
+        amount: swept,
+    };
+    Ok(response.add_message(transfer_msg(&dust_asset, &recipient)?))
 }
 
 fn rebalance_emissions(
     deps: DepsMut,
     env: Env,
     info: MessageInfo,
-) -> Result<Response, ContractError> {
+) -> Result<Response<CustomExecuteMsg>, ContractError> {
     // Allow execution only from the controller account
     let config = CONFIG.load(deps.storage)?;
     is_controller(&info, &config)?;
@@ -563,7 +1643,7 @@ fn rebalance_emissions_callback(
     deps: DepsMut,
     env: Env,
     info: MessageInfo,
-) -> Result<Response, ContractError> {
+) -> Result<Response<CustomExecuteMsg>, ContractError> {
     if info.sender != env.contract.address {
         return Err(ContractError::Unauthorized {});
     }
@@ -599,10 +1679,10 @@ fn rebalance_emissions_callback(
         &OracleQueryMsg::QueryEmissionsDistributions(distr_req),
     )?;
 
-    let asset_reward_distribution: StdResult<Vec<AssetDistribution>> = distr_res
+    let asset_reward_distribution: Result<Vec<AssetDistribution>, ContractError> = distr_res
         .iter()
-        .map(|d| -> StdResult<AssetDistribution> {
-            let distribution = d.distribution.to_decimal()?;
+        .map(|d| -> Result<AssetDistribution, ContractError> {
+            let distribution = d.to_decimal()?;
             Ok(AssetDistribution {
                 asset: AssetInfo::Native(d.denom.to_string()),
                 distribution,
@@ -630,58 +1710,316 @@ pub fn reply(
 ) -> Result<Response<CustomExecuteMsg>, ContractError> {
     match reply.id {
         CREATE_REPLY_ID => {
-            let response = reply.result.unwrap();
-            // It works because the response data is a protobuf encoded string that contains the denom in the first slot (similar to the contract instantiation response)
-            let denom = parse_instantiate_response_data(response.data.unwrap().as_slice())
-                .map_err(|_| ContractError::Std(StdError::generic_err("parse error".to_string())))?
-                .contract_address;
-            let total_supply = Uint128::from(1_000_000_000_000_u128);
-            let sub_msg_mint = SubMsg::new(CosmosMsg::Custom(CustomExecuteMsg::Token(
-                TokenExecuteMsg::MintTokens {
-                    denom: denom.clone(),
-                    amount: total_supply,
-                    mint_to_address: env.contract.address.to_string(),
-                },
-            )));
-            CONFIG.update(deps.storage, |mut config| -> Result<_, ContractError> {
-                config.alliance_token_denom = denom.clone();
-                config.alliance_token_supply = total_supply;
-                Ok(config)
-            })?;
-            let symbol = "ALLIANCE";
-
-            let sub_msg_metadata = SubMsg::new(CosmosMsg::Custom(CustomExecuteMsg::Token(
-                TokenExecuteMsg::SetMetadata {
-                    denom: denom.clone(),
-                    metadata: Metadata {
-                        description: "Staking token for the alliance protocol".to_string(),
-                        denom_units: vec![DenomUnit {
-                            denom: denom.clone(),
-                            exponent: 0,
-                            aliases: vec![],
-                        }],
-                        base: denom.to_string(),
-                        display: denom.to_string(),
-                        name: "Alliance Token".to_string(),
-                        symbol: symbol.to_string(),
-                    },
-                },
-            )));
-            Ok(Response::new()
-                .add_attributes(vec![
-                    ("alliance_token_denom", denom),
-                    ("alliance_token_total_supply", total_supply.to_string()),
-                ])
-                .add_submessage(sub_msg_mint)
-                .add_submessage(sub_msg_metadata))
-        }
-        CLAIM_REWARD_ERROR_REPLY_ID => {
-            Ok(Response::new().add_attributes(vec![("action", "claim_reward_error")]))
+            let response = reply
+                .result
+                .into_result()
+                .map_err(ContractError::TokenCreationFailed)?;
+            let denom = parse_create_denom_response(&response)
+                .ok_or(ContractError::TokenCreationReplyParse {})?;
+            validate_alliance_token_denom(&env, &denom)?;
+            setup_alliance_token(deps, env, denom)
         }
+        CLAIM_REWARD_REPLY_ID => claim_reward_reply(deps, env, reply),
+        RECEIPT_REPLY_ID => setup_receipt_denom(deps, env, reply),
         _ => Err(ContractError::InvalidReplyId(reply.id)),
     }
 }
 
+fn claim_reward_reply(
+    deps: DepsMut,
+    env: Env,
+    reply: Reply,
+) -> Result<Response<CustomExecuteMsg>, ContractError> {
+    let mut pending_claims = PENDING_CLAIMS.load(deps.storage)?;
+    let validator = pending_claims
+        .pop_front()
+        .ok_or(ContractError::InvalidReplyId(reply.id))?;
+    PENDING_CLAIMS.save(deps.storage, &pending_claims)?;
+    let error = match reply.result {
+        SubMsgResult::Ok(_) => return Ok(Response::new()),
+        SubMsgResult::Err(error) => error,
+    };
+    let failures = CLAIM_FAILURES
+        .may_load(deps.storage, &validator)?
+        .map_or(0, |failure| failure.failures);
+    CLAIM_FAILURES.save(
+        deps.storage,
+        &validator,
+        &ClaimFailure {
+            failures: failures + 1,
+            last_failure: env.block.time,
+            last_error: error.clone(),
+        },
+    )?;
+    Ok(Response::new().add_attributes(vec![
+        ("action", "claim_reward_error"),
+        ("validator", &validator),
+        ("error", &error),
+    ]))
+}
+
+// The denom is decoded from the MsgCreateDenomResponse in the reply data,
+// falling back to the create_denom event when the data is missing
+fn parse_create_denom_response(response: &SubMsgResponse) -> Option<String> {
+    response
+        .data
+        .as_ref()
+        .and_then(|data| MsgCreateDenomResponse::decode(data.as_slice()).ok())
+        .map(|res| res.new_token_denom)
+        .filter(|denom| !denom.is_empty())
+        .or_else(|| {
+            response
+                .events
+                .iter()
+                .filter(|event| event.ty == "create_denom")
+                .flat_map(|event| event.attributes.iter())
+                .find(|attr| attr.key == "new_token_denom" && !attr.value.is_empty())
+                .map(|attr| attr.value.clone())
+        })
+}
+
+// Mints the initial supply of the alliance token and sets its metadata
+// once the denom has been created by the token factory
+fn setup_alliance_token(
+    deps: DepsMut,
+    env: Env,
+    denom: String,
+) -> Result<Response<CustomExecuteMsg>, ContractError> {
+    let alliance_token = ALLIANCE_TOKEN_PARAMS
+        .may_load(deps.storage)?
+        .unwrap_or_default();
+    ALLIANCE_TOKEN_PARAMS.remove(deps.storage);
+    let total_supply = alliance_token.initial_supply;
+    let sub_msg_mint = SubMsg::new(CosmosMsg::Custom(CustomExecuteMsg::Token(
+        TokenExecuteMsg::MintTokens {
+            denom: denom.clone(),
+            amount: total_supply,
+            mint_to_address: env.contract.address.to_string(),
+        },
+    )));
+    CONFIG.update(deps.storage, |mut config| -> Result<_, ContractError> {
+        config.alliance_token_denom = denom.clone();
+        config.alliance_token_supply = total_supply;
+        Ok(config)
+    })?;
+    let sub_msg_metadata = SubMsg::new(CosmosMsg::Custom(CustomExecuteMsg::Token(
+        TokenExecuteMsg::SetMetadata {
+            denom: denom.clone(),
+            metadata: Metadata {
+                description: alliance_token.description,
+                denom_units: vec![DenomUnit {
+                    denom: denom.clone(),
+                    exponent: 0,
+                    aliases: vec![],
+                }],
+                base: denom.to_string(),
+                display: denom.to_string(),
+                name: alliance_token.name,
+                symbol: alliance_token.symbol,
+            },
+        },
+    )));
+    Ok(Response::new()
+        .add_attributes(vec![
+            ("alliance_token_denom", denom),
+            ("alliance_token_total_supply", total_supply.to_string()),
+        ])
+        .add_submessage(sub_msg_mint)
+        .add_submessage(sub_msg_metadata))
+}
+
+// Recovers the alliance token when the denom was created but could
+// not be stored from the token factory reply
+fn set_alliance_token_denom(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    denom: String,
+) -> Result<Response<CustomExecuteMsg>, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    is_governance(&info, &config)?;
+    if !config.alliance_token_denom.is_empty() {
+        return Err(ContractError::AllianceTokenDenomAlreadySet {});
+    }
+    validate_alliance_token_denom(&env, &denom)?;
+    let res = setup_alliance_token(deps, env, denom)?;
+    Ok(res.add_attribute("action", "set_alliance_token_denom"))
+}
+
+// Creates the alliance token again when the denom was never created
+fn recreate_denom(
+    deps: DepsMut,
+    info: MessageInfo,
+    subdenom: String,
+) -> Result<Response<CustomExecuteMsg>, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    is_governance(&info, &config)?;
+    if !config.alliance_token_denom.is_empty() {
+        return Err(ContractError::AllianceTokenDenomAlreadySet {});
+    }
+    Ok(Response::new()
+        .add_attributes(vec![("action", "recreate_denom"), ("subdenom", &subdenom)])
+        .add_submessage(create_denom_msg(subdenom)))
+}
+
+fn create_receipt_denom(
+    deps: DepsMut,
+    info: MessageInfo,
+    params: ReceiptDenomParams,
+) -> Result<Response<CustomExecuteMsg>, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    is_governance(&info, &config)?;
+    let asset_key = AssetInfoKey::from(&params.asset);
+    WHITELIST
+        .load(deps.storage, asset_key.clone())
+        .map_err(|_| ContractError::AssetNotWhitelisted(params.asset.to_string()))?;
+    if RECEIPT_DENOMS.has(deps.storage, asset_key) {
+        return Err(ContractError::ReceiptDenomAlreadySet(
+            params.asset.to_string(),
+        ));
+    }
+    PENDING_RECEIPT_ASSET.save(deps.storage, &params.asset)?;
+    let msg = CosmosMsg::Custom(CustomExecuteMsg::Token(TokenExecuteMsg::CreateDenom {
+        subdenom: params.subdenom.clone(),
+    }));
+    Ok(Response::new()
+        .add_attributes(vec![
+            ("action", "create_receipt_denom"),
+            ("asset", &params.asset.to_string()),
+            ("subdenom", &params.subdenom),
+        ])
+        .add_submessage(SubMsg::reply_on_success(msg, RECEIPT_REPLY_ID)))
+}
+
+// Receipts are minted for the tokens staked before the denom was created
+fn setup_receipt_denom(
+    deps: DepsMut,
+    env: Env,
+    reply: Reply,
+) -> Result<Response<CustomExecuteMsg>, ContractError> {
+    let response = reply
+        .result
+        .into_result()
+        .map_err(ContractError::TokenCreationFailed)?;
+    let denom =
+        parse_create_denom_response(&response).ok_or(ContractError::TokenCreationReplyParse {})?;
+    let asset = PENDING_RECEIPT_ASSET.load(deps.storage)?;
+    PENDING_RECEIPT_ASSET.remove(deps.storage);
+    let asset_key = AssetInfoKey::from(&asset);
+    RECEIPT_DENOMS.save(deps.storage, asset_key.clone(), &denom)?;
+    RECEIPT_ASSETS.save(deps.storage, &denom, &asset)?;
+    let total_balance = TOTAL_BALANCES
+        .may_load(deps.storage, asset_key)?
+        .unwrap_or_default();
+    let res = Response::new().add_attributes(vec![
+        ("action", "setup_receipt_denom"),
+        ("asset", &asset.to_string()),
+        ("denom", &denom),
+    ]);
+    if total_balance.is_zero() {
+        return Ok(res);
+    }
+    Ok(res.add_message(CosmosMsg::Custom(CustomExecuteMsg::Token(
+        TokenExecuteMsg::MintTokens {
+            denom,
+            amount: total_balance,
+            mint_to_address: env.contract.address.to_string(),
+        },
+    ))))
+}
+
+fn create_denom_msg(subdenom: String) -> SubMsg<CustomExecuteMsg> {
+    SubMsg::reply_on_success(
+        CosmosMsg::Custom(CustomExecuteMsg::Token(TokenExecuteMsg::CreateDenom {
+            subdenom,
+        })),
+        CREATE_REPLY_ID,
+    )
+}
+
+// Token factory denoms created by this contract are prefixed with its address
+fn validate_alliance_token_denom(env: &Env, denom: &str) -> Result<(), ContractError> {
+    let prefix = format!("factory/{}/", env.contract.address);
+    match denom.strip_prefix(&prefix) {
+        Some(subdenom) if !subdenom.is_empty() => Ok(()),
+        _ => Err(ContractError::InvalidAllianceTokenDenom(denom.to_string())),
+    }
+}
+
+// Minted tokens stay in the contract until the controller delegates them
+fn mint_alliance_tokens(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    amount: Uint128,
+) -> Result<Response<CustomExecuteMsg>, ContractError> {
+    let mut config = CONFIG.load(deps.storage)?;
+    is_governance(&info, &config)?;
+    ensure_alliance_token_denom(&config)?;
+    if amount.is_zero() {
+        return Err(ContractError::AmountCannotBeZero {});
+    }
+    config.alliance_token_supply = config.alliance_token_supply.checked_add(amount)?;
+    CONFIG.save(deps.storage, &config)?;
+
+    let msg = CosmosMsg::Custom(CustomExecuteMsg::Token(TokenExecuteMsg::MintTokens {
+        denom: config.alliance_token_denom,
+        amount,
+        mint_to_address: env.contract.address.to_string(),
+    }));
+    Ok(Response::new()
+        .add_attributes(vec![
+            ("action", "mint_alliance_tokens"),
+            ("amount", &amount.to_string()),
+            (
+                "alliance_token_total_supply",
+                &config.alliance_token_supply.to_string(),
+            ),
+        ])
+        .add_message(msg))
+}
+
+// Only the tokens held by the contract, which are not delegated
+// or being undelegated, can be burned
+fn burn_alliance_tokens(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    amount: Uint128,
+) -> Result<Response<CustomExecuteMsg>, ContractError> {
+    let mut config = CONFIG.load(deps.storage)?;
+    is_governance(&info, &config)?;
+    ensure_alliance_token_denom(&config)?;
+    if amount.is_zero() {
+        return Err(ContractError::AmountCannotBeZero {});
+    }
+    let undelegated = deps
+        .querier
+        .query_balance(env.contract.address.clone(), &config.alliance_token_denom)?
+        .amount;
+    if undelegated < amount {
+        return Err(ContractError::InsufficientBalance {});
+    }
+    config.alliance_token_supply = config.alliance_token_supply.checked_sub(amount)?;
+    CONFIG.save(deps.storage, &config)?;
+
+    let msg = CosmosMsg::Custom(CustomExecuteMsg::Token(TokenExecuteMsg::BurnTokens {
+        denom: config.alliance_token_denom,
+        amount,
+        burn_from_address: env.contract.address.to_string(),
+    }));
+    Ok(Response::new()
+        .add_attributes(vec![
+            ("action", "burn_alliance_tokens"),
+            ("amount", &amount.to_string()),
+            (
+                "alliance_token_total_supply",
+                &config.alliance_token_supply.to_string(),
+            ),
+        ])
+        .add_message(msg))
+}
+
 // Controller is used to perform administrative operations that deals with delegating the virtual
 // tokens to the expected validators
 fn is_controller(info: &MessageInfo, config: &Config) -> Result<(), ContractError> {
@@ -698,3 +2036,55 @@ fn is_governance(info: &MessageInfo, config: &Config) -> Result<(), ContractErro
     }
     Ok(())
 }
+
+// Validators must be terra operator addresses in the active set when they are allowed
+fn validate_validator(deps: Deps, validator: &str) -> Result<(), ContractError> {
+    match bech32::decode(validator) {
+        Ok((prefix, _, Variant::Bech32)) if prefix == VALIDATOR_ADDRESS_PREFIX => {}
+        _ => {
+            return Err(ContractError::InvalidValidatorAddress(
+                validator.to_string(),
+            ))
+        }
+    }
+    // The validator query also returns unbonded validators, so the
+    // address is looked up in the bonded set instead
+    let bonded = deps.querier.query_all_validators()?;
+    if !bonded.iter().any(|v| v.address == validator) {
+        return Err(ContractError::ValidatorNotBonded(validator.to_string()));
+    }
+    Ok(())
+}
+
+fn is_allowed_validator(
+    allowed_validators: &HashSet<String>,
+    validator: &str,
+) -> Result<(), ContractError> {
+    if !allowed_validators.contains(validator) {
+        return Err(ContractError::ValidatorNotAllowed(validator.to_string()));
+    }
+    Ok(())
+}
+
+// Alliance messages cannot be sent until the alliance token denom is created
+fn ensure_alliance_token_denom(config: &Config) -> Result<(), ContractError> {
+    if config.alliance_token_denom.is_empty() {
+        return Err(ContractError::AllianceTokenDenomNotSet {});
+    }
+    Ok(())
+}
+
+// Staked assets and rewards are always native tokens so the transfers
+// are built as bank messages that can be sent with the custom responses
+fn transfer_msg(asset: &Asset, to: &Addr) -> Result<CosmosMsg<CustomExecuteMsg>, ContractError> {
+    match &asset.info {
+        AssetInfo::Native(denom) => Ok(CosmosMsg::Bank(BankMsg::Send {
+            to_address: to.to_string(),
+            amount: vec![CwCoin {
+                denom: denom.clone(),
+                amount: asset.amount,
+            }],
+        })),
+        _ => Err(ContractError::AssetNotWhitelisted(asset.info.to_string())),
+    }
+}
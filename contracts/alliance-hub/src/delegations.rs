@@ -0,0 +1,146 @@
+use alliance_protocol::alliance_protocol::{AllianceDelegation, AllianceRedelegation};
+use alliance_protocol::error::ContractError;
+use cosmwasm_std::{Decimal, Uint128};
+use std::collections::{BTreeMap, HashSet};
+
+use crate::models::DelegationStrategy;
+
+#[derive(Debug, Default, PartialEq)]
+pub struct RebalancePlan {
+    pub delegations: Vec<AllianceDelegation>,
+    pub undelegations: Vec<AllianceDelegation>,
+    pub redelegations: Vec<AllianceRedelegation>,
+}
+
+pub fn validate_strategy(strategy: &DelegationStrategy) -> Result<(), ContractError> {
+    if strategy.targets.is_empty() {
+        return Err(ContractError::InvalidDelegationStrategy(
+            "targets cannot be empty".to_string(),
+        ));
+    }
+    let mut validators = HashSet::new();
+    let mut total_weight = Decimal::zero();
+    for target in &strategy.targets {
+        if !validators.insert(&target.validator) {
+            return Err(ContractError::InvalidDelegationStrategy(format!(
+                "duplicated target for validator {}",
+                target.validator
+            )));
+        }
+        if target.weight.is_zero() {
+            return Err(ContractError::InvalidDelegationStrategy(format!(
+                "zero weight for validator {}",
+                target.validator
+            )));
+        }
+        total_weight = total_weight.checked_add(target.weight)?;
+    }
+    if total_weight > Decimal::one() {
+        return Err(ContractError::InvalidDelegationStrategy(format!(
+            "sum of weights {} is greater than 1",
+            total_weight
+        )));
+    }
+    Ok(())
+}
+
+// Computes the messages that move the current delegations and the alliance
+// tokens held by the contract to the target weights. The tokens being unbonded
+// are part of the total since they will be delegated once they are back, and
+// the share of the total not covered by the targets is kept undelegated.
+// Surplus is redelegated to the validators under their target up to
+// max_redelegations and the rest stays delegated until a later rebalance.
+// Only the surplus that no target can take is undelegated, so calling it
+// again does not undelegate more until the targets change.
+pub fn compute_rebalance(
+    strategy: &DelegationStrategy,
+    current: &BTreeMap<String, Uint128>,
+    unbonding: Uint128,
+    available: Uint128,
+) -> Result<RebalancePlan, ContractError> {
+    let mut delegated = Uint128::zero();
+    for amount in current.values() {
+        delegated = delegated.checked_add(*amount)?;
+    }
+    let total = available.checked_add(unbonding)?.checked_add(delegated)?;
+
+    // Weights add up to at most 1 so the multiplications cannot overflow and
+    // the rounding remainder goes to the first target so that the targets add
+    // up to the amount of alliance tokens that should be delegated
+    let total_weight = strategy
+        .targets
+        .iter()
+        .fold(Decimal::zero(), |acc, target| acc + target.weight);
+    let delegated_target = total.mul_floor(total_weight);
+    let mut amounts = vec![];
+    for target in &strategy.targets {
+        amounts.push(total.mul_floor(target.weight));
+    }
+    let remainder = delegated_target.checked_sub(amounts.iter().sum())?;
+    amounts[0] += remainder;
+
+    let mut targets = BTreeMap::new();
+    let mut deficits = vec![];
+    for (target, amount) in strategy.targets.iter().zip(amounts) {
+        let delegated = current.get(&target.validator).copied().unwrap_or_default();
+        if amount > delegated {
+            deficits.push((target.validator.clone(), amount - delegated));
+        }
+        targets.insert(target.validator.clone(), amount);
+    }
+    let mut surpluses = vec![];
+    for (validator, delegated) in current {
+        let amount = targets.get(validator).copied().unwrap_or_default();
+        if *delegated > amount {
+            surpluses.push((validator.clone(), *delegated - amount));
+        }
+    }
+
+    let mut plan = RebalancePlan::default();
+    let mut next_deficit = 0;
+    for (src_validator, surplus) in surpluses.iter_mut() {
+        while !surplus.is_zero()
+            && next_deficit < deficits.len()
+            && plan.redelegations.len() < strategy.max_redelegations as usize
+        {
+            let (dst_validator, deficit) = &mut deficits[next_deficit];
+            let amount = (*surplus).min(*deficit);
+            plan.redelegations.push(AllianceRedelegation {
+                src_validator: src_validator.clone(),
+                dst_validator: dst_validator.clone(),
+                amount,
+            });
+            *surplus -= amount;
+            *deficit -= amount;
+            if deficit.is_zero() {
+                next_deficit += 1;
+            }
+        }
+    }
+
+    let mut excess = delegated.saturating_sub(delegated_target);
+    for (validator, surplus) in surpluses {
+        if excess.is_zero() {
+            break;
+        }
+        let amount = surplus.min(excess);
+        if amount.is_zero() {
+            continue;
+        }
+        plan.undelegations
+            .push(AllianceDelegation { validator, amount });
+        excess -= amount;
+    }
+
+    let mut available = available.min(delegated_target.saturating_sub(delegated));
+    for (validator, deficit) in deficits.into_iter().skip(next_deficit) {
+        let amount = deficit.min(available);
+        if amount.is_zero() {
+            break;
+        }
+        plan.delegations
+            .push(AllianceDelegation { validator, amount });
+        available -= amount;
+    }
+    Ok(plan)
+}
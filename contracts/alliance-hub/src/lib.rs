@@ -1,6 +1,9 @@
 pub mod contract;
+pub mod delegations;
+pub mod lockup;
 pub mod models;
 pub mod query;
+pub mod reward_index;
 pub mod state;
 #[cfg(test)]
 mod tests;
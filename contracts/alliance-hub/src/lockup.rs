@@ -0,0 +1,260 @@
+use alliance_protocol::error::ContractError;
+use cosmwasm_std::{Addr, Decimal, Order, StdResult, Storage, Timestamp, Uint128, Uint256};
+use cw_asset::AssetInfoKey;
+use cw_storage_plus::Bound;
+use std::collections::HashSet;
+
+use crate::models::{LockTier, LockedPosition};
+use crate::state::{
+    BALANCES, BALANCE_BOOSTS, EXPIRED_LOCK_INDEXES, LOCKED_POSITIONS, LOCK_EXPIRIES,
+    LOCK_EXPIRY_POSITIONS, TOTAL_BALANCES, TOTAL_BALANCE_BOOSTS,
+};
+
+pub fn validate_lock_tiers(tiers: &[LockTier]) -> Result<(), ContractError> {
+    let mut durations = HashSet::new();
+    for tier in tiers {
+        if tier.duration == 0 {
+            return Err(ContractError::InvalidLockTiers(
+                "duration cannot be zero".to_string(),
+            ));
+        }
+        if !durations.insert(tier.duration) {
+            return Err(ContractError::InvalidLockTiers(format!(
+                "duplicated tier for duration {}",
+                tier.duration
+            )));
+        }
+        if tier.multiplier < Decimal::one() {
+            return Err(ContractError::InvalidLockTiers(format!(
+                "multiplier {} is lower than 1",
+                tier.multiplier
+            )));
+        }
+    }
+    Ok(())
+}
+
+// Weight added on top of the locked amount
+pub fn lock_boost(amount: Uint128, multiplier: Decimal) -> Uint128 {
+    amount.mul_floor(multiplier - Decimal::one())
+}
+
+// Balance used to split the rewards among the stakers of an asset
+pub fn effective_balance(storage: &dyn Storage, key: (Addr, AssetInfoKey)) -> StdResult<Uint128> {
+    let balance = BALANCES.may_load(storage, key.clone())?.unwrap_or_default();
+    let boost = BALANCE_BOOSTS.may_load(storage, key)?.unwrap_or_default();
+    Ok(balance + boost)
+}
+
+pub fn total_effective_balance(
+    storage: &dyn Storage,
+    asset_key: AssetInfoKey,
+) -> StdResult<Uint128> {
+    let balance = TOTAL_BALANCES
+        .may_load(storage, asset_key.clone())?
+        .unwrap_or_default();
+    let boost = TOTAL_BALANCE_BOOSTS
+        .may_load(storage, asset_key)?
+        .unwrap_or_default();
+    Ok(balance + boost)
+}
+
+// Tokens of the user that cannot be unstaked until their positions are unlocked
+pub fn locked_balance(
+    storage: &dyn Storage,
+    user: Addr,
+    asset_key: AssetInfoKey,
+) -> StdResult<Uint128> {
+    LOCKED_POSITIONS
+        .prefix((user, asset_key))
+        .range(storage, None, None, Order::Ascending)
+        .map(|item| item.map(|(_, position)| position.amount))
+        .sum()
+}
+
+// Rewards of the user must be settled before the boost changes
+pub fn update_boost(
+    storage: &mut dyn Storage,
+    key: (Addr, AssetInfoKey),
+    old_boost: Uint128,
+    new_boost: Uint128,
+) -> Result<(), ContractError> {
+    let boost = BALANCE_BOOSTS
+        .may_load(storage, key.clone())?
+        .unwrap_or_default();
+    let boost = boost.checked_sub(old_boost)? + new_boost;
+    if boost.is_zero() {
+        BALANCE_BOOSTS.remove(storage, key.clone());
+    } else {
+        BALANCE_BOOSTS.save(storage, key.clone(), &boost)?;
+    }
+    let total_boost = TOTAL_BALANCE_BOOSTS
+        .may_load(storage, key.1.clone())?
+        .unwrap_or_default();
+    TOTAL_BALANCE_BOOSTS.save(
+        storage,
+        key.1,
+        &(total_boost.checked_sub(old_boost)? + new_boost),
+    )?;
+    Ok(())
+}
+
+// Positions without a boost do not expire
+pub fn add_lock_expiry(
+    storage: &mut dyn Storage,
+    asset_key: AssetInfoKey,
+    position: &LockedPosition,
+) -> StdResult<()> {
+    if position.multiplier <= Decimal::one() {
+        return Ok(());
+    }
+    let key = (asset_key, position.unlock_time.seconds());
+    LOCK_EXPIRIES.update(storage, key.clone(), |expiring| -> StdResult<_> {
+        Ok(expiring.unwrap_or_default() + lock_boost(position.amount, position.multiplier))
+    })?;
+    LOCK_EXPIRY_POSITIONS.update(storage, key, |positions| -> StdResult<_> {
+        Ok(positions.unwrap_or_default() + 1)
+    })?;
+    Ok(())
+}
+
+// Removes the boost of a position that did not expire yet
+pub fn remove_lock_expiry(
+    storage: &mut dyn Storage,
+    asset_key: AssetInfoKey,
+    position: &LockedPosition,
+) -> Result<(), ContractError> {
+    if position.multiplier <= Decimal::one() {
+        return Ok(());
+    }
+    let key = (asset_key, position.unlock_time.seconds());
+    if release_lock_expiry(storage, key.clone())? == 0 {
+        LOCK_EXPIRIES.remove(storage, key);
+        return Ok(());
+    }
+    let expiring = LOCK_EXPIRIES
+        .may_load(storage, key.clone())?
+        .unwrap_or_default()
+        .checked_sub(lock_boost(position.amount, position.multiplier))?;
+    LOCK_EXPIRIES.save(storage, key, &expiring)?;
+    Ok(())
+}
+
+// Returns the positions left at the unlock time once one of them is released
+fn release_lock_expiry(storage: &mut dyn Storage, key: (AssetInfoKey, u64)) -> StdResult<u32> {
+    let positions = LOCK_EXPIRY_POSITIONS
+        .may_load(storage, key.clone())?
+        .unwrap_or_default()
+        .saturating_sub(1);
+    if positions == 0 {
+        LOCK_EXPIRY_POSITIONS.remove(storage, key);
+    } else {
+        LOCK_EXPIRY_POSITIONS.save(storage, key, &positions)?;
+    }
+    Ok(positions)
+}
+
+// Boosts of the asset that expire up to now by unlock time in ascending order
+pub fn due_lock_expiries(
+    storage: &dyn Storage,
+    asset_key: AssetInfoKey,
+    now: Timestamp,
+) -> StdResult<Vec<(u64, Uint128)>> {
+    LOCK_EXPIRIES
+        .prefix(asset_key)
+        .range(
+            storage,
+            None,
+            Some(Bound::inclusive(now.seconds())),
+            Order::Ascending,
+        )
+        .collect()
+}
+
+// Removes the expired boost from the total, the boost of each user is removed
+// when their rewards are settled up to the index of the asset at the unlock time
+pub fn expire_total_boost(
+    storage: &mut dyn Storage,
+    asset_key: AssetInfoKey,
+    unlock_time: u64,
+    boost: Uint128,
+    index: Uint256,
+) -> Result<(), ContractError> {
+    EXPIRED_LOCK_INDEXES.save(storage, (asset_key.clone(), unlock_time), &index)?;
+    LOCK_EXPIRIES.remove(storage, (asset_key.clone(), unlock_time));
+    let total_boost = TOTAL_BALANCE_BOOSTS
+        .may_load(storage, asset_key.clone())?
+        .unwrap_or_default();
+    TOTAL_BALANCE_BOOSTS.save(storage, asset_key, &total_boost.checked_sub(boost)?)?;
+    Ok(())
+}
+
+// Positions of the user that still count their boost by unlock time in ascending order
+pub fn boosted_positions(
+    storage: &dyn Storage,
+    user: Addr,
+    asset_key: AssetInfoKey,
+) -> StdResult<Vec<(u64, LockedPosition)>> {
+    let mut positions = LOCKED_POSITIONS
+        .prefix((user, asset_key))
+        .range(storage, None, None, Order::Ascending)
+        .filter(|item| {
+            item.as_ref()
+                .map_or(true, |(_, position)| position.multiplier > Decimal::one())
+        })
+        .collect::<StdResult<Vec<_>>>()?;
+    positions.sort_by_key(|(_, position)| position.unlock_time);
+    Ok(positions)
+}
+
+// The position keeps locking its tokens until it is unlocked but stops being boosted
+pub fn expire_user_boost(
+    storage: &mut dyn Storage,
+    key: (Addr, AssetInfoKey),
+    duration: u64,
+    mut position: LockedPosition,
+) -> Result<(), ContractError> {
+    let boost = BALANCE_BOOSTS
+        .may_load(storage, key.clone())?
+        .unwrap_or_default()
+        .checked_sub(lock_boost(position.amount, position.multiplier))?;
+    if boost.is_zero() {
+        BALANCE_BOOSTS.remove(storage, key.clone());
+    } else {
+        BALANCE_BOOSTS.save(storage, key.clone(), &boost)?;
+    }
+    // The expired lock index is only kept while positions still need it
+    let expiry_key = (key.1.clone(), position.unlock_time.seconds());
+    if release_lock_expiry(storage, expiry_key.clone())? == 0 {
+        EXPIRED_LOCK_INDEXES.remove(storage, expiry_key);
+    }
+    position.multiplier = Decimal::one();
+    LOCKED_POSITIONS.save(storage, (key.0, key.1, duration), &position)?;
+    Ok(())
+}
+
+// Balance of the user once the boost of the positions expired up to now is removed
+pub fn current_effective_balance(
+    storage: &dyn Storage,
+    key: (Addr, AssetInfoKey),
+    now: Timestamp,
+) -> StdResult<Uint128> {
+    let expired_boost = boosted_positions(storage, key.0.clone(), key.1.clone())?
+        .into_iter()
+        .filter(|(_, position)| position.unlock_time <= now)
+        .map(|(_, position)| lock_boost(position.amount, position.multiplier))
+        .sum::<Uint128>();
+    Ok(effective_balance(storage, key)? - expired_boost)
+}
+
+pub fn current_total_effective_balance(
+    storage: &dyn Storage,
+    asset_key: AssetInfoKey,
+    now: Timestamp,
+) -> StdResult<Uint128> {
+    let expired_boost = due_lock_expiries(storage, asset_key.clone(), now)?
+        .into_iter()
+        .map(|(_, boost)| boost)
+        .sum::<Uint128>();
+    Ok(total_effective_balance(storage, asset_key)? - expired_boost)
+}
@@ -1,11 +1,12 @@
 use alliance_protocol::{
     alliance_oracle_types::ChainId,
     alliance_protocol::{
-        AllianceDelegateMsg, AllianceRedelegateMsg, AllianceUndelegateMsg, AssetDistribution,
+        AllianceDelegateMsg, AllianceDelegation, AllianceRedelegateMsg, AllianceUndelegateMsg,
+        AssetDistribution,
     },
 };
 use cosmwasm_schema::{cw_serde, QueryResponses};
-use cosmwasm_std::{Addr, Timestamp, Uint128};
+use cosmwasm_std::{Addr, Decimal, Timestamp, Uint128};
 use cw_asset::{Asset, AssetInfo};
 use std::collections::{HashMap, HashSet};
 
@@ -20,31 +21,170 @@ pub struct Config {
     pub reward_denom: String,
 }
 
+#[cw_serde]
+pub struct ClaimFailure {
+    pub failures: u64,
+    pub last_failure: Timestamp,
+    pub last_error: String,
+}
+
+// Share of the rewards sent to the fee collector before they are distributed
+#[cw_serde]
+pub struct ProtocolFee {
+    pub take_rate: Decimal,
+    pub fee_collector: Addr,
+}
+
+// Rewards of an asset released linearly until the end of the stream
+#[cw_serde]
+pub struct RewardStream {
+    pub amount: Decimal,
+    pub last_update: Timestamp,
+    pub end: Timestamp,
+}
+
+// Staked tokens locked for the duration of the tier are weighted by its multiplier
+#[cw_serde]
+pub struct LockTier {
+    pub duration: u64,
+    pub multiplier: Decimal,
+}
+
+#[cw_serde]
+pub struct LockedPosition {
+    pub amount: Uint128,
+    pub multiplier: Decimal,
+    pub unlock_time: Timestamp,
+}
+
+#[cw_serde]
+pub struct ProtocolFeeParams {
+    pub take_rate: Decimal,
+    pub fee_collector: String,
+}
+
+#[cw_serde]
+pub struct UnlockParams {
+    pub address: String,
+    pub asset: AssetInfo,
+    pub duration: u64,
+}
+
+// Transfers the stake of the owner when set, using the allowance of the sender
+#[cw_serde]
+pub struct TransferStakeParams {
+    pub owner: Option<String>,
+    pub asset: Asset,
+    pub recipient: String,
+}
+
+// Stake of the sender that the spender can transfer
+#[cw_serde]
+pub struct StakeAllowanceParams {
+    pub spender: String,
+    pub asset: Asset,
+}
+
+#[cw_serde]
+pub struct ReceiptDenomParams {
+    pub asset: AssetInfo,
+    pub subdenom: String,
+}
+
 #[cw_serde]
 pub struct InstantiateMsg {
     pub governance: String,
     pub controller: String,
     pub oracle: String,
     pub reward_denom: String,
+    pub alliance_token: Option<AllianceTokenParams>,
+    // Seconds until undelegated tokens are returned, 21 days by default
+    pub unbonding_period: Option<u64>,
+}
+
+#[cw_serde]
+#[derive(Default)]
+pub struct MigrateMsg {
+    // Alliance tokens delegated before the delegations were tracked,
+    // recorded on migration so that they can be rebalanced
+    pub delegations: Option<Vec<AllianceDelegation>>,
+}
+
+#[cw_serde]
+pub struct AllianceTokenParams {
+    pub initial_supply: Uint128,
+    pub name: String,
+    pub symbol: String,
+    pub description: String,
+}
+
+impl Default for AllianceTokenParams {
+    fn default() -> Self {
+        AllianceTokenParams {
+            initial_supply: Uint128::new(1_000_000_000_000),
+            name: "Alliance Token".to_string(),
+            symbol: "ALLIANCE".to_string(),
+            description: "Staking token for the alliance protocol".to_string(),
+        }
+    }
+}
+
+// Target share of the alliance tokens delegated to each validator
+#[cw_serde]
+pub struct DelegationTarget {
+    pub validator: String,
+    pub weight: Decimal,
+}
+
+#[cw_serde]
+pub struct DelegationStrategy {
+    // Weights add up to at most 1, the rest of the alliance tokens is kept undelegated
+    pub targets: Vec<DelegationTarget>,
+    // Redelegations sent by each RebalanceDelegations call, the surplus
+    // that cannot be redelegated stays delegated until the next call
+    pub max_redelegations: u32,
 }
 
 #[cw_serde]
 pub enum ExecuteMsg {
     // Public functions
-    Stake {},
+    // Stakes the funds for the recipient, the sender by default
+    Stake { recipient: Option<String> },
+    StakeLocked { duration: u64 },
     Unstake(Asset),
+    Unlock(UnlockParams),
+    TransferStake(TransferStakeParams),
+    SetStakeAllowance(StakeAllowanceParams),
+    // The receipts buy into the rewards owed to the issued ones with the unclaimed
+    // rewards of the sender, the reward tokens sent cover the rest and are refunded otherwise
+    WithdrawReceipts(Asset),
     ClaimRewards(AssetInfo),
     UpdateRewards {},
+    RebalanceDelegations {},
 
     // Privileged functions
     WhitelistAssets(HashMap<ChainId, Vec<AssetInfo>>),
     RemoveAssets(Vec<AssetInfo>),
+    AddAllowedValidators(Vec<String>),
+    RemoveAllowedValidators(Vec<String>),
     UpdateRewardsCallback {},
     AllianceDelegate(AllianceDelegateMsg),
     AllianceUndelegate(AllianceUndelegateMsg),
     AllianceRedelegate(AllianceRedelegateMsg),
     RebalanceEmissions {},
     RebalanceEmissionsCallback {},
+    MintAllianceTokens { amount: Uint128 },
+    BurnAllianceTokens { amount: Uint128 },
+    SetAllianceTokenDenom { denom: String },
+    UpdateDelegationStrategy(DelegationStrategy),
+    RecreateDenom { subdenom: String },
+    SetDustRecipient { recipient: Option<String> },
+    SweepDust {},
+    UpdateProtocolFee(ProtocolFeeParams),
+    UpdateRewardStreamPeriod { seconds: u64 },
+    UpdateUnbondingPeriod { seconds: u64 },
+    UpdateLockTiers(Vec<LockTier>),
+    CreateReceiptDenom(ReceiptDenomParams),
 }
 
 #[cw_serde]
@@ -56,6 +196,9 @@ pub enum QueryMsg {
     #[returns(HashSet<Addr>)]
     Validators {},
 
+    #[returns(HashSet<String>)]
+    AllowedValidators {},
+
     #[returns(WhitelistedAssetsResponse)]
     WhitelistedAssets {},
 
@@ -76,6 +219,39 @@ pub enum QueryMsg {
 
     #[returns(Vec<StakedBalanceRes>)]
     TotalStakedBalances {},
+
+    #[returns(DelegationStrategy)]
+    DelegationStrategy {},
+
+    #[returns(Vec<AllianceDelegation>)]
+    Delegations {},
+
+    #[returns(AllianceTokenBalanceRes)]
+    AllianceTokenBalance {},
+
+    #[returns(Vec<UnbondingEntry>)]
+    Unbondings {},
+
+    #[returns(Vec<ClaimFailureRes>)]
+    ClaimFailures {},
+
+    #[returns(DustRes)]
+    Dust {},
+
+    #[returns(ProtocolFeeRes)]
+    ProtocolFee {},
+
+    #[returns(Vec<LockTier>)]
+    LockTiers {},
+
+    #[returns(Vec<LockedPositionRes>)]
+    LockedPositions(AssetQuery),
+
+    #[returns(Uint128)]
+    StakeAllowance(StakeAllowanceQuery),
+
+    #[returns(Vec<ReceiptDenomRes>)]
+    ReceiptDenoms {},
 }
 pub type WhitelistedAssetsResponse = HashMap<ChainId, Vec<AssetInfo>>;
 
@@ -102,8 +278,75 @@ pub struct AssetQuery {
     pub asset: AssetInfo,
 }
 
+#[cw_serde]
+pub struct StakeAllowanceQuery {
+    pub owner: String,
+    pub spender: String,
+    pub asset: AssetInfo,
+}
+
+#[cw_serde]
+pub struct AllianceTokenBalanceRes {
+    pub free: Uint128,
+    pub delegated: Uint128,
+    pub unbonding: Uint128,
+}
+
+#[cw_serde]
+pub struct UnbondingEntry {
+    pub validator: String,
+    pub amount: Uint128,
+    pub completion_time: Timestamp,
+}
+
+#[cw_serde]
+pub struct ClaimFailureRes {
+    pub validator: String,
+    pub failures: u64,
+    pub last_failure: Timestamp,
+    pub last_error: String,
+}
+
+#[cw_serde]
+pub struct AssetDust {
+    pub asset: AssetInfo,
+    pub amount: Decimal,
+}
+
+#[cw_serde]
+pub struct DustRes {
+    pub recipient: Option<Addr>,
+    pub dust: Vec<AssetDust>,
+}
+
+#[cw_serde]
+pub struct ProtocolFeeRes {
+    pub protocol_fee: Option<ProtocolFee>,
+    pub fees_collected: Uint128,
+}
+
+#[cw_serde]
+pub struct LockedPositionRes {
+    pub duration: u64,
+    pub amount: Uint128,
+    pub multiplier: Decimal,
+    pub unlock_time: Timestamp,
+}
+
+#[cw_serde]
+pub struct ReceiptDenomRes {
+    pub asset: AssetInfo,
+    pub denom: String,
+    // Receipts held outside of the hub
+    pub issued: Uint128,
+    // Rewards owed to the issued receipts, paid to their holders when staked back
+    pub rewards: Uint128,
+}
+
 #[cw_serde]
 pub struct StakedBalanceRes {
     pub asset: AssetInfo,
     pub balance: Uint128,
+    // Balance weighted by the multipliers of the locked positions
+    pub effective_balance: Uint128,
 }
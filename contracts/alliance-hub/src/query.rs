@@ -1,32 +1,56 @@
+use alliance_protocol::alliance_protocol::AllianceDelegation;
 #[cfg(not(feature = "library"))]
 use cosmwasm_std::entry_point;
-use cosmwasm_std::{to_json_binary, Binary, Deps, Env, Order, StdResult, Uint128};
+use cosmwasm_std::{to_json_binary, Addr, Binary, Deps, Env, Order, StdResult, Timestamp, Uint128};
 use cw_asset::{AssetInfo, AssetInfoKey};
+use cw_storage_plus::PrefixBound;
 use std::collections::HashMap;
 
 use crate::{
+    lockup::{current_effective_balance, current_total_effective_balance},
     models::{
-        AllPendingRewardsQuery, AllStakedBalancesQuery, AssetQuery, PendingRewardsRes, QueryMsg,
-        StakedBalanceRes, WhitelistedAssetsResponse,
+        AllPendingRewardsQuery, AllStakedBalancesQuery, AllianceTokenBalanceRes, AssetDust,
+        AssetQuery, ClaimFailureRes, DustRes, LockedPositionRes, PendingRewardsRes, ProtocolFeeRes,
+        QueryMsg, ReceiptDenomRes, StakeAllowanceQuery, StakedBalanceRes, UnbondingEntry,
+        WhitelistedAssetsResponse,
+    },
+    reward_index::{
+        accrue_user_rewards, accrued_rewards, current_asset_reward_index, load_user_reward_index,
     },
     state::{
-        ASSET_REWARD_DISTRIBUTION, ASSET_REWARD_RATE, BALANCES, CONFIG, TOTAL_BALANCES,
-        UNCLAIMED_REWARDS, USER_ASSET_REWARD_RATE, VALIDATORS, WHITELIST,
+        ALLOWED_VALIDATORS, ASSET_REWARD_DISTRIBUTION, BALANCES, CLAIM_FAILURES, CONFIG,
+        DELEGATIONS, DELEGATION_STRATEGY, DUST, DUST_RECIPIENT, EXPIRED_LOCK_INDEXES,
+        FEES_COLLECTED, ISSUED_RECEIPTS, LEGACY_USER_ASSET_REWARD_RATE, LOCKED_POSITIONS,
+        LOCK_TIERS, PROTOCOL_FEE, RECEIPT_DENOMS, RECEIPT_REWARDS, RECEIPT_REWARD_INDEX,
+        STAKE_ALLOWANCES, TOTAL_BALANCES, UNBONDINGS, UNCLAIMED_REWARDS, USER_ASSET_REWARD_INDEX,
+        VALIDATORS, WHITELIST,
     },
 };
 
 #[cfg_attr(not(feature = "library"), entry_point)]
-pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<Binary> {
+pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> StdResult<Binary> {
     Ok(match msg {
         QueryMsg::Config {} => get_config(deps)?,
         QueryMsg::Validators {} => get_validators(deps)?,
+        QueryMsg::AllowedValidators {} => get_allowed_validators(deps)?,
         QueryMsg::WhitelistedAssets {} => get_whitelisted_assets(deps)?,
         QueryMsg::RewardDistribution {} => get_rewards_distribution(deps)?,
-        QueryMsg::StakedBalance(asset_query) => get_staked_balance(deps, asset_query)?,
-        QueryMsg::PendingRewards(asset_query) => get_pending_rewards(deps, asset_query)?,
-        QueryMsg::AllStakedBalances(query) => get_all_staked_balances(deps, query)?,
-        QueryMsg::AllPendingRewards(query) => get_all_pending_rewards(deps, query)?,
-        QueryMsg::TotalStakedBalances {} => get_total_staked_balances(deps)?,
+        QueryMsg::StakedBalance(asset_query) => get_staked_balance(deps, env, asset_query)?,
+        QueryMsg::PendingRewards(asset_query) => get_pending_rewards(deps, env, asset_query)?,
+        QueryMsg::AllStakedBalances(query) => get_all_staked_balances(deps, env, query)?,
+        QueryMsg::AllPendingRewards(query) => get_all_pending_rewards(deps, env, query)?,
+        QueryMsg::TotalStakedBalances {} => get_total_staked_balances(deps, env)?,
+        QueryMsg::DelegationStrategy {} => get_delegation_strategy(deps)?,
+        QueryMsg::Delegations {} => get_delegations(deps)?,
+        QueryMsg::AllianceTokenBalance {} => get_alliance_token_balance(deps, env)?,
+        QueryMsg::Unbondings {} => get_unbondings(deps, env)?,
+        QueryMsg::ClaimFailures {} => get_claim_failures(deps)?,
+        QueryMsg::Dust {} => get_dust(deps)?,
+        QueryMsg::ProtocolFee {} => get_protocol_fee(deps)?,
+        QueryMsg::LockTiers {} => get_lock_tiers(deps)?,
+        QueryMsg::LockedPositions(asset_query) => get_locked_positions(deps, asset_query)?,
+        QueryMsg::StakeAllowance(query) => get_stake_allowance(deps, query)?,
+        QueryMsg::ReceiptDenoms {} => get_receipt_denoms(deps, env)?,
     })
 }
 
@@ -42,6 +66,195 @@ fn get_validators(deps: Deps) -> StdResult<Binary> {
     to_json_binary(&validators)
 }
 
+fn get_allowed_validators(deps: Deps) -> StdResult<Binary> {
+    let validators = ALLOWED_VALIDATORS
+        .may_load(deps.storage)?
+        .unwrap_or_default();
+
+    to_json_binary(&validators)
+}
+
+fn get_delegation_strategy(deps: Deps) -> StdResult<Binary> {
+    let strategy = DELEGATION_STRATEGY.load(deps.storage)?;
+
+    to_json_binary(&strategy)
+}
+
+fn get_delegations(deps: Deps) -> StdResult<Binary> {
+    let delegations = DELEGATIONS
+        .range(deps.storage, None, None, Order::Ascending)
+        .map(|item| {
+            let (validator, amount) = item?;
+            Ok(AllianceDelegation { validator, amount })
+        })
+        .collect::<StdResult<Vec<AllianceDelegation>>>()?;
+
+    to_json_binary(&delegations)
+}
+
+fn get_alliance_token_balance(deps: Deps, env: Env) -> StdResult<Binary> {
+    let config = CONFIG.load(deps.storage)?;
+    let free = if config.alliance_token_denom.is_empty() {
+        Uint128::zero()
+    } else {
+        deps.querier
+            .query_balance(&env.contract.address, config.alliance_token_denom)?
+            .amount
+    };
+    let delegated = DELEGATIONS
+        .range(deps.storage, None, None, Order::Ascending)
+        .map(|item| item.map(|(_, amount)| amount))
+        .sum::<StdResult<Uint128>>()?;
+    let unbonding = pending_unbondings(deps, &env)
+        .map(|item| item.map(|entry| entry.amount))
+        .sum::<StdResult<Uint128>>()?;
+
+    to_json_binary(&AllianceTokenBalanceRes {
+        free,
+        delegated,
+        unbonding,
+    })
+}
+
+fn get_unbondings(deps: Deps, env: Env) -> StdResult<Binary> {
+    let unbondings = pending_unbondings(deps, &env).collect::<StdResult<Vec<UnbondingEntry>>>()?;
+
+    to_json_binary(&unbondings)
+}
+
+// Unbondings that completed are not removed until the next undelegation or rebalance
+fn pending_unbondings<'a>(
+    deps: Deps<'a>,
+    env: &Env,
+) -> impl Iterator<Item = StdResult<UnbondingEntry>> + 'a {
+    UNBONDINGS
+        .prefix_range(
+            deps.storage,
+            Some(PrefixBound::exclusive(env.block.time.seconds())),
+            None,
+            Order::Ascending,
+        )
+        .map(|item| {
+            let ((completion_time, validator), amount) = item?;
+            Ok(UnbondingEntry {
+                validator,
+                amount,
+                completion_time: Timestamp::from_seconds(completion_time),
+            })
+        })
+}
+
+fn get_claim_failures(deps: Deps) -> StdResult<Binary> {
+    let failures = CLAIM_FAILURES
+        .range(deps.storage, None, None, Order::Ascending)
+        .map(|item| {
+            let (validator, failure) = item?;
+            Ok(ClaimFailureRes {
+                validator,
+                failures: failure.failures,
+                last_failure: failure.last_failure,
+                last_error: failure.last_error,
+            })
+        })
+        .collect::<StdResult<Vec<ClaimFailureRes>>>()?;
+
+    to_json_binary(&failures)
+}
+
+fn get_dust(deps: Deps) -> StdResult<Binary> {
+    let dust = DUST
+        .range(deps.storage, None, None, Order::Ascending)
+        .map(|item| {
+            let (asset, amount) = item?;
+            Ok(AssetDust {
+                asset: asset.check(deps.api, None)?,
+                amount,
+            })
+        })
+        .collect::<StdResult<Vec<AssetDust>>>()?;
+
+    to_json_binary(&DustRes {
+        recipient: DUST_RECIPIENT.may_load(deps.storage)?,
+        dust,
+    })
+}
+
+fn get_protocol_fee(deps: Deps) -> StdResult<Binary> {
+    to_json_binary(&ProtocolFeeRes {
+        protocol_fee: PROTOCOL_FEE.may_load(deps.storage)?,
+        fees_collected: FEES_COLLECTED.may_load(deps.storage)?.unwrap_or_default(),
+    })
+}
+
+fn get_lock_tiers(deps: Deps) -> StdResult<Binary> {
+    let tiers = LOCK_TIERS.may_load(deps.storage)?.unwrap_or_default();
+
+    to_json_binary(&tiers)
+}
+
+fn get_locked_positions(deps: Deps, asset_query: AssetQuery) -> StdResult<Binary> {
+    let addr = deps.api.addr_validate(&asset_query.address)?;
+    let positions = LOCKED_POSITIONS
+        .prefix((addr, AssetInfoKey::from(asset_query.asset)))
+        .range(deps.storage, None, None, Order::Ascending)
+        .map(|item| {
+            let (duration, position) = item?;
+            Ok(LockedPositionRes {
+                duration,
+                amount: position.amount,
+                multiplier: position.multiplier,
+                unlock_time: position.unlock_time,
+            })
+        })
+        .collect::<StdResult<Vec<LockedPositionRes>>>()?;
+
+    to_json_binary(&positions)
+}
+
+fn get_stake_allowance(deps: Deps, query: StakeAllowanceQuery) -> StdResult<Binary> {
+    let owner = deps.api.addr_validate(&query.owner)?;
+    let spender = deps.api.addr_validate(&query.spender)?;
+    let allowance = STAKE_ALLOWANCES
+        .may_load(
+            deps.storage,
+            (owner, spender, AssetInfoKey::from(query.asset)),
+        )?
+        .unwrap_or_default();
+
+    to_json_binary(&allowance)
+}
+
+fn get_receipt_denoms(deps: Deps, env: Env) -> StdResult<Binary> {
+    let receipt_denoms = RECEIPT_DENOMS
+        .range(deps.storage, None, None, Order::Ascending)
+        .map(|item| {
+            let (asset, denom) = item?;
+            let asset = asset.check(deps.api, None)?;
+            let asset_key = AssetInfoKey::from(&asset);
+            let issued = ISSUED_RECEIPTS
+                .may_load(deps.storage, asset_key.clone())?
+                .unwrap_or_default();
+            let (asset_reward_index, _) =
+                current_asset_reward_index(deps.storage, env.block.time, asset_key.clone())?;
+            let receipt_reward_index = RECEIPT_REWARD_INDEX
+                .may_load(deps.storage, asset_key.clone())?
+                .unwrap_or(asset_reward_index);
+            let (rewards, _) = accrued_rewards(asset_reward_index, receipt_reward_index, issued)?;
+            let owed = RECEIPT_REWARDS
+                .may_load(deps.storage, asset_key)?
+                .unwrap_or_default();
+            Ok(ReceiptDenomRes {
+                asset,
+                denom,
+                issued,
+                rewards: owed + rewards,
+            })
+        })
+        .collect::<StdResult<Vec<ReceiptDenomRes>>>()?;
+
+    to_json_binary(&receipt_denoms)
+}
+
 fn get_whitelisted_assets(deps: Deps) -> StdResult<Binary> {
     let whitelist = WHITELIST.range(deps.storage, None, None, Order::Ascending);
     let mut res: WhitelistedAssetsResponse = HashMap::new();
@@ -61,29 +274,26 @@ fn get_rewards_distribution(deps: Deps) -> StdResult<Binary> {
     to_json_binary(&asset_rewards_distr)
 }
 
-fn get_staked_balance(deps: Deps, asset_query: AssetQuery) -> StdResult<Binary> {
+fn get_staked_balance(deps: Deps, env: Env, asset_query: AssetQuery) -> StdResult<Binary> {
     let addr = deps.api.addr_validate(&asset_query.address)?;
     let key = (addr, asset_query.asset.clone().into());
-    let balance = BALANCES.load(deps.storage, key)?;
+    let balance = BALANCES.load(deps.storage, key.clone())?;
 
     to_json_binary(&StakedBalanceRes {
         asset: asset_query.asset,
         balance,
+        effective_balance: current_effective_balance(deps.storage, key, env.block.time)?,
     })
 }
 
-fn get_pending_rewards(deps: Deps, asset_query: AssetQuery) -> StdResult<Binary> {
+fn get_pending_rewards(deps: Deps, env: Env, asset_query: AssetQuery) -> StdResult<Binary> {
     let config = CONFIG.load(deps.storage)?;
     let addr = deps.api.addr_validate(&asset_query.address)?;
     let key = (addr, AssetInfoKey::from(asset_query.asset.clone()));
-    let asset_reward_rate =
-        ASSET_REWARD_RATE.load(deps.storage, AssetInfoKey::from(asset_query.asset.clone()))?;
-    let user_reward_rate = USER_ASSET_REWARD_RATE.load(deps.storage, key.clone()).unwrap_or(asset_reward_rate);
-    let user_balance = BALANCES.load(deps.storage, key.clone()).unwrap_or(Uint128::zero());
+    let pending_rewards = pending_rewards(deps, &env, key.clone())?;
     let unclaimed_rewards = UNCLAIMED_REWARDS
         .load(deps.storage, key)
         .unwrap_or(Uint128::zero());
-    let pending_rewards = (asset_reward_rate - user_reward_rate) * user_balance;
 
     to_json_binary(&PendingRewardsRes {
         rewards: unclaimed_rewards + pending_rewards,
@@ -92,7 +302,33 @@ fn get_pending_rewards(deps: Deps, asset_query: AssetQuery) -> StdResult<Binary>
     })
 }
 
-fn get_all_staked_balances(deps: Deps, asset_query: AllStakedBalancesQuery) -> StdResult<Binary> {
+// Rewards the user would settle at the current block time
+fn pending_rewards(deps: Deps, env: &Env, key: (Addr, AssetInfoKey)) -> StdResult<Uint128> {
+    let (asset_reward_index, expiry_indexes) =
+        current_asset_reward_index(deps.storage, env.block.time, key.1.clone())?;
+    let user_reward_index = match load_user_reward_index(deps.storage, key.clone())? {
+        Some(user_reward_index) => user_reward_index,
+        None => return Ok(Uint128::zero()),
+    };
+    let accrual = accrue_user_rewards(
+        deps.storage,
+        key.clone(),
+        user_reward_index,
+        asset_reward_index,
+        |unlock_time| {
+            Ok(EXPIRED_LOCK_INDEXES
+                .may_load(deps.storage, (key.1.clone(), unlock_time))?
+                .or_else(|| expiry_indexes.get(&unlock_time).copied()))
+        },
+    )?;
+    Ok(accrual.rewards)
+}
+
+fn get_all_staked_balances(
+    deps: Deps,
+    env: Env,
+    asset_query: AllStakedBalancesQuery,
+) -> StdResult<Binary> {
     let addr = deps.api.addr_validate(&asset_query.address)?;
     let whitelist = WHITELIST.range(deps.storage, None, None, Order::Ascending);
     let mut res: Vec<StakedBalanceRes> = Vec::new();
@@ -104,41 +340,49 @@ fn get_all_staked_balances(deps: Deps, asset_query: AllStakedBalancesQuery) -> S
         let asset_info_key = AssetInfoKey::from(checked_asset_info.clone());
         let stake_key = (addr.clone(), asset_info_key);
         let balance = BALANCES
-            .load(deps.storage, stake_key)
+            .load(deps.storage, stake_key.clone())
             .unwrap_or(Uint128::zero());
 
         // Append the request
         res.push(StakedBalanceRes {
             asset: checked_asset_info,
             balance,
+            effective_balance: current_effective_balance(deps.storage, stake_key, env.block.time)?,
         })
     }
 
     to_json_binary(&res)
 }
 
-fn get_all_pending_rewards(deps: Deps, query: AllPendingRewardsQuery) -> StdResult<Binary> {
+fn get_all_pending_rewards(
+    deps: Deps,
+    env: Env,
+    query: AllPendingRewardsQuery,
+) -> StdResult<Binary> {
     let config = CONFIG.load(deps.storage)?;
     let addr = deps.api.addr_validate(&query.address)?;
-    let all_pending_rewards: StdResult<Vec<PendingRewardsRes>> = USER_ASSET_REWARD_RATE
+    // Users that did not update their rewards since the index was scaled
+    // still have their reward rates stored by the previous versions, each
+    // asset is stored in only one of them
+    let assets = USER_ASSET_REWARD_INDEX
         .prefix(addr.clone())
-        .range(deps.storage, None, None, Order::Ascending)
-        .map(|item| {
-            let (asset, user_reward_rate) = item?;
+        .keys(deps.storage, None, None, Order::Ascending)
+        .chain(LEGACY_USER_ASSET_REWARD_RATE.prefix(addr.clone()).keys(
+            deps.storage,
+            None,
+            None,
+            Order::Ascending,
+        ))
+        .collect::<StdResult<Vec<_>>>()?;
+    let all_pending_rewards: StdResult<Vec<PendingRewardsRes>> = assets
+        .into_iter()
+        .map(|asset| {
             let asset = asset.check(deps.api, None)?;
-            let asset_reward_rate =
-                ASSET_REWARD_RATE.load(deps.storage, AssetInfoKey::from(asset.clone()))?;
-            let user_balance = BALANCES.load(
-                deps.storage,
-                (addr.clone(), AssetInfoKey::from(asset.clone())),
-            ).unwrap_or(Uint128::zero());
+            let key = (addr.clone(), AssetInfoKey::from(asset.clone()));
+            let pending_rewards = pending_rewards(deps, &env, key.clone())?;
             let unclaimed_rewards = UNCLAIMED_REWARDS
-                .load(
-                    deps.storage,
-                    (addr.clone(), AssetInfoKey::from(asset.clone())),
-                )
+                .load(deps.storage, key)
                 .unwrap_or(Uint128::zero());
-            let pending_rewards = (asset_reward_rate - user_reward_rate) * user_balance;
             Ok(PendingRewardsRes {
                 rewards: pending_rewards + unclaimed_rewards,
                 staked_asset: asset,
@@ -150,13 +394,19 @@ fn get_all_pending_rewards(deps: Deps, query: AllPendingRewardsQuery) -> StdResu
     to_json_binary(&all_pending_rewards?)
 }
 
-fn get_total_staked_balances(deps: Deps) -> StdResult<Binary> {
+fn get_total_staked_balances(deps: Deps, env: Env) -> StdResult<Binary> {
     let total_staked_balances: StdResult<Vec<StakedBalanceRes>> = TOTAL_BALANCES
         .range(deps.storage, None, None, Order::Ascending)
         .map(|total_balance| -> StdResult<StakedBalanceRes> {
             let (asset, balance) = total_balance?;
+            let asset = asset.check(deps.api, None)?;
             Ok(StakedBalanceRes {
-                asset: asset.check(deps.api, None)?,
+                effective_balance: current_total_effective_balance(
+                    deps.storage,
+                    AssetInfoKey::from(&asset),
+                    env.block.time,
+                )?,
+                asset,
                 balance,
             })
         })
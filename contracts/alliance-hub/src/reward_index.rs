@@ -0,0 +1,173 @@
+use cosmwasm_std::{Addr, Decimal, StdResult, Storage, Timestamp, Uint128, Uint256};
+use cw_asset::AssetInfoKey;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+
+use crate::lockup::{
+    boosted_positions, due_lock_expiries, effective_balance, lock_boost, total_effective_balance,
+};
+use crate::models::{LockedPosition, RewardStream};
+use crate::state::{
+    ASSET_REWARD_INDEX, LEGACY_USER_ASSET_REWARD_RATE, REWARD_STREAMS, USER_ASSET_REWARD_INDEX,
+};
+
+// Reward indexes are the rewards per staked token scaled by 1e36, so that small rewards
+// split among large balances of 18 decimals tokens do not round down to zero
+const INDEX_SCALE: u128 = 1_000_000_000_000_000_000_000_000_000_000_000_000;
+// Decimal stores 18 decimal places, the index stores 18 more
+const DECIMAL_TO_INDEX: u128 = 1_000_000_000_000_000_000;
+
+pub fn index_from_rate(rate: Decimal) -> Uint256 {
+    Uint256::from(rate.atomics()) * Uint256::from(DECIMAL_TO_INDEX)
+}
+
+// Increase of the index for rewards split among total_balance, along with
+// the rewards left over by the rounding
+pub fn index_increase(rewards: Decimal, total_balance: Uint128) -> StdResult<(Uint256, Decimal)> {
+    let scaled_rewards = index_from_rate(rewards);
+    let total_balance = Uint256::from(total_balance);
+    let increase = scaled_rewards / total_balance;
+    let remainder = scaled_rewards - increase * total_balance;
+    Ok((increase, scaled_to_decimal(remainder)?))
+}
+
+// Rewards accrued by staked since user_index, in whole tokens along with
+// the fraction of a token that cannot be paid out
+pub fn accrued_rewards(
+    index: Uint256,
+    user_index: Uint256,
+    staked: Uint128,
+) -> StdResult<(Uint128, Decimal)> {
+    let scaled_rewards = index
+        .checked_sub(user_index)?
+        .checked_mul(Uint256::from(staked))?;
+    let rewards = scaled_rewards / Uint256::from(INDEX_SCALE);
+    let fraction = scaled_rewards - rewards * Uint256::from(INDEX_SCALE);
+    Ok((Uint128::try_from(rewards)?, scaled_to_decimal(fraction)?))
+}
+
+fn scaled_to_decimal(amount: Uint256) -> StdResult<Decimal> {
+    let atomics = amount / Uint256::from(DECIMAL_TO_INDEX);
+    Ok(Decimal::new(Uint128::try_from(atomics)?))
+}
+
+// Rewards of the stream released since its last update
+pub fn released_rewards(stream: &RewardStream, now: Timestamp) -> Decimal {
+    if now >= stream.end {
+        return stream.amount;
+    }
+    if now <= stream.last_update {
+        return Decimal::zero();
+    }
+    let elapsed = now.seconds() - stream.last_update.seconds();
+    let duration = stream.end.seconds() - stream.last_update.seconds();
+    stream.amount * Decimal::from_ratio(elapsed, duration)
+}
+
+// Reward index of the asset including the streamed rewards released up to now
+// that have not been distributed yet, along with the indexes at the unlock times
+// of the positions that expired meanwhile
+pub fn current_asset_reward_index(
+    storage: &dyn Storage,
+    now: Timestamp,
+    asset_key: AssetInfoKey,
+) -> StdResult<(Uint256, HashMap<u64, Uint256>)> {
+    let mut index = ASSET_REWARD_INDEX.load(storage, asset_key.clone())?;
+    let mut stream = REWARD_STREAMS.may_load(storage, asset_key.clone())?;
+    let mut total_balance = total_effective_balance(storage, asset_key.clone())?;
+    let mut expiry_indexes = HashMap::new();
+    for (unlock_time, boost) in due_lock_expiries(storage, asset_key, now)? {
+        index += streamed_increase(
+            &mut stream,
+            Timestamp::from_seconds(unlock_time),
+            total_balance,
+        )?;
+        expiry_indexes.insert(unlock_time, index);
+        total_balance -= boost;
+    }
+    index += streamed_increase(&mut stream, now, total_balance)?;
+    Ok((index, expiry_indexes))
+}
+
+fn streamed_increase(
+    stream: &mut Option<RewardStream>,
+    now: Timestamp,
+    total_balance: Uint128,
+) -> StdResult<Uint256> {
+    let stream = match stream {
+        Some(stream) if now > stream.last_update => stream,
+        _ => return Ok(Uint256::zero()),
+    };
+    let released = released_rewards(stream, now);
+    stream.amount -= released;
+    stream.last_update = now;
+    if total_balance.is_zero() {
+        return Ok(Uint256::zero());
+    }
+    Ok(index_increase(released, total_balance)?.0)
+}
+
+pub struct UserAccrual {
+    pub rewards: Uint128,
+    pub fraction: Decimal,
+    // Positions that stopped being boosted, by lock duration
+    pub expired: Vec<(u64, LockedPosition)>,
+}
+
+// Rewards accrued by the user from user_index up to index, the boost of each expired
+// position only counts up to the index of the asset at its unlock time
+pub fn accrue_user_rewards(
+    storage: &dyn Storage,
+    key: (Addr, AssetInfoKey),
+    mut user_index: Uint256,
+    index: Uint256,
+    expiry_index: impl Fn(u64) -> StdResult<Option<Uint256>>,
+) -> StdResult<UserAccrual> {
+    let mut staked = effective_balance(storage, key.clone())?;
+    let mut accrual = UserAccrual {
+        rewards: Uint128::zero(),
+        fraction: Decimal::zero(),
+        expired: vec![],
+    };
+    for (duration, position) in boosted_positions(storage, key.0, key.1)? {
+        // Positions are sorted by unlock time so the next ones did not expire either
+        let expiry_index = match expiry_index(position.unlock_time.seconds())? {
+            Some(expiry_index) => expiry_index,
+            None => break,
+        };
+        if expiry_index > user_index {
+            let (rewards, fraction) = accrued_rewards(expiry_index, user_index, staked)?;
+            accrual.rewards += rewards;
+            accrual.fraction += fraction;
+            user_index = expiry_index;
+        }
+        staked -= lock_boost(position.amount, position.multiplier);
+        accrual.expired.push((duration, position));
+    }
+    let (rewards, fraction) = accrued_rewards(index, user_index, staked)?;
+    accrual.rewards += rewards;
+    accrual.fraction += fraction;
+    Ok(accrual)
+}
+
+// User rates stored before the index was scaled are migrated when they are updated
+pub fn load_user_reward_index(
+    storage: &dyn Storage,
+    key: (Addr, AssetInfoKey),
+) -> StdResult<Option<Uint256>> {
+    if let Some(index) = USER_ASSET_REWARD_INDEX.may_load(storage, key.clone())? {
+        return Ok(Some(index));
+    }
+    Ok(LEGACY_USER_ASSET_REWARD_RATE
+        .may_load(storage, key)?
+        .map(index_from_rate))
+}
+
+pub fn save_user_reward_index(
+    storage: &mut dyn Storage,
+    key: (Addr, AssetInfoKey),
+    index: &Uint256,
+) -> StdResult<()> {
+    LEGACY_USER_ASSET_REWARD_RATE.remove(storage, key.clone());
+    USER_ASSET_REWARD_INDEX.save(storage, key, index)
+}
@@ -1,25 +1,83 @@
 use alliance_protocol::alliance_oracle_types::ChainId;
 use alliance_protocol::alliance_protocol::AssetDistribution;
-use cosmwasm_std::{Addr, Decimal, Uint128};
-use cw_asset::AssetInfoKey;
+use cosmwasm_std::{Addr, Decimal, Uint128, Uint256};
+use cw_asset::{AssetInfo, AssetInfoKey};
 use cw_storage_plus::{Item, Map};
-use std::collections::HashSet;
+use std::collections::{HashSet, VecDeque};
 
-use crate::models::Config;
+use crate::models::{
+    AllianceTokenParams, ClaimFailure, Config, DelegationStrategy, LockTier, LockedPosition,
+    ProtocolFee, RewardStream,
+};
 
 pub const CONFIG: Item<Config> = Item::new("config");
+// Only used between instantiate and the token creation reply
+pub const ALLIANCE_TOKEN_PARAMS: Item<AllianceTokenParams> = Item::new("alliance_token_params");
 pub const WHITELIST: Map<AssetInfoKey, ChainId> = Map::new("whitelist");
 
 pub const BALANCES: Map<(Addr, AssetInfoKey), Uint128> = Map::new("balances");
 pub const TOTAL_BALANCES: Map<AssetInfoKey, Uint128> = Map::new("total_balances");
+// Token factory denoms of the receipts of the staked assets
+pub const RECEIPT_DENOMS: Map<AssetInfoKey, String> = Map::new("receipt_denoms");
+pub const RECEIPT_ASSETS: Map<&str, AssetInfo> = Map::new("receipt_assets");
+// Receipts withdrawn from the hub, their tokens keep earning rewards for the receipt holders
+pub const ISSUED_RECEIPTS: Map<AssetInfoKey, Uint128> = Map::new("issued_receipts");
+// Rewards owed to the issued receipts and the asset reward index they were settled at
+pub const RECEIPT_REWARDS: Map<AssetInfoKey, Uint128> = Map::new("receipt_rewards");
+pub const RECEIPT_REWARD_INDEX: Map<AssetInfoKey, Uint256> = Map::new("receipt_reward_index");
+// Only used between CreateReceiptDenom and the denom creation reply
+pub const PENDING_RECEIPT_ASSET: Item<AssetInfo> = Item::new("pending_receipt_asset");
+// Stake that each spender can transfer by (owner, spender, asset)
+pub const STAKE_ALLOWANCES: Map<(Addr, Addr, AssetInfoKey), Uint128> = Map::new("stake_allowances");
+pub const LOCK_TIERS: Item<Vec<LockTier>> = Item::new("lock_tiers");
+// Positions by (user, asset, lock duration), their tokens are included in the balances
+pub const LOCKED_POSITIONS: Map<(Addr, AssetInfoKey, u64), LockedPosition> =
+    Map::new("locked_positions");
+// Weight added to the balances by the multipliers of the locked positions
+pub const BALANCE_BOOSTS: Map<(Addr, AssetInfoKey), Uint128> = Map::new("balance_boosts");
+pub const TOTAL_BALANCE_BOOSTS: Map<AssetInfoKey, Uint128> = Map::new("total_balance_boosts");
+// Boost of the locked positions of each asset by their unlock time in seconds
+pub const LOCK_EXPIRIES: Map<(AssetInfoKey, u64), Uint128> = Map::new("lock_expiries");
+// Reward index of the asset when the positions unlocking at the time stopped being boosted
+pub const EXPIRED_LOCK_INDEXES: Map<(AssetInfoKey, u64), Uint256> =
+    Map::new("expired_lock_indexes");
+// Boosted positions of each asset by their unlock time, the expired lock index
+// is removed once none of them is left
+pub const LOCK_EXPIRY_POSITIONS: Map<(AssetInfoKey, u64), u32> = Map::new("lock_expiry_positions");
 
 pub const VALIDATORS: Item<HashSet<String>> = Item::new("validators");
+// Validators that can receive alliance token delegations
+pub const ALLOWED_VALIDATORS: Item<HashSet<String>> = Item::new("allowed_validators");
+// Alliance tokens delegated to each validator
+pub const DELEGATIONS: Map<&str, Uint128> = Map::new("delegations");
+pub const DELEGATION_STRATEGY: Item<DelegationStrategy> = Item::new("delegation_strategy");
+pub const UNBONDING_PERIOD: Item<u64> = Item::new("unbonding_period");
+// Alliance tokens being undelegated by (completion time in seconds, validator)
+pub const UNBONDINGS: Map<(u64, &str), Uint128> = Map::new("unbondings");
 
 pub const ASSET_REWARD_DISTRIBUTION: Item<Vec<AssetDistribution>> =
     Item::new("asset_reward_distribution");
-pub const ASSET_REWARD_RATE: Map<AssetInfoKey, Decimal> = Map::new("asset_reward_rate");
-pub const USER_ASSET_REWARD_RATE: Map<(Addr, AssetInfoKey), Decimal> =
-    Map::new("user_asset_reward_rate");
+pub const ASSET_REWARD_INDEX: Map<AssetInfoKey, Uint256> = Map::new("asset_reward_index");
+pub const USER_ASSET_REWARD_INDEX: Map<(Addr, AssetInfoKey), Uint256> =
+    Map::new("user_asset_reward_index");
 pub const UNCLAIMED_REWARDS: Map<(Addr, AssetInfoKey), Uint128> = Map::new("unclaimed_rewards");
 
-pub const TEMP_BALANCE: Item<Uint128> = Item::new("temp_balance");
+// Rewards owed to the stakers that have not been claimed yet and the dust
+pub const RESERVED_REWARDS: Item<Uint128> = Item::new("reserved_rewards");
+// Validators of the claim messages sent by UpdateRewards in the order of their replies
+pub const PENDING_CLAIMS: Item<VecDeque<String>> = Item::new("pending_claims");
+pub const CLAIM_FAILURES: Map<&str, ClaimFailure> = Map::new("claim_failures");
+// Rewards that could not be paid to the stakers of each asset, kept in token units
+pub const DUST: Map<AssetInfoKey, Decimal> = Map::new("dust");
+pub const DUST_RECIPIENT: Item<Addr> = Item::new("dust_recipient");
+pub const PROTOCOL_FEE: Item<ProtocolFee> = Item::new("protocol_fee");
+// Seconds over which collected rewards are released, released at once when zero
+pub const REWARD_STREAM_PERIOD: Item<u64> = Item::new("reward_stream_period");
+pub const REWARD_STREAMS: Map<AssetInfoKey, RewardStream> = Map::new("reward_streams");
+// Total reward tokens sent to the fee collector
+pub const FEES_COLLECTED: Item<Uint128> = Item::new("fees_collected");
+
+// Reward rates of the previous versions, only used to migrate them to the reward indexes
+pub const LEGACY_ASSET_REWARD_RATE: Map<AssetInfoKey, Decimal> = Map::new("asset_reward_rate");
+pub const LEGACY_USER_ASSET_REWARD_RATE: Map<(Addr, AssetInfoKey), Decimal> =
+    Map::new("user_asset_reward_rate");
@@ -1,24 +1,41 @@
-use crate::contract::execute;
-use crate::models::{Config, ExecuteMsg};
-use crate::state::{CONFIG, VALIDATORS};
+use crate::contract::{execute, migrate};
+use crate::models::{
+    AllianceTokenBalanceRes, Config, ExecuteMsg, MigrateMsg, QueryMsg, UnbondingEntry,
+};
+use crate::query::query;
+use crate::state::{CONFIG, DELEGATIONS, UNBONDINGS, UNBONDING_PERIOD, VALIDATORS};
 use crate::tests::helpers::{
-    alliance_delegate, alliance_redelegate, alliance_undelegate, setup_contract,
+    alliance_delegate, alliance_redelegate, alliance_undelegate, allow_validators,
+    set_alliance_asset, setup_contract, DENOM,
 };
 use alliance_protocol::alliance_protocol::{
     AllianceDelegateMsg, AllianceDelegation, AllianceUndelegateMsg,
 };
 use alliance_protocol::error::ContractError;
-use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info};
-use cosmwasm_std::{Binary, CosmosMsg, StdResult, SubMsg, Uint128};
+use alliance_protocol::token_factory::{CustomExecuteMsg, TokenExecuteMsg};
+use cosmwasm_std::testing::{
+    mock_dependencies, mock_dependencies_with_balance, mock_env, mock_info, MockApi, MockQuerier,
+    MockStorage,
+};
+use cosmwasm_std::{
+    attr, coin, from_json, to_json_binary, Binary, ContractResult, CosmosMsg, Decimal, Empty,
+    Order, OwnedDeps, Querier, QuerierResult, QueryRequest, Response, StakingQuery, StdResult,
+    SubMsg, SystemResult, Timestamp, Uint128, Validator, ValidatorResponse,
+};
 use std::collections::HashSet;
+use std::marker::PhantomData;
 use terra_proto_rs::alliance::alliance::{MsgDelegate, MsgRedelegate};
 use terra_proto_rs::cosmos::base::v1beta1::Coin;
 use terra_proto_rs::traits::Message;
 
 #[test]
 fn test_alliance_delegate() {
-    let mut deps = mock_dependencies();
+    let mut deps = mock_dependencies_with_balance(&[coin(500, DENOM)]);
     setup_contract(deps.as_mut());
+    allow_validators(
+        deps.as_mut(),
+        vec!["validator1", "validator2", "validator3", "validator4"],
+    );
 
     let denom = "token_factory/token";
     // set alliance token denom
@@ -135,6 +152,12 @@ fn test_alliance_undelegate() {
             })
         })
         .unwrap();
+    DELEGATIONS
+        .save(deps.as_mut().storage, "validator1", &Uint128::new(100))
+        .unwrap();
+    DELEGATIONS
+        .save(deps.as_mut().storage, "validator2", &Uint128::new(400))
+        .unwrap();
 
     let res = alliance_undelegate(
         deps.as_mut(),
@@ -220,12 +243,42 @@ fn test_alliance_undelegation_invalid() {
     )
     .unwrap_err();
     assert_eq!(err, ContractError::EmptyDelegation {});
+
+    // Only the tracked delegations can be undelegated
+    DELEGATIONS
+        .save(deps.as_mut().storage, "validator1", &Uint128::new(50))
+        .unwrap();
+    let info = mock_info("controller", &[]);
+    let msg = AllianceUndelegateMsg {
+        undelegations: vec![AllianceDelegation {
+            validator: "validator1".to_string(),
+            amount: Uint128::new(100),
+        }],
+    };
+    let err = execute(
+        deps.as_mut(),
+        mock_env(),
+        info,
+        ExecuteMsg::AllianceUndelegate(msg),
+    )
+    .unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::InsufficientDelegation {
+            validator: "validator1".to_string(),
+            delegated: Uint128::new(50),
+        }
+    );
 }
 
 #[test]
 fn test_alliance_redelegate() {
     let mut deps = mock_dependencies();
     setup_contract(deps.as_mut());
+    allow_validators(
+        deps.as_mut(),
+        vec!["validator1", "validator2", "validator3", "validator4"],
+    );
     let denom = "token_factory/token";
     // set alliance token denom
     CONFIG
@@ -237,6 +290,12 @@ fn test_alliance_redelegate() {
             })
         })
         .unwrap();
+    DELEGATIONS
+        .save(deps.as_mut().storage, "validator1", &Uint128::new(100))
+        .unwrap();
+    DELEGATIONS
+        .save(deps.as_mut().storage, "validator2", &Uint128::new(300))
+        .unwrap();
 
     let res = alliance_redelegate(
         deps.as_mut(),
@@ -288,3 +347,515 @@ fn test_alliance_redelegate() {
         HashSet::from(["validator2".to_string(), "validator3".to_string()])
     );
 }
+
+#[test]
+fn test_mint_alliance_tokens() {
+    let mut deps = mock_dependencies();
+    setup_contract(deps.as_mut());
+    set_alliance_asset(deps.as_mut());
+
+    let msg = ExecuteMsg::MintAllianceTokens {
+        amount: Uint128::new(500),
+    };
+    let err = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("controller", &[]),
+        msg.clone(),
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::Unauthorized {});
+
+    let res = execute(deps.as_mut(), mock_env(), mock_info("gov", &[]), msg).unwrap();
+    assert_eq!(
+        res,
+        Response::new()
+            .add_attributes(vec![
+                ("action", "mint_alliance_tokens"),
+                ("amount", "500"),
+                ("alliance_token_total_supply", "1000000000500"),
+            ])
+            .add_message(CosmosMsg::Custom(CustomExecuteMsg::Token(
+                TokenExecuteMsg::MintTokens {
+                    denom: DENOM.to_string(),
+                    amount: Uint128::new(500),
+                    mint_to_address: "cosmos2contract".to_string(),
+                }
+            )))
+    );
+    let config = CONFIG.load(deps.as_ref().storage).unwrap();
+    assert_eq!(config.alliance_token_supply, Uint128::new(1000000000500));
+}
+
+#[test]
+fn test_burn_alliance_tokens() {
+    // Only 1000 tokens are not delegated
+    let mut deps = mock_dependencies_with_balance(&[coin(1000, DENOM)]);
+    setup_contract(deps.as_mut());
+    set_alliance_asset(deps.as_mut());
+
+    let err = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("gov", &[]),
+        ExecuteMsg::BurnAllianceTokens {
+            amount: Uint128::new(1001),
+        },
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::InsufficientBalance {});
+
+    let err = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("gov", &[]),
+        ExecuteMsg::BurnAllianceTokens {
+            amount: Uint128::zero(),
+        },
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::AmountCannotBeZero {});
+
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("gov", &[]),
+        ExecuteMsg::BurnAllianceTokens {
+            amount: Uint128::new(1000),
+        },
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(CosmosMsg::Custom(CustomExecuteMsg::Token(
+            TokenExecuteMsg::BurnTokens {
+                denom: DENOM.to_string(),
+                amount: Uint128::new(1000),
+                burn_from_address: "cosmos2contract".to_string(),
+            }
+        )))]
+    );
+    let config = CONFIG.load(deps.as_ref().storage).unwrap();
+    assert_eq!(config.alliance_token_supply, Uint128::new(999999999000));
+}
+
+#[test]
+fn test_alliance_messages_without_denom() {
+    let mut deps = mock_dependencies();
+    setup_contract(deps.as_mut());
+
+    let err = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("controller", &[]),
+        ExecuteMsg::AllianceDelegate(AllianceDelegateMsg {
+            delegations: vec![AllianceDelegation {
+                validator: "validator1".to_string(),
+                amount: Uint128::new(100),
+            }],
+        }),
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::AllianceTokenDenomNotSet {});
+
+    let err = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("gov", &[]),
+        ExecuteMsg::MintAllianceTokens {
+            amount: Uint128::new(100),
+        },
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::AllianceTokenDenomNotSet {});
+}
+
+#[test]
+fn test_allowed_validators() {
+    let validator_1 = "terravaloper1qyqszqgpqyqszqgpqyqszqgpqyqszqgp5chrl0";
+    let validator_2 = "terravaloper1qgpqyqszqgpqyqszqgpqyqszqgpqyqsz9u3x5e";
+    let mut deps = mock_dependencies_with_balance(&[coin(1000, DENOM)]);
+    deps.querier.update_staking(
+        "uluna",
+        &[Validator {
+            address: validator_1.to_string(),
+            commission: Decimal::percent(5),
+            max_commission: Decimal::percent(10),
+            max_change_rate: Decimal::percent(1),
+        }],
+        &[],
+    );
+    setup_contract(deps.as_mut());
+    set_alliance_asset(deps.as_mut());
+
+    let err = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("controller", &[]),
+        ExecuteMsg::AddAllowedValidators(vec![validator_1.to_string()]),
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::Unauthorized {});
+
+    for invalid in [
+        "validator1",
+        "terra1qyqszqgpqyqszqgpqyqszqgpqyqszqgp5hm70u",
+        "terravaloper1qyqszqgpqyqszqgpqyqszqgpqyqszqgp5chrl1",
+    ] {
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("gov", &[]),
+            ExecuteMsg::AddAllowedValidators(vec![invalid.to_string()]),
+        )
+        .unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::InvalidValidatorAddress(invalid.to_string())
+        );
+    }
+
+    // validator_2 is a valid address that is not in the active set
+    let err = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("gov", &[]),
+        ExecuteMsg::AddAllowedValidators(vec![validator_1.to_string(), validator_2.to_string()]),
+    )
+    .unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::ValidatorNotBonded(validator_2.to_string())
+    );
+
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("gov", &[]),
+        ExecuteMsg::AddAllowedValidators(vec![validator_1.to_string()]),
+    )
+    .unwrap();
+    assert_eq!(
+        res.attributes,
+        vec![
+            attr("action", "add_allowed_validators"),
+            attr("validators", validator_1),
+        ]
+    );
+    let res = query(deps.as_ref(), mock_env(), QueryMsg::AllowedValidators {}).unwrap();
+    let allowed: HashSet<String> = from_json(res).unwrap();
+    assert_eq!(allowed, HashSet::from([validator_1.to_string()]));
+
+    let delegate = |validator: &str| {
+        ExecuteMsg::AllianceDelegate(AllianceDelegateMsg {
+            delegations: vec![AllianceDelegation {
+                validator: validator.to_string(),
+                amount: Uint128::new(100),
+            }],
+        })
+    };
+    let err = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("controller", &[]),
+        delegate(validator_2),
+    )
+    .unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::ValidatorNotAllowed(validator_2.to_string())
+    );
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("controller", &[]),
+        delegate(validator_1),
+    )
+    .unwrap();
+
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("gov", &[]),
+        ExecuteMsg::RemoveAllowedValidators(vec![validator_1.to_string()]),
+    )
+    .unwrap();
+    let err = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("controller", &[]),
+        delegate(validator_1),
+    )
+    .unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::ValidatorNotAllowed(validator_1.to_string())
+    );
+}
+
+#[test]
+fn test_alliance_token_balance() {
+    let mut deps = mock_dependencies_with_balance(&[coin(1000, DENOM)]);
+    setup_contract(deps.as_mut());
+    set_alliance_asset(deps.as_mut());
+    allow_validators(deps.as_mut(), vec!["validator1"]);
+
+    alliance_delegate(deps.as_mut(), vec![("validator1", 600)]);
+    deps.querier
+        .update_balance("cosmos2contract", vec![coin(400, DENOM)]);
+
+    // Delegations cannot exceed the free balance
+    let err = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("controller", &[]),
+        ExecuteMsg::AllianceDelegate(AllianceDelegateMsg {
+            delegations: vec![AllianceDelegation {
+                validator: "validator1".to_string(),
+                amount: Uint128::new(401),
+            }],
+        }),
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::InsufficientBalance {});
+
+    alliance_undelegate(deps.as_mut(), vec![("validator1", 200)]);
+    let completion_time =
+        Timestamp::from_seconds(mock_env().block.time.seconds() + 21 * 24 * 60 * 60);
+    let res = query(deps.as_ref(), mock_env(), QueryMsg::Unbondings {}).unwrap();
+    let unbondings: Vec<UnbondingEntry> = from_json(res).unwrap();
+    assert_eq!(
+        unbondings,
+        vec![UnbondingEntry {
+            validator: "validator1".to_string(),
+            amount: Uint128::new(200),
+            completion_time,
+        }]
+    );
+    let res = query(deps.as_ref(), mock_env(), QueryMsg::AllianceTokenBalance {}).unwrap();
+    let balance: AllianceTokenBalanceRes = from_json(res).unwrap();
+    assert_eq!(
+        balance,
+        AllianceTokenBalanceRes {
+            free: Uint128::new(400),
+            delegated: Uint128::new(400),
+            unbonding: Uint128::new(200),
+        }
+    );
+
+    // Completed unbondings are part of the free balance
+    let mut env = mock_env();
+    env.block.time = completion_time;
+    deps.querier
+        .update_balance("cosmos2contract", vec![coin(600, DENOM)]);
+    let res = query(
+        deps.as_ref(),
+        env.clone(),
+        QueryMsg::AllianceTokenBalance {},
+    )
+    .unwrap();
+    let balance: AllianceTokenBalanceRes = from_json(res).unwrap();
+    assert_eq!(
+        balance,
+        AllianceTokenBalanceRes {
+            free: Uint128::new(600),
+            delegated: Uint128::new(400),
+            unbonding: Uint128::zero(),
+        }
+    );
+
+    // and they are removed on the next undelegation
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("controller", &[]),
+        ExecuteMsg::AllianceUndelegate(AllianceUndelegateMsg {
+            undelegations: vec![AllianceDelegation {
+                validator: "validator1".to_string(),
+                amount: Uint128::new(100),
+            }],
+        }),
+    )
+    .unwrap();
+    let unbondings = UNBONDINGS
+        .range(deps.as_ref().storage, None, None, Order::Ascending)
+        .collect::<StdResult<Vec<_>>>()
+        .unwrap();
+    assert_eq!(
+        unbondings,
+        vec![(
+            (
+                completion_time.plus_seconds(21 * 24 * 60 * 60).seconds(),
+                "validator1".to_string()
+            ),
+            Uint128::new(100)
+        )]
+    );
+}
+
+#[test]
+fn test_update_unbonding_period() {
+    let mut deps = mock_dependencies_with_balance(&[coin(1000, DENOM)]);
+    setup_contract(deps.as_mut());
+    set_alliance_asset(deps.as_mut());
+    allow_validators(deps.as_mut(), vec!["validator1"]);
+    alliance_delegate(deps.as_mut(), vec![("validator1", 600)]);
+
+    let err = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("controller", &[]),
+        ExecuteMsg::UpdateUnbondingPeriod { seconds: 100 },
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::Unauthorized {});
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("gov", &[]),
+        ExecuteMsg::UpdateUnbondingPeriod { seconds: 100 },
+    )
+    .unwrap();
+    assert_eq!(
+        res.attributes,
+        vec![
+            attr("action", "update_unbonding_period"),
+            attr("seconds", "100")
+        ]
+    );
+
+    alliance_undelegate(deps.as_mut(), vec![("validator1", 200)]);
+    let res = query(deps.as_ref(), mock_env(), QueryMsg::Unbondings {}).unwrap();
+    let unbondings: Vec<UnbondingEntry> = from_json(res).unwrap();
+    assert_eq!(
+        unbondings,
+        vec![UnbondingEntry {
+            validator: "validator1".to_string(),
+            amount: Uint128::new(200),
+            completion_time: Timestamp::from_seconds(mock_env().block.time.seconds() + 100),
+        }]
+    );
+
+    // Contracts migrating without an unbonding period get the default one
+    UNBONDING_PERIOD.remove(deps.as_mut().storage);
+    migrate(deps.as_mut(), mock_env(), MigrateMsg::default()).unwrap();
+    assert_eq!(
+        UNBONDING_PERIOD.load(deps.as_ref().storage).unwrap(),
+        21 * 24 * 60 * 60
+    );
+    // and an existing one is kept
+    UNBONDING_PERIOD.save(deps.as_mut().storage, &100).unwrap();
+    migrate(deps.as_mut(), mock_env(), MigrateMsg::default()).unwrap();
+    assert_eq!(UNBONDING_PERIOD.load(deps.as_ref().storage).unwrap(), 100);
+}
+
+#[test]
+fn test_alliance_token_balance_after_migrate() {
+    let mut deps = mock_dependencies_with_balance(&[coin(400, DENOM)]);
+    setup_contract(deps.as_mut());
+    set_alliance_asset(deps.as_mut());
+
+    // Tokens delegated before the delegations were tracked are not reported
+    let res = query(deps.as_ref(), mock_env(), QueryMsg::AllianceTokenBalance {}).unwrap();
+    let balance: AllianceTokenBalanceRes = from_json(res).unwrap();
+    assert_eq!(balance.delegated, Uint128::zero());
+
+    // until they are seeded by the migration
+    migrate(
+        deps.as_mut(),
+        mock_env(),
+        MigrateMsg {
+            delegations: Some(vec![
+                AllianceDelegation {
+                    validator: "validator1".to_string(),
+                    amount: Uint128::new(250),
+                },
+                AllianceDelegation {
+                    validator: "validator2".to_string(),
+                    amount: Uint128::new(350),
+                },
+            ]),
+        },
+    )
+    .unwrap();
+    let res = query(deps.as_ref(), mock_env(), QueryMsg::AllianceTokenBalance {}).unwrap();
+    let balance: AllianceTokenBalanceRes = from_json(res).unwrap();
+    assert_eq!(
+        balance,
+        AllianceTokenBalanceRes {
+            free: Uint128::new(400),
+            delegated: Uint128::new(600),
+            unbonding: Uint128::zero(),
+        }
+    );
+}
+
+// Answers the validator query for an unbonded validator like the chain does,
+// while the bonded set only has the validators of the mock querier
+struct UnbondedValidatorQuerier {
+    base: MockQuerier,
+    unbonded: Validator,
+}
+
+impl Querier for UnbondedValidatorQuerier {
+    fn raw_query(&self, bin_request: &[u8]) -> QuerierResult {
+        match from_json(bin_request) {
+            Ok(QueryRequest::<Empty>::Staking(StakingQuery::Validator { address }))
+                if address == self.unbonded.address =>
+            {
+                SystemResult::Ok(ContractResult::Ok(
+                    to_json_binary(&ValidatorResponse {
+                        validator: Some(self.unbonded.clone()),
+                    })
+                    .unwrap(),
+                ))
+            }
+            _ => self.base.raw_query(bin_request),
+        }
+    }
+}
+
+#[test]
+fn test_allowed_validators_not_bonded() {
+    let validator = |address: &str| Validator {
+        address: address.to_string(),
+        commission: Decimal::percent(5),
+        max_commission: Decimal::percent(10),
+        max_change_rate: Decimal::percent(1),
+    };
+    let bonded = "terravaloper1qyqszqgpqyqszqgpqyqszqgpqyqszqgp5chrl0";
+    let unbonded = "terravaloper1qgpqyqszqgpqyqszqgpqyqszqgpqyqsz9u3x5e";
+    let mut base = MockQuerier::default();
+    base.update_staking("uluna", &[validator(bonded)], &[]);
+    let mut deps = OwnedDeps {
+        storage: MockStorage::default(),
+        api: MockApi::default(),
+        querier: UnbondedValidatorQuerier {
+            base,
+            unbonded: validator(unbonded),
+        },
+        custom_query_type: PhantomData::<Empty>,
+    };
+    setup_contract(deps.as_mut());
+
+    // The unbonded validator exists but cannot be allowed
+    let err = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("gov", &[]),
+        ExecuteMsg::AddAllowedValidators(vec![unbonded.to_string()]),
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::ValidatorNotBonded(unbonded.to_string()));
+
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("gov", &[]),
+        ExecuteMsg::AddAllowedValidators(vec![bonded.to_string()]),
+    )
+    .unwrap();
+    let res = query(deps.as_ref(), mock_env(), QueryMsg::AllowedValidators {}).unwrap();
+    let allowed: HashSet<String> = from_json(res).unwrap();
+    assert_eq!(allowed, HashSet::from([bonded.to_string()]));
+}
@@ -0,0 +1,552 @@
+use crate::contract::{execute, migrate};
+use crate::models::{
+    DelegationStrategy, DelegationTarget, ExecuteMsg, MigrateMsg, QueryMsg, UnbondingEntry,
+};
+use crate::query::query;
+use crate::tests::helpers::{
+    alliance_delegate, alliance_redelegate, alliance_undelegate, allow_validators,
+    set_alliance_asset, setup_contract, DENOM,
+};
+use alliance_protocol::alliance_protocol::AllianceDelegation;
+use alliance_protocol::error::ContractError;
+use alliance_protocol::token_factory::CustomExecuteMsg;
+use cosmwasm_std::testing::{
+    mock_dependencies, mock_dependencies_with_balance, mock_env, mock_info,
+};
+use cosmwasm_std::{coin, from_json, Binary, CosmosMsg, Decimal, Deps, DepsMut, Response, SubMsg};
+use std::collections::HashSet;
+use std::str::FromStr;
+use terra_proto_rs::alliance::alliance::{MsgDelegate, MsgRedelegate, MsgUndelegate};
+use terra_proto_rs::cosmos::base::v1beta1::Coin;
+use terra_proto_rs::traits::Message;
+
+fn strategy(targets: Vec<(&str, &str)>, max_redelegations: u32) -> DelegationStrategy {
+    DelegationStrategy {
+        targets: targets
+            .into_iter()
+            .map(|(validator, weight)| DelegationTarget {
+                validator: validator.to_string(),
+                weight: Decimal::from_str(weight).unwrap(),
+            })
+            .collect(),
+        max_redelegations,
+    }
+}
+
+fn update_strategy(
+    deps: DepsMut,
+    sender: &str,
+    strategy: DelegationStrategy,
+) -> Result<Response<CustomExecuteMsg>, ContractError> {
+    execute(
+        deps,
+        mock_env(),
+        mock_info(sender, &[]),
+        ExecuteMsg::UpdateDelegationStrategy(strategy),
+    )
+}
+
+fn rebalance(deps: DepsMut) -> Result<Response<CustomExecuteMsg>, ContractError> {
+    execute(
+        deps,
+        mock_env(),
+        mock_info("user", &[]),
+        ExecuteMsg::RebalanceDelegations {},
+    )
+}
+
+fn query_delegations(deps: Deps) -> Vec<(String, u128)> {
+    let res = query(deps, mock_env(), QueryMsg::Delegations {}).unwrap();
+    let delegations: Vec<AllianceDelegation> = from_json(res).unwrap();
+    delegations
+        .into_iter()
+        .map(|d| (d.validator, d.amount.u128()))
+        .collect()
+}
+
+fn coin_msg(amount: u128) -> Option<Coin> {
+    Some(Coin {
+        denom: DENOM.to_string(),
+        amount: amount.to_string(),
+    })
+}
+
+fn delegate_msg(validator: &str, amount: u128) -> SubMsg<CustomExecuteMsg> {
+    let msg = MsgDelegate {
+        amount: coin_msg(amount),
+        delegator_address: "cosmos2contract".to_string(),
+        validator_address: validator.to_string(),
+    };
+    SubMsg::new(CosmosMsg::Stargate {
+        type_url: "/alliance.alliance.MsgDelegate".to_string(),
+        value: Binary::from(msg.encode_to_vec()),
+    })
+}
+
+fn undelegate_msg(validator: &str, amount: u128) -> SubMsg<CustomExecuteMsg> {
+    let msg = MsgUndelegate {
+        amount: coin_msg(amount),
+        delegator_address: "cosmos2contract".to_string(),
+        validator_address: validator.to_string(),
+    };
+    SubMsg::new(CosmosMsg::Stargate {
+        type_url: "/alliance.alliance.MsgUndelegate".to_string(),
+        value: Binary::from(msg.encode_to_vec()),
+    })
+}
+
+fn redelegate_msg(src: &str, dst: &str, amount: u128) -> SubMsg<CustomExecuteMsg> {
+    let msg = MsgRedelegate {
+        amount: coin_msg(amount),
+        delegator_address: "cosmos2contract".to_string(),
+        validator_src_address: src.to_string(),
+        validator_dst_address: dst.to_string(),
+    };
+    SubMsg::new(CosmosMsg::Stargate {
+        type_url: "/alliance.alliance.MsgRedelegate".to_string(),
+        value: Binary::from(msg.encode_to_vec()),
+    })
+}
+
+#[test]
+fn test_update_delegation_strategy() {
+    let mut deps = mock_dependencies();
+    setup_contract(deps.as_mut());
+    allow_validators(
+        deps.as_mut(),
+        vec!["validator1", "validator2", "validator3", "validator4"],
+    );
+
+    let err = update_strategy(
+        deps.as_mut(),
+        "user",
+        strategy(vec![("validator1", "1")], 1),
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::Unauthorized {});
+
+    let err = update_strategy(deps.as_mut(), "gov", strategy(vec![], 1)).unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::InvalidDelegationStrategy("targets cannot be empty".to_string())
+    );
+
+    let err = update_strategy(
+        deps.as_mut(),
+        "gov",
+        strategy(vec![("validator1", "0.5"), ("validator1", "0.5")], 1),
+    )
+    .unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::InvalidDelegationStrategy(
+            "duplicated target for validator validator1".to_string()
+        )
+    );
+
+    let err = update_strategy(
+        deps.as_mut(),
+        "gov",
+        strategy(vec![("validator1", "1"), ("validator2", "0")], 1),
+    )
+    .unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::InvalidDelegationStrategy(
+            "zero weight for validator validator2".to_string()
+        )
+    );
+
+    let err = update_strategy(
+        deps.as_mut(),
+        "gov",
+        strategy(vec![("validator1", "0.5"), ("validator2", "0.6")], 1),
+    )
+    .unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::InvalidDelegationStrategy(
+            "sum of weights 1.1 is greater than 1".to_string()
+        )
+    );
+
+    let err = update_strategy(
+        deps.as_mut(),
+        "gov",
+        strategy(vec![("validator1", "0.5"), ("validator5", "0.5")], 1),
+    )
+    .unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::ValidatorNotAllowed("validator5".to_string())
+    );
+
+    // Both governance and the controller can update the targets
+    update_strategy(
+        deps.as_mut(),
+        "gov",
+        strategy(vec![("validator1", "0.5"), ("validator2", "0.5")], 1),
+    )
+    .unwrap();
+    let new_strategy = strategy(vec![("validator1", "0.6"), ("validator2", "0.4")], 2);
+    update_strategy(deps.as_mut(), "controller", new_strategy.clone()).unwrap();
+
+    let res = query(deps.as_ref(), mock_env(), QueryMsg::DelegationStrategy {}).unwrap();
+    let res: DelegationStrategy = from_json(res).unwrap();
+    assert_eq!(res, new_strategy);
+}
+
+#[test]
+fn test_rebalance_delegations_without_strategy() {
+    let mut deps = mock_dependencies();
+    setup_contract(deps.as_mut());
+
+    let err = rebalance(deps.as_mut()).unwrap_err();
+    assert_eq!(err, ContractError::AllianceTokenDenomNotSet {});
+
+    set_alliance_asset(deps.as_mut());
+    let err = rebalance(deps.as_mut()).unwrap_err();
+    assert_eq!(err, ContractError::DelegationStrategyNotSet {});
+}
+
+#[test]
+fn test_rebalance_delegates_available_tokens() {
+    let mut deps = mock_dependencies_with_balance(&[coin(1000, DENOM)]);
+    setup_contract(deps.as_mut());
+    allow_validators(
+        deps.as_mut(),
+        vec!["validator1", "validator2", "validator3", "validator4"],
+    );
+    set_alliance_asset(deps.as_mut());
+    update_strategy(
+        deps.as_mut(),
+        "gov",
+        strategy(
+            vec![
+                ("validator1", "0.333333333333333333"),
+                ("validator2", "0.333333333333333333"),
+                ("validator3", "0.333333333333333334"),
+            ],
+            1,
+        ),
+    )
+    .unwrap();
+
+    // The rounding remainder is delegated to the first validator
+    let res = rebalance(deps.as_mut()).unwrap();
+    assert_eq!(
+        res.messages,
+        vec![
+            delegate_msg("validator1", 334),
+            delegate_msg("validator2", 333),
+            delegate_msg("validator3", 333),
+        ]
+    );
+    assert_eq!(
+        query_delegations(deps.as_ref()),
+        vec![
+            ("validator1".to_string(), 334),
+            ("validator2".to_string(), 333),
+            ("validator3".to_string(), 333),
+        ]
+    );
+}
+
+#[test]
+fn test_rebalance_redelegations() {
+    let mut deps = mock_dependencies();
+    setup_contract(deps.as_mut());
+    allow_validators(
+        deps.as_mut(),
+        vec!["validator1", "validator2", "validator3", "validator4"],
+    );
+    set_alliance_asset(deps.as_mut());
+
+    // Manual delegations are tracked as well
+    deps.querier
+        .update_balance("cosmos2contract", vec![coin(1100, DENOM)]);
+    alliance_delegate(
+        deps.as_mut(),
+        vec![("validator1", 700), ("validator2", 400)],
+    );
+    deps.querier.update_balance("cosmos2contract", vec![]);
+    alliance_undelegate(deps.as_mut(), vec![("validator1", 100)]);
+    alliance_redelegate(deps.as_mut(), vec![("validator2", "validator4", 100)]);
+    assert_eq!(
+        query_delegations(deps.as_ref()),
+        vec![
+            ("validator1".to_string(), 600),
+            ("validator2".to_string(), 300),
+            ("validator4".to_string(), 100),
+        ]
+    );
+
+    update_strategy(
+        deps.as_mut(),
+        "gov",
+        strategy(
+            vec![
+                ("validator2", "0.4"),
+                ("validator3", "0.5"),
+                ("validator4", "0.1"),
+            ],
+            2,
+        ),
+    )
+    .unwrap();
+    let res = rebalance(deps.as_mut()).unwrap();
+    assert_eq!(
+        res.messages,
+        vec![
+            redelegate_msg("validator1", "validator2", 140),
+            redelegate_msg("validator1", "validator3", 460),
+        ]
+    );
+    assert_eq!(
+        query_delegations(deps.as_ref()),
+        vec![
+            ("validator2".to_string(), 440),
+            ("validator3".to_string(), 460),
+            ("validator4".to_string(), 100),
+        ]
+    );
+
+    // The tokens being unbonded count towards the targets so there is
+    // nothing to do until they are back
+    let res = rebalance(deps.as_mut()).unwrap();
+    assert_eq!(res.messages, vec![]);
+
+    let mut env = mock_env();
+    env.block.time = env.block.time.plus_seconds(21 * 24 * 60 * 60);
+    deps.querier
+        .update_balance("cosmos2contract", vec![coin(100, DENOM)]);
+    let res = execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("user", &[]),
+        ExecuteMsg::RebalanceDelegations {},
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages,
+        vec![
+            delegate_msg("validator3", 90),
+            delegate_msg("validator4", 10)
+        ]
+    );
+    let res = query(deps.as_ref(), env, QueryMsg::Unbondings {}).unwrap();
+    let unbondings: Vec<UnbondingEntry> = from_json(res).unwrap();
+    assert_eq!(unbondings, vec![]);
+}
+
+#[test]
+fn test_rebalance_max_redelegations() {
+    let mut deps = mock_dependencies();
+    setup_contract(deps.as_mut());
+    allow_validators(
+        deps.as_mut(),
+        vec!["validator1", "validator2", "validator3", "validator4"],
+    );
+    set_alliance_asset(deps.as_mut());
+    deps.querier
+        .update_balance("cosmos2contract", vec![coin(1000, DENOM)]);
+    alliance_delegate(
+        deps.as_mut(),
+        vec![("validator1", 500), ("validator2", 300)],
+    );
+    deps.querier
+        .update_balance("cosmos2contract", vec![coin(200, DENOM)]);
+
+    // validator1 and validator2 have a surplus of 500 and 100 while validator3
+    // needs 800. Only one redelegation is allowed so the surplus of validator2
+    // stays delegated and the tokens held by the contract are delegated.
+    update_strategy(
+        deps.as_mut(),
+        "controller",
+        strategy(vec![("validator2", "0.2"), ("validator3", "0.8")], 1),
+    )
+    .unwrap();
+    let res = rebalance(deps.as_mut()).unwrap();
+    assert_eq!(
+        res.messages,
+        vec![
+            redelegate_msg("validator1", "validator3", 500),
+            delegate_msg("validator3", 200),
+        ]
+    );
+    assert_eq!(
+        query_delegations(deps.as_ref()),
+        vec![
+            ("validator2".to_string(), 300),
+            ("validator3".to_string(), 700),
+        ]
+    );
+    let res = query(deps.as_ref(), mock_env(), QueryMsg::Validators {}).unwrap();
+    let validators: HashSet<String> = from_json(res).unwrap();
+    assert!(validators.contains("validator3"));
+    let res = query(deps.as_ref(), mock_env(), QueryMsg::Unbondings {}).unwrap();
+    let unbondings: Vec<UnbondingEntry> = from_json(res).unwrap();
+    assert_eq!(unbondings, vec![]);
+
+    // The remaining surplus is redelegated by the next rebalance
+    deps.querier.update_balance("cosmos2contract", vec![]);
+    let res = rebalance(deps.as_mut()).unwrap();
+    assert_eq!(
+        res.messages,
+        vec![redelegate_msg("validator2", "validator3", 100)]
+    );
+    assert_eq!(
+        query_delegations(deps.as_ref()),
+        vec![
+            ("validator2".to_string(), 200),
+            ("validator3".to_string(), 800),
+        ]
+    );
+}
+
+#[test]
+fn test_rebalance_undelegates_share_kept_out_of_targets() {
+    let mut deps = mock_dependencies();
+    setup_contract(deps.as_mut());
+    allow_validators(deps.as_mut(), vec!["validator1", "validator2"]);
+    set_alliance_asset(deps.as_mut());
+    deps.querier
+        .update_balance("cosmos2contract", vec![coin(1000, DENOM)]);
+    alliance_delegate(
+        deps.as_mut(),
+        vec![("validator1", 600), ("validator2", 400)],
+    );
+    deps.querier.update_balance("cosmos2contract", vec![]);
+
+    // 20% of the alliance tokens is kept undelegated
+    update_strategy(
+        deps.as_mut(),
+        "gov",
+        strategy(vec![("validator1", "0.5"), ("validator2", "0.3")], 1),
+    )
+    .unwrap();
+    let res = rebalance(deps.as_mut()).unwrap();
+    assert_eq!(
+        res.messages,
+        vec![
+            undelegate_msg("validator1", 100),
+            undelegate_msg("validator2", 100),
+        ]
+    );
+    assert_eq!(
+        query_delegations(deps.as_ref()),
+        vec![
+            ("validator1".to_string(), 500),
+            ("validator2".to_string(), 300),
+        ]
+    );
+
+    // The tokens being unbonded are not undelegated again
+    let res = rebalance(deps.as_mut()).unwrap();
+    assert_eq!(res.messages, vec![]);
+
+    // Nor delegated back once they are returned
+    let mut env = mock_env();
+    env.block.time = env.block.time.plus_seconds(21 * 24 * 60 * 60);
+    deps.querier
+        .update_balance("cosmos2contract", vec![coin(200, DENOM)]);
+    let res = execute(
+        deps.as_mut(),
+        env,
+        mock_info("user", &[]),
+        ExecuteMsg::RebalanceDelegations {},
+    )
+    .unwrap();
+    assert_eq!(res.messages, vec![]);
+}
+
+fn seed_msg(delegations: Vec<(&str, u128)>) -> MigrateMsg {
+    MigrateMsg {
+        delegations: Some(
+            delegations
+                .into_iter()
+                .map(|(validator, amount)| AllianceDelegation {
+                    validator: validator.to_string(),
+                    amount: amount.into(),
+                })
+                .collect(),
+        ),
+    }
+}
+
+#[test]
+fn test_migrate_seeds_delegations() {
+    let mut deps = mock_dependencies_with_balance(&[coin(500, DENOM)]);
+    setup_contract(deps.as_mut());
+    allow_validators(
+        deps.as_mut(),
+        vec!["validator1", "validator2", "validator3"],
+    );
+    set_alliance_asset(deps.as_mut());
+
+    migrate(
+        deps.as_mut(),
+        mock_env(),
+        seed_msg(vec![("validator1", 300), ("validator2", 200)]),
+    )
+    .unwrap();
+    assert_eq!(
+        query_delegations(deps.as_ref()),
+        vec![
+            ("validator1".to_string(), 300),
+            ("validator2".to_string(), 200),
+        ]
+    );
+    let res = query(deps.as_ref(), mock_env(), QueryMsg::Validators {}).unwrap();
+    let validators: HashSet<String> = from_json(res).unwrap();
+    assert!(validators.contains("validator1") && validators.contains("validator2"));
+
+    // Delegations are seeded only once
+    let err = migrate(
+        deps.as_mut(),
+        mock_env(),
+        seed_msg(vec![("validator1", 300)]),
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::DelegationsAlreadyTracked {});
+
+    // The seeded delegations are rebalanced like the tracked ones
+    update_strategy(deps.as_mut(), "gov", strategy(vec![("validator3", "1")], 2)).unwrap();
+    let res = rebalance(deps.as_mut()).unwrap();
+    assert_eq!(
+        res.messages,
+        vec![
+            redelegate_msg("validator1", "validator3", 300),
+            redelegate_msg("validator2", "validator3", 200),
+            delegate_msg("validator3", 500),
+        ]
+    );
+    assert_eq!(
+        query_delegations(deps.as_ref()),
+        vec![("validator3".to_string(), 1000)]
+    );
+}
+
+#[test]
+fn test_migrate_seed_delegations_invalid() {
+    let mut deps = mock_dependencies();
+    setup_contract(deps.as_mut());
+    let err = migrate(
+        deps.as_mut(),
+        mock_env(),
+        seed_msg(vec![("validator1", 100)]),
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::AllianceTokenDenomNotSet {});
+
+    set_alliance_asset(deps.as_mut());
+    let err = migrate(deps.as_mut(), mock_env(), seed_msg(vec![("validator1", 0)])).unwrap_err();
+    assert_eq!(err, ContractError::AmountCannotBeZero {});
+
+    // The delegations cannot be more than the supply not held by the contract
+    let mut deps = mock_dependencies_with_balance(&[coin(1000000000000, DENOM)]);
+    setup_contract(deps.as_mut());
+    set_alliance_asset(deps.as_mut());
+    let err = migrate(deps.as_mut(), mock_env(), seed_msg(vec![("validator1", 1)])).unwrap_err();
+    assert_eq!(err, ContractError::InsufficientBalance {});
+}
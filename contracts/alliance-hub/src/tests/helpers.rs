@@ -4,7 +4,7 @@ use crate::models::{
     QueryMsg, StakedBalanceRes,
 };
 use crate::query::query;
-use crate::state::CONFIG;
+use crate::state::{ALLOWED_VALIDATORS, CONFIG};
 use alliance_protocol::alliance_oracle_types::ChainId;
 use alliance_protocol::alliance_protocol::{
     AllianceDelegateMsg, AllianceDelegation, AllianceRedelegateMsg, AllianceRedelegation,
@@ -27,6 +27,8 @@ pub fn setup_contract(deps: DepsMut) -> Response<CustomExecuteMsg> {
         controller: "controller".to_string(),
         oracle: "oracle".to_string(),
         reward_denom: "uluna".to_string(),
+        alliance_token: None,
+        unbonding_period: None,
     };
     instantiate(deps, env, info, init_msg).unwrap()
 }
@@ -43,7 +45,15 @@ pub fn set_alliance_asset(deps: DepsMut) {
         .unwrap();
 }
 
-pub fn whitelist_assets(deps: DepsMut, assets: HashMap<ChainId, Vec<AssetInfo>>) -> Response {
+pub fn allow_validators(deps: DepsMut, validators: Vec<&str>) {
+    let validators = validators.into_iter().map(|v| v.to_string()).collect();
+    ALLOWED_VALIDATORS.save(deps.storage, &validators).unwrap();
+}
+
+pub fn whitelist_assets(
+    deps: DepsMut,
+    assets: HashMap<ChainId, Vec<AssetInfo>>,
+) -> Response<CustomExecuteMsg> {
     let info = mock_info("gov", &[]);
     let env = mock_env();
 
@@ -51,7 +61,7 @@ pub fn whitelist_assets(deps: DepsMut, assets: HashMap<ChainId, Vec<AssetInfo>>)
     execute(deps, env, info, msg).unwrap()
 }
 
-pub fn remove_assets(deps: DepsMut, assets: Vec<AssetInfo>) -> Response {
+pub fn remove_assets(deps: DepsMut, assets: Vec<AssetInfo>) -> Response<CustomExecuteMsg> {
     let info = mock_info("gov", &[]);
     let env = mock_env();
 
@@ -59,21 +69,24 @@ pub fn remove_assets(deps: DepsMut, assets: Vec<AssetInfo>) -> Response {
     execute(deps, env, info, msg).unwrap()
 }
 
-pub fn stake(deps: DepsMut, user: &str, amount: u128, denom: &str) -> Response {
+pub fn stake(deps: DepsMut, user: &str, amount: u128, denom: &str) -> Response<CustomExecuteMsg> {
     let info = mock_info(user, &[coin(amount, denom)]);
     let env = mock_env();
-    let msg = ExecuteMsg::Stake {};
+    let msg = ExecuteMsg::Stake { recipient: None };
     execute(deps, env, info, msg).unwrap()
 }
 
-pub fn unstake(deps: DepsMut, user: &str, amount: u128, denom: &str) -> Response {
+pub fn unstake(deps: DepsMut, user: &str, amount: u128, denom: &str) -> Response<CustomExecuteMsg> {
     let info = mock_info(user, &[]);
     let env = mock_env();
     let msg = ExecuteMsg::Unstake(Asset::native(denom, amount));
     execute(deps, env, info, msg).unwrap()
 }
 
-pub fn alliance_delegate(deps: DepsMut, delegations: Vec<(&str, u128)>) -> Response {
+pub fn alliance_delegate(
+    deps: DepsMut,
+    delegations: Vec<(&str, u128)>,
+) -> Response<CustomExecuteMsg> {
     let info = mock_info("controller", &[]);
     let env = mock_env();
     let delegations: Vec<AllianceDelegation> = delegations
@@ -87,7 +100,10 @@ pub fn alliance_delegate(deps: DepsMut, delegations: Vec<(&str, u128)>) -> Respo
     execute(deps, env, info, msg).unwrap()
 }
 
-pub fn alliance_undelegate(deps: DepsMut, delegations: Vec<(&str, u128)>) -> Response {
+pub fn alliance_undelegate(
+    deps: DepsMut,
+    delegations: Vec<(&str, u128)>,
+) -> Response<CustomExecuteMsg> {
     let info = mock_info("controller", &[]);
     let env = mock_env();
     let delegations: Vec<AllianceDelegation> = delegations
@@ -103,7 +119,10 @@ pub fn alliance_undelegate(deps: DepsMut, delegations: Vec<(&str, u128)>) -> Res
     execute(deps, env, info, msg).unwrap()
 }
 
-pub fn alliance_redelegate(deps: DepsMut, redelegations: Vec<(&str, &str, u128)>) -> Response {
+pub fn alliance_redelegate(
+    deps: DepsMut,
+    redelegations: Vec<(&str, &str, u128)>,
+) -> Response<CustomExecuteMsg> {
     let info = mock_info("controller", &[]);
     let env = mock_env();
     let redelegations: Vec<AllianceRedelegation> = redelegations
@@ -118,7 +137,7 @@ pub fn alliance_redelegate(deps: DepsMut, redelegations: Vec<(&str, &str, u128)>
     execute(deps, env, info, msg).unwrap()
 }
 
-pub fn claim_rewards(deps: DepsMut, user: &str, denom: &str) -> Response {
+pub fn claim_rewards(deps: DepsMut, user: &str, denom: &str) -> Response<CustomExecuteMsg> {
     let info = mock_info(user, &[]);
     let env = mock_env();
     let msg = ExecuteMsg::ClaimRewards(AssetInfo::Native(denom.to_string()));
@@ -0,0 +1,408 @@
+use crate::contract::execute;
+use crate::models::{
+    AssetQuery, ExecuteMsg, LockTier, LockedPositionRes, QueryMsg, StakedBalanceRes, UnlockParams,
+};
+use crate::query::query;
+use crate::state::{ASSET_REWARD_DISTRIBUTION, EXPIRED_LOCK_INDEXES, LOCK_EXPIRY_POSITIONS};
+use crate::tests::helpers::{
+    query_rewards, set_alliance_asset, setup_contract, stake, whitelist_assets,
+};
+use alliance_protocol::alliance_protocol::AssetDistribution;
+use alliance_protocol::error::ContractError;
+use alliance_protocol::token_factory::CustomExecuteMsg;
+use cosmwasm_std::testing::{
+    mock_dependencies, mock_dependencies_with_balance, mock_env, mock_info,
+};
+use cosmwasm_std::{coin, from_json, Decimal, Deps, DepsMut, Env, Response, Uint128};
+use cw_asset::{Asset, AssetInfo, AssetInfoKey};
+use std::collections::HashMap;
+
+fn tier(duration: u64, multiplier: u64) -> LockTier {
+    LockTier {
+        duration,
+        multiplier: Decimal::percent(multiplier),
+    }
+}
+
+fn update_lock_tiers(
+    deps: DepsMut,
+    sender: &str,
+    tiers: Vec<LockTier>,
+) -> Result<Response<CustomExecuteMsg>, ContractError> {
+    execute(
+        deps,
+        mock_env(),
+        mock_info(sender, &[]),
+        ExecuteMsg::UpdateLockTiers(tiers),
+    )
+}
+
+fn stake_locked(
+    deps: DepsMut,
+    user: &str,
+    amount: u128,
+    duration: u64,
+) -> Result<Response<CustomExecuteMsg>, ContractError> {
+    execute(
+        deps,
+        mock_env(),
+        mock_info(user, &[coin(amount, "aWHALE")]),
+        ExecuteMsg::StakeLocked { duration },
+    )
+}
+
+fn unlock(
+    deps: DepsMut,
+    env: Env,
+    user: &str,
+    duration: u64,
+) -> Result<Response<CustomExecuteMsg>, ContractError> {
+    execute(
+        deps,
+        env,
+        mock_info("anyone", &[]),
+        ExecuteMsg::Unlock(UnlockParams {
+            address: user.to_string(),
+            asset: AssetInfo::Native("aWHALE".to_string()),
+            duration,
+        }),
+    )
+}
+
+fn query_staked_balance(deps: Deps, user: &str) -> StakedBalanceRes {
+    let res = query(
+        deps,
+        mock_env(),
+        QueryMsg::StakedBalance(AssetQuery {
+            address: user.to_string(),
+            asset: AssetInfo::Native("aWHALE".to_string()),
+        }),
+    )
+    .unwrap();
+    from_json(res).unwrap()
+}
+
+fn update_rewards_callback(deps: DepsMut) {
+    execute(
+        deps,
+        mock_env(),
+        mock_info("cosmos2contract", &[]),
+        ExecuteMsg::UpdateRewardsCallback {},
+    )
+    .unwrap();
+}
+
+#[test]
+fn test_update_lock_tiers() {
+    let mut deps = mock_dependencies();
+    setup_contract(deps.as_mut());
+
+    let err = update_lock_tiers(deps.as_mut(), "user", vec![tier(100, 150)]).unwrap_err();
+    assert_eq!(err, ContractError::Unauthorized {});
+
+    let err = update_lock_tiers(deps.as_mut(), "gov", vec![tier(0, 150)]).unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::InvalidLockTiers("duration cannot be zero".to_string())
+    );
+
+    let err =
+        update_lock_tiers(deps.as_mut(), "gov", vec![tier(100, 150), tier(100, 200)]).unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::InvalidLockTiers("duplicated tier for duration 100".to_string())
+    );
+
+    let err = update_lock_tiers(deps.as_mut(), "gov", vec![tier(100, 90)]).unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::InvalidLockTiers("multiplier 0.9 is lower than 1".to_string())
+    );
+
+    let tiers = vec![tier(100, 150), tier(200, 200)];
+    update_lock_tiers(deps.as_mut(), "gov", tiers.clone()).unwrap();
+    let res = query(deps.as_ref(), mock_env(), QueryMsg::LockTiers {}).unwrap();
+    let res: Vec<LockTier> = from_json(res).unwrap();
+    assert_eq!(res, tiers);
+}
+
+#[test]
+fn test_locked_positions_boost_rewards() {
+    let mut deps = mock_dependencies_with_balance(&[]);
+    setup_contract(deps.as_mut());
+    set_alliance_asset(deps.as_mut());
+    whitelist_assets(
+        deps.as_mut(),
+        HashMap::from([(
+            "chain-1".to_string(),
+            vec![AssetInfo::Native("aWHALE".to_string())],
+        )]),
+    );
+    ASSET_REWARD_DISTRIBUTION
+        .save(
+            deps.as_mut().storage,
+            &vec![AssetDistribution {
+                asset: AssetInfo::Native("aWHALE".to_string()),
+                distribution: Decimal::percent(100),
+            }],
+        )
+        .unwrap();
+    update_lock_tiers(deps.as_mut(), "gov", vec![tier(100, 200)]).unwrap();
+
+    let err = stake_locked(deps.as_mut(), "user2", 100, 50).unwrap_err();
+    assert_eq!(err, ContractError::LockTierNotFound(50));
+
+    stake(deps.as_mut(), "user1", 100, "aWHALE");
+    stake_locked(deps.as_mut(), "user2", 100, 100).unwrap();
+    assert_eq!(
+        query_staked_balance(deps.as_ref(), "user2"),
+        StakedBalanceRes {
+            asset: AssetInfo::Native("aWHALE".to_string()),
+            balance: Uint128::new(100),
+            effective_balance: Uint128::new(200),
+        }
+    );
+    let res = query(
+        deps.as_ref(),
+        mock_env(),
+        QueryMsg::LockedPositions(AssetQuery {
+            address: "user2".to_string(),
+            asset: AssetInfo::Native("aWHALE".to_string()),
+        }),
+    )
+    .unwrap();
+    let res: Vec<LockedPositionRes> = from_json(res).unwrap();
+    assert_eq!(
+        res,
+        vec![LockedPositionRes {
+            duration: 100,
+            amount: Uint128::new(100),
+            multiplier: Decimal::percent(200),
+            unlock_time: mock_env().block.time.plus_seconds(100),
+        }]
+    );
+
+    // The locked position earns twice the rewards per token
+    deps.querier
+        .update_balance("cosmos2contract", vec![coin(300, "uluna")]);
+    update_rewards_callback(deps.as_mut());
+    assert_eq!(
+        query_rewards(deps.as_ref(), "user1", "aWHALE").rewards,
+        Uint128::new(100)
+    );
+    assert_eq!(
+        query_rewards(deps.as_ref(), "user2", "aWHALE").rewards,
+        Uint128::new(200)
+    );
+
+    // Locked tokens cannot be unstaked until the position is unlocked
+    let err = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("user2", &[]),
+        ExecuteMsg::Unstake(Asset::native("aWHALE", 100u128)),
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::InsufficientBalance {});
+    let err = unlock(deps.as_mut(), mock_env(), "user2", 100).unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::PositionLocked(mock_env().block.time.plus_seconds(100))
+    );
+
+    let mut env = mock_env();
+    env.block.time = env.block.time.plus_seconds(100);
+    unlock(deps.as_mut(), env, "user2", 100).unwrap();
+    assert_eq!(
+        query_staked_balance(deps.as_ref(), "user2").effective_balance,
+        Uint128::new(100)
+    );
+    let err = unlock(deps.as_mut(), mock_env(), "user2", 100).unwrap_err();
+    assert_eq!(err, ContractError::LockedPositionNotFound {});
+
+    deps.querier
+        .update_balance("cosmos2contract", vec![coin(600, "uluna")]);
+    update_rewards_callback(deps.as_mut());
+    assert_eq!(
+        query_rewards(deps.as_ref(), "user1", "aWHALE").rewards,
+        Uint128::new(250)
+    );
+    assert_eq!(
+        query_rewards(deps.as_ref(), "user2", "aWHALE").rewards,
+        Uint128::new(350)
+    );
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("user2", &[]),
+        ExecuteMsg::Unstake(Asset::native("aWHALE", 100u128)),
+    )
+    .unwrap();
+}
+
+#[test]
+fn test_expired_lock_stops_boost() {
+    let mut deps = mock_dependencies_with_balance(&[]);
+    setup_contract(deps.as_mut());
+    set_alliance_asset(deps.as_mut());
+    whitelist_assets(
+        deps.as_mut(),
+        HashMap::from([(
+            "chain-1".to_string(),
+            vec![AssetInfo::Native("aWHALE".to_string())],
+        )]),
+    );
+    ASSET_REWARD_DISTRIBUTION
+        .save(
+            deps.as_mut().storage,
+            &vec![AssetDistribution {
+                asset: AssetInfo::Native("aWHALE".to_string()),
+                distribution: Decimal::percent(100),
+            }],
+        )
+        .unwrap();
+    update_lock_tiers(deps.as_mut(), "gov", vec![tier(100, 200)]).unwrap();
+    stake(deps.as_mut(), "user1", 100, "aWHALE");
+    stake_locked(deps.as_mut(), "user2", 100, 100).unwrap();
+
+    deps.querier
+        .update_balance("cosmos2contract", vec![coin(300, "uluna")]);
+    update_rewards_callback(deps.as_mut());
+
+    // Once the position expires the boost is not counted even if it is not unlocked
+    let mut env = mock_env();
+    env.block.time = env.block.time.plus_seconds(100);
+    let res = query(
+        deps.as_ref(),
+        env.clone(),
+        QueryMsg::StakedBalance(AssetQuery {
+            address: "user2".to_string(),
+            asset: AssetInfo::Native("aWHALE".to_string()),
+        }),
+    )
+    .unwrap();
+    let res: StakedBalanceRes = from_json(res).unwrap();
+    assert_eq!(res.effective_balance, Uint128::new(100));
+
+    deps.querier
+        .update_balance("cosmos2contract", vec![coin(900, "uluna")]);
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("cosmos2contract", &[]),
+        ExecuteMsg::UpdateRewardsCallback {},
+    )
+    .unwrap();
+    assert_eq!(
+        query_rewards(deps.as_ref(), "user1", "aWHALE").rewards,
+        Uint128::new(400)
+    );
+    assert_eq!(
+        query_rewards(deps.as_ref(), "user2", "aWHALE").rewards,
+        Uint128::new(500)
+    );
+
+    // Settling the rewards of the user removes the expired boost from its balance
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("user2", &[]),
+        ExecuteMsg::ClaimRewards(AssetInfo::Native("aWHALE".to_string())),
+    )
+    .unwrap();
+    assert_eq!(
+        query_staked_balance(deps.as_ref(), "user2").effective_balance,
+        Uint128::new(100)
+    );
+    // while its tokens stay locked until the position is unlocked
+    let err = execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("user2", &[]),
+        ExecuteMsg::Unstake(Asset::native("aWHALE", 100u128)),
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::InsufficientBalance {});
+    unlock(deps.as_mut(), env.clone(), "user2", 100).unwrap();
+    execute(
+        deps.as_mut(),
+        env,
+        mock_info("user2", &[]),
+        ExecuteMsg::Unstake(Asset::native("aWHALE", 100u128)),
+    )
+    .unwrap();
+}
+
+#[test]
+fn test_expired_lock_index_removed_when_released() {
+    let mut deps = mock_dependencies_with_balance(&[]);
+    setup_contract(deps.as_mut());
+    set_alliance_asset(deps.as_mut());
+    whitelist_assets(
+        deps.as_mut(),
+        HashMap::from([(
+            "chain-1".to_string(),
+            vec![AssetInfo::Native("aWHALE".to_string())],
+        )]),
+    );
+    update_lock_tiers(deps.as_mut(), "gov", vec![tier(100, 200)]).unwrap();
+    let asset_key = AssetInfoKey::from(AssetInfo::Native("aWHALE".to_string()));
+    let unlock_time = mock_env().block.time.plus_seconds(100).seconds();
+    let expiry_key = (asset_key.clone(), unlock_time);
+
+    stake_locked(deps.as_mut(), "user1", 100, 100).unwrap();
+    stake_locked(deps.as_mut(), "user2", 100, 100).unwrap();
+    stake_locked(deps.as_mut(), "user3", 10, 100).unwrap();
+    // Locking again moves the position to its new unlock time
+    let mut env = mock_env();
+    env.block.time = env.block.time.plus_seconds(10);
+    execute(
+        deps.as_mut(),
+        env,
+        mock_info("user3", &[coin(10, "aWHALE")]),
+        ExecuteMsg::StakeLocked { duration: 100 },
+    )
+    .unwrap();
+    assert_eq!(
+        LOCK_EXPIRY_POSITIONS
+            .load(deps.as_ref().storage, expiry_key.clone())
+            .unwrap(),
+        2
+    );
+    assert_eq!(
+        LOCK_EXPIRY_POSITIONS
+            .load(deps.as_ref().storage, (asset_key, unlock_time + 10))
+            .unwrap(),
+        1
+    );
+
+    // The index at the unlock time is kept while a position still needs it
+    let mut env = mock_env();
+    env.block.time = env.block.time.plus_seconds(100);
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("user1", &[]),
+        ExecuteMsg::ClaimRewards(AssetInfo::Native("aWHALE".to_string())),
+    )
+    .unwrap();
+    assert!(EXPIRED_LOCK_INDEXES.has(deps.as_ref().storage, expiry_key.clone()));
+    assert_eq!(
+        LOCK_EXPIRY_POSITIONS
+            .load(deps.as_ref().storage, expiry_key.clone())
+            .unwrap(),
+        1
+    );
+
+    // and removed once the last position referencing it is settled
+    unlock(deps.as_mut(), env.clone(), "user2", 100).unwrap();
+    assert!(!EXPIRED_LOCK_INDEXES.has(deps.as_ref().storage, expiry_key.clone()));
+    assert!(!LOCK_EXPIRY_POSITIONS.has(deps.as_ref().storage, expiry_key));
+
+    // Positions cannot be unlocked early
+    let err = unlock(deps.as_mut(), env, "user3", 100).unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::PositionLocked(mock_env().block.time.plus_seconds(110))
+    );
+}
@@ -1,6 +1,9 @@
 mod alliance;
 mod assets;
+mod delegations;
 mod helpers;
 mod instantiate;
+mod lockup;
+mod receipts;
 mod rewards;
 mod stake_unstake;
@@ -0,0 +1,363 @@
+use crate::contract::{execute, reply};
+use crate::models::{ExecuteMsg, QueryMsg, ReceiptDenomParams, ReceiptDenomRes};
+use crate::query::query;
+use crate::state::ASSET_REWARD_DISTRIBUTION;
+use crate::tests::helpers::{
+    query_rewards, set_alliance_asset, setup_contract, stake, unstake, whitelist_assets,
+};
+use alliance_protocol::alliance_protocol::AssetDistribution;
+use alliance_protocol::error::ContractError;
+use alliance_protocol::token_factory::{CustomExecuteMsg, TokenExecuteMsg};
+use cosmwasm_std::testing::{mock_dependencies_with_balance, mock_env, mock_info};
+use cosmwasm_std::{
+    coin, coins, from_json, BankMsg, Binary, Coin, CosmosMsg, Decimal, Deps, DepsMut, Reply,
+    Response, SubMsg, SubMsgResponse, SubMsgResult, Uint128,
+};
+use cw_asset::{Asset, AssetInfo};
+use std::collections::HashMap;
+use terra_proto_rs::traits::MessageExt;
+
+const RECEIPT_DENOM: &str = "factory/cosmos2contract/raWHALE";
+
+fn create_receipt_denom(
+    deps: DepsMut,
+    sender: &str,
+) -> Result<Response<CustomExecuteMsg>, ContractError> {
+    execute(
+        deps,
+        mock_env(),
+        mock_info(sender, &[]),
+        ExecuteMsg::CreateReceiptDenom(ReceiptDenomParams {
+            asset: AssetInfo::Native("aWHALE".to_string()),
+            subdenom: "raWHALE".to_string(),
+        }),
+    )
+}
+
+fn mint_receipts_msg(amount: u128) -> SubMsg<CustomExecuteMsg> {
+    SubMsg::new(CosmosMsg::Custom(CustomExecuteMsg::Token(
+        TokenExecuteMsg::MintTokens {
+            denom: RECEIPT_DENOM.to_string(),
+            amount: Uint128::new(amount),
+            mint_to_address: "cosmos2contract".to_string(),
+        },
+    )))
+}
+
+fn query_receipt_denoms(deps: Deps) -> Vec<ReceiptDenomRes> {
+    let res = query(deps, mock_env(), QueryMsg::ReceiptDenoms {}).unwrap();
+    from_json(res).unwrap()
+}
+
+fn update_rewards_callback(deps: DepsMut) {
+    execute(
+        deps,
+        mock_env(),
+        mock_info("cosmos2contract", &[]),
+        ExecuteMsg::UpdateRewardsCallback {},
+    )
+    .unwrap();
+}
+
+#[test]
+fn test_receipts_follow_holder() {
+    let mut deps = mock_dependencies_with_balance(&[]);
+    setup_contract(deps.as_mut());
+    set_alliance_asset(deps.as_mut());
+    whitelist_assets(
+        deps.as_mut(),
+        HashMap::from([(
+            "chain-1".to_string(),
+            vec![AssetInfo::Native("aWHALE".to_string())],
+        )]),
+    );
+    ASSET_REWARD_DISTRIBUTION
+        .save(
+            deps.as_mut().storage,
+            &vec![AssetDistribution {
+                asset: AssetInfo::Native("aWHALE".to_string()),
+                distribution: Decimal::percent(100),
+            }],
+        )
+        .unwrap();
+    stake(deps.as_mut(), "user1", 100, "aWHALE");
+
+    let err = create_receipt_denom(deps.as_mut(), "user1").unwrap_err();
+    assert_eq!(err, ContractError::Unauthorized {});
+    let res = create_receipt_denom(deps.as_mut(), "gov").unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::reply_on_success(
+            CosmosMsg::Custom(CustomExecuteMsg::Token(TokenExecuteMsg::CreateDenom {
+                subdenom: "raWHALE".to_string(),
+            })),
+            3
+        )]
+    );
+
+    // Receipts are minted for the tokens staked before the denom was created
+    let res = reply(
+        deps.as_mut(),
+        mock_env(),
+        Reply {
+            id: 3,
+            result: SubMsgResult::Ok(SubMsgResponse {
+                events: vec![],
+                data: Some(Binary::from(RECEIPT_DENOM.to_string().to_bytes().unwrap())),
+            }),
+        },
+    )
+    .unwrap();
+    assert_eq!(res.messages, vec![mint_receipts_msg(100)]);
+    let err = create_receipt_denom(deps.as_mut(), "gov").unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::ReceiptDenomAlreadySet("native:aWHALE".to_string())
+    );
+
+    let res = stake(deps.as_mut(), "user2", 50, "aWHALE");
+    assert_eq!(res.messages, vec![mint_receipts_msg(50)]);
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("user2", &[]),
+        ExecuteMsg::WithdrawReceipts(Asset::native("aWHALE", 50u128)),
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(CosmosMsg::Bank(BankMsg::Send {
+            to_address: "user2".to_string(),
+            amount: coins(50, RECEIPT_DENOM),
+        }))]
+    );
+    assert_eq!(
+        query_receipt_denoms(deps.as_ref()),
+        vec![ReceiptDenomRes {
+            asset: AssetInfo::Native("aWHALE".to_string()),
+            denom: RECEIPT_DENOM.to_string(),
+            issued: Uint128::new(50),
+            rewards: Uint128::zero(),
+        }]
+    );
+
+    // The stake of the withdrawn receipts keeps earning rewards for their holder
+    deps.querier
+        .update_balance("cosmos2contract", vec![coin(300, "uluna")]);
+    update_rewards_callback(deps.as_mut());
+    assert_eq!(
+        query_rewards(deps.as_ref(), "user1", "aWHALE").rewards,
+        Uint128::new(200)
+    );
+    assert_eq!(
+        query_rewards(deps.as_ref(), "user2", "aWHALE").rewards,
+        Uint128::zero()
+    );
+    assert_eq!(
+        query_receipt_denoms(deps.as_ref())[0].rewards,
+        Uint128::new(100)
+    );
+
+    // A third party that got the receipts is paid their share when staking them back
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("user3", &[coin(20, RECEIPT_DENOM)]),
+        ExecuteMsg::Stake { recipient: None },
+    )
+    .unwrap();
+    assert_eq!(res.messages, vec![]);
+    assert_eq!(
+        query_rewards(deps.as_ref(), "user3", "aWHALE").rewards,
+        Uint128::new(40)
+    );
+    let res = query_receipt_denoms(deps.as_ref());
+    assert_eq!(res[0].issued, Uint128::new(30));
+    assert_eq!(res[0].rewards, Uint128::new(60));
+
+    // New receipts buy into the rewards owed to the issued ones
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("user1", &[]),
+        ExecuteMsg::WithdrawReceipts(Asset::native("aWHALE", 30u128)),
+    )
+    .unwrap();
+    assert_eq!(
+        query_rewards(deps.as_ref(), "user1", "aWHALE").rewards,
+        Uint128::new(140)
+    );
+    let res = query_receipt_denoms(deps.as_ref());
+    assert_eq!(res[0].issued, Uint128::new(60));
+    assert_eq!(res[0].rewards, Uint128::new(120));
+
+    // A fresh staker has no rewards to buy in and sends the reward tokens missing
+    stake(deps.as_mut(), "user4", 10, "aWHALE");
+    let withdraw_receipts = |deps: DepsMut, funds: &[Coin]| {
+        execute(
+            deps,
+            mock_env(),
+            mock_info("user4", funds),
+            ExecuteMsg::WithdrawReceipts(Asset::native("aWHALE", 10u128)),
+        )
+    };
+    let err = withdraw_receipts(deps.as_mut(), &[]).unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::InsufficientRewards {
+            missing: Uint128::new(20)
+        }
+    );
+    let err = withdraw_receipts(deps.as_mut(), &[coin(15, "uluna")]).unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::InsufficientRewards {
+            missing: Uint128::new(5)
+        }
+    );
+    let err = withdraw_receipts(deps.as_mut(), &[coin(20, "aWHALE")]).unwrap_err();
+    assert_eq!(err, ContractError::InvalidBuyInFunds("uluna".to_string()));
+    let res = withdraw_receipts(deps.as_mut(), &[coin(25, "uluna")]).unwrap();
+    assert_eq!(
+        res.messages,
+        vec![
+            SubMsg::new(CosmosMsg::Bank(BankMsg::Send {
+                to_address: "user4".to_string(),
+                amount: coins(10, RECEIPT_DENOM),
+            })),
+            SubMsg::new(CosmosMsg::Bank(BankMsg::Send {
+                to_address: "user4".to_string(),
+                amount: coins(5, "uluna"),
+            })),
+        ]
+    );
+    let res = query_receipt_denoms(deps.as_ref());
+    assert_eq!(res[0].issued, Uint128::new(70));
+    assert_eq!(res[0].rewards, Uint128::new(140));
+
+    // 160 tokens earn the rewards, 70 of them are behind the issued receipts,
+    // and the buy-in sent is not counted as new rewards
+    deps.querier
+        .update_balance("cosmos2contract", vec![coin(640, "uluna")]);
+    update_rewards_callback(deps.as_mut());
+    assert_eq!(
+        query_rewards(deps.as_ref(), "user1", "aWHALE").rewards,
+        Uint128::new(280)
+    );
+    assert_eq!(
+        query_rewards(deps.as_ref(), "user4", "aWHALE").rewards,
+        Uint128::zero()
+    );
+    assert_eq!(
+        query_receipt_denoms(deps.as_ref())[0].rewards,
+        Uint128::new(280)
+    );
+
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("user3", &[coin(30, RECEIPT_DENOM)]),
+        ExecuteMsg::Stake { recipient: None },
+    )
+    .unwrap();
+    assert_eq!(
+        query_rewards(deps.as_ref(), "user3", "aWHALE").rewards,
+        Uint128::new(200)
+    );
+    let res = query_receipt_denoms(deps.as_ref());
+    assert_eq!(res[0].issued, Uint128::new(40));
+    assert_eq!(res[0].rewards, Uint128::new(160));
+
+    let res = unstake(deps.as_mut(), "user3", 50, "aWHALE");
+    assert_eq!(
+        res.messages,
+        vec![
+            SubMsg::new(CosmosMsg::Bank(BankMsg::Send {
+                to_address: "user3".to_string(),
+                amount: coins(50, "aWHALE"),
+            })),
+            SubMsg::new(CosmosMsg::Custom(CustomExecuteMsg::Token(
+                TokenExecuteMsg::BurnTokens {
+                    denom: RECEIPT_DENOM.to_string(),
+                    amount: Uint128::new(50),
+                    burn_from_address: "cosmos2contract".to_string(),
+                },
+            ))),
+        ]
+    );
+}
+
+#[test]
+fn test_receipts_staked_back_after_asset_removed() {
+    let mut deps = mock_dependencies_with_balance(&[]);
+    setup_contract(deps.as_mut());
+    set_alliance_asset(deps.as_mut());
+    whitelist_assets(
+        deps.as_mut(),
+        HashMap::from([(
+            "chain-1".to_string(),
+            vec![AssetInfo::Native("aWHALE".to_string())],
+        )]),
+    );
+    create_receipt_denom(deps.as_mut(), "gov").unwrap();
+    reply(
+        deps.as_mut(),
+        mock_env(),
+        Reply {
+            id: 3,
+            result: SubMsgResult::Ok(SubMsgResponse {
+                events: vec![],
+                data: Some(Binary::from(RECEIPT_DENOM.to_string().to_bytes().unwrap())),
+            }),
+        },
+    )
+    .unwrap();
+    stake(deps.as_mut(), "user1", 100, "aWHALE");
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("user1", &[]),
+        ExecuteMsg::WithdrawReceipts(Asset::native("aWHALE", 100u128)),
+    )
+    .unwrap();
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("gov", &[]),
+        ExecuteMsg::RemoveAssets(vec![AssetInfo::Native("aWHALE".to_string())]),
+    )
+    .unwrap();
+
+    let err = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("user2", &[coin(10, "aWHALE")]),
+        ExecuteMsg::Stake { recipient: None },
+    )
+    .unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::AssetNotWhitelisted("native:aWHALE".to_string())
+    );
+
+    // The receipts can still be staked back to unstake their tokens
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("user2", &[coin(100, RECEIPT_DENOM)]),
+        ExecuteMsg::Stake { recipient: None },
+    )
+    .unwrap();
+    assert_eq!(
+        query_receipt_denoms(deps.as_ref())[0].issued,
+        Uint128::zero()
+    );
+    let res = unstake(deps.as_mut(), "user2", 100, "aWHALE");
+    assert_eq!(
+        res.messages[0],
+        SubMsg::new(CosmosMsg::Bank(BankMsg::Send {
+            to_address: "user2".to_string(),
+            amount: coins(100, "aWHALE"),
+        }))
+    );
+}
@@ -1,21 +1,30 @@
-use crate::contract::execute;
-use crate::models::{ExecuteMsg, PendingRewardsRes};
+use crate::contract::{execute, migrate, reply};
+use crate::models::{
+    AssetDust, AssetQuery, ClaimFailureRes, DustRes, ExecuteMsg, MigrateMsg, PendingRewardsRes,
+    ProtocolFee, ProtocolFeeParams, ProtocolFeeRes, QueryMsg,
+};
+use crate::query::query;
 use crate::state::{
-    ASSET_REWARD_DISTRIBUTION, ASSET_REWARD_RATE, TEMP_BALANCE, TOTAL_BALANCES,
-    USER_ASSET_REWARD_RATE, VALIDATORS,
+    ASSET_REWARD_DISTRIBUTION, ASSET_REWARD_INDEX, LEGACY_ASSET_REWARD_RATE,
+    LEGACY_USER_ASSET_REWARD_RATE, RESERVED_REWARDS, TOTAL_BALANCES, USER_ASSET_REWARD_INDEX,
+    VALIDATORS,
 };
 use crate::tests::helpers::{
     claim_rewards, query_all_rewards, query_rewards, set_alliance_asset, setup_contract, stake,
     unstake, whitelist_assets, DENOM,
 };
+use alliance_protocol::alliance_oracle_types::EmissionsDistribution;
 use alliance_protocol::alliance_protocol::AssetDistribution;
+use alliance_protocol::error::ContractError;
 use cosmwasm_std::testing::{mock_dependencies_with_balance, mock_env, mock_info};
 use cosmwasm_std::{
-    coin, coins, to_json_binary, Addr, BankMsg, Binary, CosmosMsg, Decimal, Response, SubMsg,
-    Uint128, WasmMsg,
+    attr, coin, coins, from_json, to_json_binary, Addr, BankMsg, Binary, ContractResult, CosmosMsg,
+    Decimal, Deps, DepsMut, Env, Reply, Response, SubMsg, SubMsgResponse, SubMsgResult,
+    SystemResult, Uint128, Uint256, WasmMsg,
 };
 use cw_asset::{AssetInfo, AssetInfoKey};
 use std::collections::{HashMap, HashSet};
+use std::str::FromStr;
 use terra_proto_rs::alliance::alliance::MsgClaimDelegationRewards;
 use terra_proto_rs::traits::Message;
 
@@ -42,7 +51,7 @@ fn test_update_rewards() {
     assert_eq!(
         res.messages,
         vec![
-            SubMsg::reply_on_error(
+            SubMsg::reply_always(
                 CosmosMsg::Stargate {
                     type_url: "/alliance.alliance.MsgClaimDelegationRewards".to_string(),
                     value: Binary::from(
@@ -63,8 +72,6 @@ fn test_update_rewards() {
             }))
         ]
     );
-    let prev_balance = TEMP_BALANCE.load(deps.as_ref().storage).unwrap();
-    assert_eq!(prev_balance, Uint128::new(1000000));
 }
 
 #[test]
@@ -89,9 +96,75 @@ fn test_update_rewards_with_funds_sent() {
         ExecuteMsg::UpdateRewards {},
     )
     .unwrap();
-    let prev_balance = TEMP_BALANCE.load(deps.as_ref().storage).unwrap();
     assert_eq!(res.messages.len(), 3);
-    assert_eq!(prev_balance, Uint128::new(1000000));
+}
+
+#[test]
+fn test_update_rewards_claim_failures() {
+    let mut deps = mock_dependencies_with_balance(&[coin(1000000, "uluna")]);
+    setup_contract(deps.as_mut());
+    set_alliance_asset(deps.as_mut());
+    VALIDATORS
+        .save(
+            deps.as_mut().storage,
+            &HashSet::from(["validator1".to_string()]),
+        )
+        .unwrap();
+
+    let claim_reply = |mut deps: DepsMut, env: Env, result: SubMsgResult| {
+        execute(
+            deps.branch(),
+            env.clone(),
+            mock_info("user", &[]),
+            ExecuteMsg::UpdateRewards {},
+        )
+        .unwrap();
+        reply(deps, env, Reply { id: 2, result }).unwrap()
+    };
+    let res = claim_reply(
+        deps.as_mut(),
+        mock_env(),
+        SubMsgResult::Err("no delegation found".to_string()),
+    );
+    assert_eq!(
+        res.attributes,
+        vec![
+            attr("action", "claim_reward_error"),
+            attr("validator", "validator1"),
+            attr("error", "no delegation found"),
+        ]
+    );
+
+    // Successful claims do not change the recorded failures
+    let mut env = mock_env();
+    env.block.time = env.block.time.plus_seconds(60);
+    let res = claim_reply(
+        deps.as_mut(),
+        env.clone(),
+        SubMsgResult::Ok(SubMsgResponse {
+            events: vec![],
+            data: None,
+        }),
+    );
+    assert_eq!(res, Response::new());
+
+    env.block.time = env.block.time.plus_seconds(60);
+    claim_reply(
+        deps.as_mut(),
+        env.clone(),
+        SubMsgResult::Err("validator is jailed".to_string()),
+    );
+    let res = query(deps.as_ref(), env.clone(), QueryMsg::ClaimFailures {}).unwrap();
+    let failures: Vec<ClaimFailureRes> = from_json(res).unwrap();
+    assert_eq!(
+        failures,
+        vec![ClaimFailureRes {
+            validator: "validator1".to_string(),
+            failures: 2,
+            last_failure: env.block.time,
+            last_error: "validator is jailed".to_string(),
+        }]
+    );
 }
 
 #[test]
@@ -115,7 +188,7 @@ fn update_reward_callback() {
         )
         .unwrap();
 
-    TEMP_BALANCE
+    RESERVED_REWARDS
         .save(deps.as_mut().storage, &Uint128::new(1000000))
         .unwrap();
     ASSET_REWARD_DISTRIBUTION
@@ -146,27 +219,21 @@ fn update_reward_callback() {
     )
     .unwrap();
 
-    let a_whale_rate = ASSET_REWARD_RATE
+    let a_whale_rate = ASSET_REWARD_INDEX
         .load(
             deps.as_ref().storage,
             AssetInfoKey::from(AssetInfo::Native("aWHALE".to_string())),
         )
         .unwrap();
-    assert_eq!(
-        a_whale_rate,
-        Decimal::from_atomics(Uint128::one(), 1).unwrap()
-    );
-    let b_whale_rate = ASSET_REWARD_RATE
+    assert_eq!(a_whale_rate, Uint256::from(10u128.pow(35)));
+    let b_whale_rate = ASSET_REWARD_INDEX
         .load(
             deps.as_ref().storage,
             AssetInfoKey::from(AssetInfo::Native("bWHALE".to_string())),
         )
         .unwrap();
-    assert_eq!(
-        b_whale_rate,
-        Decimal::from_atomics(Uint128::new(6), 0).unwrap()
-    );
-    ASSET_REWARD_RATE
+    assert_eq!(b_whale_rate, Uint256::from(6 * 10u128.pow(36)));
+    ASSET_REWARD_INDEX
         .load(
             deps.as_ref().storage,
             AssetInfoKey::from(AssetInfo::Native("cMONKEY".to_string())),
@@ -209,7 +276,7 @@ fn claim_user_rewards() {
             ],
         )
         .unwrap();
-    TEMP_BALANCE
+    RESERVED_REWARDS
         .save(deps.as_mut().storage, &Uint128::new(1000000))
         .unwrap();
     execute(
@@ -256,7 +323,7 @@ fn claim_user_rewards() {
             }))
     );
 
-    let user_reward_rate = USER_ASSET_REWARD_RATE
+    let user_reward_rate = USER_ASSET_REWARD_INDEX
         .load(
             deps.as_ref().storage,
             (
@@ -265,7 +332,7 @@ fn claim_user_rewards() {
             ),
         )
         .unwrap();
-    let asset_reward_rate = ASSET_REWARD_RATE
+    let asset_reward_rate = ASSET_REWARD_INDEX
         .load(
             deps.as_ref().storage,
             AssetInfoKey::from(AssetInfo::Native("aWHALE".to_string())),
@@ -307,7 +374,7 @@ fn claim_user_rewards() {
     // Update more rewards
     deps.querier
         .update_balance("cosmos2contract", vec![coin(1900000 + 100000, "uluna")]);
-    TEMP_BALANCE
+    RESERVED_REWARDS
         .save(deps.as_mut().storage, &Uint128::new(1900000))
         .unwrap();
     execute(
@@ -363,7 +430,7 @@ fn claim_user_rewards_without_stake() {
             ],
         )
         .unwrap();
-    TEMP_BALANCE
+    RESERVED_REWARDS
         .save(deps.as_mut().storage, &Uint128::new(1000000))
         .unwrap();
     execute(
@@ -386,7 +453,7 @@ fn claim_user_rewards_without_stake() {
             ])
     );
 
-    USER_ASSET_REWARD_RATE
+    USER_ASSET_REWARD_INDEX
         .load(
             deps.as_ref().storage,
             (
@@ -444,7 +511,7 @@ fn claim_user_rewards_after_staking() {
             ],
         )
         .unwrap();
-    TEMP_BALANCE
+    RESERVED_REWARDS
         .save(deps.as_mut().storage, &Uint128::new(1000000))
         .unwrap();
     execute(
@@ -520,7 +587,7 @@ fn claim_rewards_after_staking_and_unstaking() {
             ],
         )
         .unwrap();
-    TEMP_BALANCE
+    RESERVED_REWARDS
         .save(deps.as_mut().storage, &Uint128::new(1000000))
         .unwrap();
     execute(
@@ -533,7 +600,7 @@ fn claim_rewards_after_staking_and_unstaking() {
     claim_rewards(deps.as_mut(), "user1", "aWHALE");
 
     // Get asset reward rate
-    let prev_rate = ASSET_REWARD_RATE
+    let prev_rate = ASSET_REWARD_INDEX
         .load(
             deps.as_mut().storage,
             AssetInfoKey::from(AssetInfo::Native("aWHALE".to_string())),
@@ -544,7 +611,7 @@ fn claim_rewards_after_staking_and_unstaking() {
     unstake(deps.as_mut(), "user1", 1000000, "aWHALE");
 
     // Accrue rewards again
-    TEMP_BALANCE
+    RESERVED_REWARDS
         .save(deps.as_mut().storage, &Uint128::new(1000000))
         .unwrap();
     execute(
@@ -555,7 +622,7 @@ fn claim_rewards_after_staking_and_unstaking() {
     )
     .unwrap();
 
-    let curr_rate = ASSET_REWARD_RATE
+    let curr_rate = ASSET_REWARD_INDEX
         .load(
             deps.as_mut().storage,
             AssetInfoKey::from(AssetInfo::Native("aWHALE".to_string())),
@@ -611,7 +678,7 @@ fn claim_rewards_after_rebalancing_emissions() {
         )
         .unwrap();
 
-    TEMP_BALANCE
+    RESERVED_REWARDS
         .save(deps.as_mut().storage, &Uint128::new(1000000))
         .unwrap();
     execute(
@@ -638,7 +705,7 @@ fn claim_rewards_after_rebalancing_emissions() {
         )
         .unwrap();
 
-    TEMP_BALANCE
+    RESERVED_REWARDS
         .save(deps.as_mut().storage, &Uint128::new(1000000))
         .unwrap();
     execute(
@@ -655,3 +722,733 @@ fn claim_rewards_after_rebalancing_emissions() {
     let rewards = query_rewards(deps.as_ref(), "user2", "bWHALE");
     assert_eq!(rewards.rewards, Uint128::new(500000));
 }
+
+#[test]
+fn update_rewards_with_reward_denom_staked() {
+    let mut deps = mock_dependencies_with_balance(&[]);
+    setup_contract(deps.as_mut());
+    set_alliance_asset(deps.as_mut());
+    whitelist_assets(
+        deps.as_mut(),
+        HashMap::from([(
+            "chain-1".to_string(),
+            vec![AssetInfo::Native("uluna".to_string())],
+        )]),
+    );
+    ASSET_REWARD_DISTRIBUTION
+        .save(
+            deps.as_mut().storage,
+            &vec![AssetDistribution {
+                asset: AssetInfo::Native("uluna".to_string()),
+                distribution: Decimal::percent(100),
+            }],
+        )
+        .unwrap();
+    let update_rewards_callback = |deps: DepsMut| {
+        execute(
+            deps,
+            mock_env(),
+            mock_info("cosmos2contract", &[]),
+            ExecuteMsg::UpdateRewardsCallback {},
+        )
+        .unwrap();
+    };
+    let pending_rewards = |deps: Deps, user: &str| query_rewards(deps, user, "uluna").rewards;
+
+    // Staked principal is not counted as rewards
+    stake(deps.as_mut(), "user1", 1000000, "uluna");
+    deps.querier
+        .update_balance("cosmos2contract", vec![coin(1000000, "uluna")]);
+    update_rewards_callback(deps.as_mut());
+    assert_eq!(pending_rewards(deps.as_ref(), "user1"), Uint128::zero());
+
+    deps.querier
+        .update_balance("cosmos2contract", vec![coin(1100000, "uluna")]);
+    update_rewards_callback(deps.as_mut());
+    assert_eq!(
+        pending_rewards(deps.as_ref(), "user1"),
+        Uint128::new(100000)
+    );
+    assert_eq!(
+        RESERVED_REWARDS.load(deps.as_ref().storage).unwrap(),
+        Uint128::new(100000)
+    );
+
+    // Stakes, claims and unstakes between updates do not change the rewards
+    stake(deps.as_mut(), "user2", 500000, "uluna");
+    deps.querier
+        .update_balance("cosmos2contract", vec![coin(1600000, "uluna")]);
+    update_rewards_callback(deps.as_mut());
+    assert_eq!(
+        pending_rewards(deps.as_ref(), "user1"),
+        Uint128::new(100000)
+    );
+    assert_eq!(pending_rewards(deps.as_ref(), "user2"), Uint128::zero());
+
+    claim_rewards(deps.as_mut(), "user1", "uluna");
+    unstake(deps.as_mut(), "user2", 500000, "uluna");
+    deps.querier
+        .update_balance("cosmos2contract", vec![coin(1000000, "uluna")]);
+    update_rewards_callback(deps.as_mut());
+    assert_eq!(pending_rewards(deps.as_ref(), "user1"), Uint128::zero());
+    assert_eq!(pending_rewards(deps.as_ref(), "user2"), Uint128::zero());
+    assert_eq!(
+        RESERVED_REWARDS.load(deps.as_ref().storage).unwrap(),
+        Uint128::zero()
+    );
+
+    // Reward denom sent to the contract is distributed with the next update
+    deps.querier
+        .update_balance("cosmos2contract", vec![coin(1050000, "uluna")]);
+    update_rewards_callback(deps.as_mut());
+    assert_eq!(pending_rewards(deps.as_ref(), "user1"), Uint128::new(50000));
+}
+
+#[test]
+fn migrate_reserves_rewards_held() {
+    let mut deps = mock_dependencies_with_balance(&[coin(1500000, "uluna")]);
+    setup_contract(deps.as_mut());
+    whitelist_assets(
+        deps.as_mut(),
+        HashMap::from([(
+            "chain-1".to_string(),
+            vec![AssetInfo::Native("uluna".to_string())],
+        )]),
+    );
+    stake(deps.as_mut(), "user1", 1000000, "uluna");
+    RESERVED_REWARDS.remove(deps.as_mut().storage);
+
+    migrate(deps.as_mut(), mock_env(), MigrateMsg::default()).unwrap();
+    assert_eq!(
+        RESERVED_REWARDS.load(deps.as_ref().storage).unwrap(),
+        Uint128::new(500000)
+    );
+
+    // Reserved rewards are kept on later migrations
+    deps.querier
+        .update_balance("cosmos2contract", vec![coin(2000000, "uluna")]);
+    migrate(deps.as_mut(), mock_env(), MigrateMsg::default()).unwrap();
+    assert_eq!(
+        RESERVED_REWARDS.load(deps.as_ref().storage).unwrap(),
+        Uint128::new(500000)
+    );
+}
+
+#[test]
+fn dust_carried_forward_and_swept() {
+    let mut deps = mock_dependencies_with_balance(&[]);
+    setup_contract(deps.as_mut());
+    set_alliance_asset(deps.as_mut());
+    whitelist_assets(
+        deps.as_mut(),
+        HashMap::from([(
+            "chain-1".to_string(),
+            vec![
+                AssetInfo::Native("aWHALE".to_string()),
+                AssetInfo::Native("bWHALE".to_string()),
+            ],
+        )]),
+    );
+    ASSET_REWARD_DISTRIBUTION
+        .save(
+            deps.as_mut().storage,
+            &vec![
+                AssetDistribution {
+                    asset: AssetInfo::Native("aWHALE".to_string()),
+                    distribution: Decimal::percent(50),
+                },
+                AssetDistribution {
+                    asset: AssetInfo::Native("bWHALE".to_string()),
+                    distribution: Decimal::percent(50),
+                },
+            ],
+        )
+        .unwrap();
+    let update_rewards_callback = |deps: DepsMut| {
+        execute(
+            deps,
+            mock_env(),
+            mock_info("cosmos2contract", &[]),
+            ExecuteMsg::UpdateRewardsCallback {},
+        )
+        .unwrap();
+    };
+    let query_dust = |deps: Deps| -> DustRes {
+        from_json(query(deps, mock_env(), QueryMsg::Dust {}).unwrap()).unwrap()
+    };
+    let asset_dust = |denom: &str, amount: &str| AssetDust {
+        asset: AssetInfo::Native(denom.to_string()),
+        amount: Decimal::from_str(amount).unwrap(),
+    };
+
+    // bWHALE has no stakers and aWHALE rewards cannot be split evenly
+    stake(deps.as_mut(), "user1", 3, "aWHALE");
+    deps.querier
+        .update_balance("cosmos2contract", vec![coin(100, "uluna")]);
+    update_rewards_callback(deps.as_mut());
+    let res = claim_rewards(deps.as_mut(), "user1", "aWHALE");
+    assert_eq!(res.attributes[3], attr("reward_amount", "49"));
+    assert_eq!(
+        query_dust(deps.as_ref()),
+        DustRes {
+            recipient: None,
+            dust: vec![
+                asset_dust("aWHALE", "0.999999999999999999"),
+                asset_dust("bWHALE", "50"),
+            ],
+        }
+    );
+
+    // Without a recipient the dust is carried forward to the stakers of the asset
+    stake(deps.as_mut(), "user2", 1, "bWHALE");
+    deps.querier
+        .update_balance("cosmos2contract", vec![coin(51, "uluna")]);
+    update_rewards_callback(deps.as_mut());
+    assert_eq!(
+        query_rewards(deps.as_ref(), "user2", "bWHALE").rewards,
+        Uint128::new(50)
+    );
+    assert_eq!(query_dust(deps.as_ref()).dust, vec![]);
+
+    let err = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("gov", &[]),
+        ExecuteMsg::SweepDust {},
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::DustRecipientNotSet {});
+    let err = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("user", &[]),
+        ExecuteMsg::SetDustRecipient {
+            recipient: Some("collector".to_string()),
+        },
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::Unauthorized {});
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("gov", &[]),
+        ExecuteMsg::SetDustRecipient {
+            recipient: Some("collector".to_string()),
+        },
+    )
+    .unwrap();
+
+    // With a recipient the dust is kept until it is swept
+    unstake(deps.as_mut(), "user2", 1, "bWHALE");
+    deps.querier
+        .update_balance("cosmos2contract", vec![coin(61, "uluna")]);
+    update_rewards_callback(deps.as_mut());
+    assert_eq!(
+        query_dust(deps.as_ref()),
+        DustRes {
+            recipient: Some(Addr::unchecked("collector")),
+            dust: vec![asset_dust("bWHALE", "5")],
+        }
+    );
+
+    let err = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("user", &[]),
+        ExecuteMsg::SweepDust {},
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::Unauthorized {});
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("gov", &[]),
+        ExecuteMsg::SweepDust {},
+    )
+    .unwrap();
+    assert_eq!(
+        res,
+        Response::new()
+            .add_attributes(vec![
+                ("action", "sweep_dust"),
+                ("recipient", "collector"),
+                ("amount", "5"),
+            ])
+            .add_message(CosmosMsg::Bank(BankMsg::Send {
+                to_address: "collector".to_string(),
+                amount: coins(5, "uluna"),
+            }))
+    );
+    assert_eq!(query_dust(deps.as_ref()).dust, vec![]);
+    assert_eq!(
+        RESERVED_REWARDS.load(deps.as_ref().storage).unwrap(),
+        Uint128::new(56)
+    );
+}
+
+#[test]
+fn small_rewards_on_large_stakes() {
+    let mut deps = mock_dependencies_with_balance(&[]);
+    setup_contract(deps.as_mut());
+    set_alliance_asset(deps.as_mut());
+    whitelist_assets(
+        deps.as_mut(),
+        HashMap::from([(
+            "chain-1".to_string(),
+            vec![AssetInfo::Native("aWHALE".to_string())],
+        )]),
+    );
+    ASSET_REWARD_DISTRIBUTION
+        .save(
+            deps.as_mut().storage,
+            &vec![AssetDistribution {
+                asset: AssetInfo::Native("aWHALE".to_string()),
+                distribution: Decimal::percent(100),
+            }],
+        )
+        .unwrap();
+    let update_rewards_callback = |deps: DepsMut| {
+        execute(
+            deps,
+            mock_env(),
+            mock_info("cosmos2contract", &[]),
+            ExecuteMsg::UpdateRewardsCallback {},
+        )
+        .unwrap();
+    };
+
+    // Billions of tokens with 18 decimals staked for a single reward token
+    stake(deps.as_mut(), "user1", 10u128.pow(27), "aWHALE");
+    stake(deps.as_mut(), "user2", 3 * 10u128.pow(27), "aWHALE");
+    deps.querier
+        .update_balance("cosmos2contract", vec![coin(1, "uluna")]);
+    update_rewards_callback(deps.as_mut());
+    let asset_key = AssetInfoKey::from(AssetInfo::Native("aWHALE".to_string()));
+    assert_eq!(
+        ASSET_REWARD_INDEX
+            .load(deps.as_ref().storage, asset_key.clone())
+            .unwrap(),
+        Uint256::from(250_000_000u128)
+    );
+    assert_eq!(
+        query_rewards(deps.as_ref(), "user1", "aWHALE").rewards,
+        Uint128::zero()
+    );
+
+    deps.querier
+        .update_balance("cosmos2contract", vec![coin(4, "uluna")]);
+    update_rewards_callback(deps.as_mut());
+    assert_eq!(
+        ASSET_REWARD_INDEX
+            .load(deps.as_ref().storage, asset_key)
+            .unwrap(),
+        Uint256::from(1_000_000_000u128)
+    );
+    assert_eq!(
+        query_rewards(deps.as_ref(), "user1", "aWHALE").rewards,
+        Uint128::new(1)
+    );
+    assert_eq!(
+        query_rewards(deps.as_ref(), "user2", "aWHALE").rewards,
+        Uint128::new(3)
+    );
+    let res = claim_rewards(deps.as_mut(), "user2", "aWHALE");
+    assert_eq!(res.attributes[3], attr("reward_amount", "3"));
+}
+
+#[test]
+fn migrate_legacy_reward_rates() {
+    let mut deps = mock_dependencies_with_balance(&[]);
+    setup_contract(deps.as_mut());
+    set_alliance_asset(deps.as_mut());
+    whitelist_assets(
+        deps.as_mut(),
+        HashMap::from([(
+            "chain-1".to_string(),
+            vec![AssetInfo::Native("aWHALE".to_string())],
+        )]),
+    );
+    stake(deps.as_mut(), "user1", 100, "aWHALE");
+
+    // Rates stored by the previous versions
+    let asset_key = AssetInfoKey::from(AssetInfo::Native("aWHALE".to_string()));
+    let user_key = (Addr::unchecked("user1"), asset_key.clone());
+    ASSET_REWARD_INDEX.remove(deps.as_mut().storage, asset_key.clone());
+    USER_ASSET_REWARD_INDEX.remove(deps.as_mut().storage, user_key.clone());
+    LEGACY_ASSET_REWARD_RATE
+        .save(
+            deps.as_mut().storage,
+            asset_key.clone(),
+            &Decimal::percent(50),
+        )
+        .unwrap();
+    LEGACY_USER_ASSET_REWARD_RATE
+        .save(
+            deps.as_mut().storage,
+            user_key.clone(),
+            &Decimal::percent(10),
+        )
+        .unwrap();
+
+    migrate(deps.as_mut(), mock_env(), MigrateMsg::default()).unwrap();
+    assert_eq!(
+        ASSET_REWARD_INDEX
+            .load(deps.as_ref().storage, asset_key.clone())
+            .unwrap(),
+        Uint256::from(5 * 10u128.pow(35))
+    );
+    LEGACY_ASSET_REWARD_RATE
+        .load(deps.as_ref().storage, asset_key)
+        .unwrap_err();
+
+    // User rates are migrated when their rewards are claimed
+    assert_eq!(
+        query_rewards(deps.as_ref(), "user1", "aWHALE").rewards,
+        Uint128::new(40)
+    );
+    assert_eq!(
+        query_all_rewards(deps.as_ref(), "user1")[0].rewards,
+        Uint128::new(40)
+    );
+    let res = claim_rewards(deps.as_mut(), "user1", "aWHALE");
+    assert_eq!(res.attributes[3], attr("reward_amount", "40"));
+    assert_eq!(
+        USER_ASSET_REWARD_INDEX
+            .load(deps.as_ref().storage, user_key.clone())
+            .unwrap(),
+        Uint256::from(5 * 10u128.pow(35))
+    );
+    LEGACY_USER_ASSET_REWARD_RATE
+        .load(deps.as_ref().storage, user_key)
+        .unwrap_err();
+}
+
+#[test]
+fn protocol_fee_taken_before_distribution() {
+    let mut deps = mock_dependencies_with_balance(&[]);
+    setup_contract(deps.as_mut());
+    set_alliance_asset(deps.as_mut());
+    whitelist_assets(
+        deps.as_mut(),
+        HashMap::from([(
+            "chain-1".to_string(),
+            vec![AssetInfo::Native("aWHALE".to_string())],
+        )]),
+    );
+    ASSET_REWARD_DISTRIBUTION
+        .save(
+            deps.as_mut().storage,
+            &vec![AssetDistribution {
+                asset: AssetInfo::Native("aWHALE".to_string()),
+                distribution: Decimal::percent(100),
+            }],
+        )
+        .unwrap();
+    stake(deps.as_mut(), "user1", 100, "aWHALE");
+    let update_protocol_fee = |deps: DepsMut, sender: &str, take_rate: u64| {
+        execute(
+            deps,
+            mock_env(),
+            mock_info(sender, &[]),
+            ExecuteMsg::UpdateProtocolFee(ProtocolFeeParams {
+                take_rate: Decimal::percent(take_rate),
+                fee_collector: "collector".to_string(),
+            }),
+        )
+    };
+    let update_rewards_callback = |deps: DepsMut| {
+        execute(
+            deps,
+            mock_env(),
+            mock_info("cosmos2contract", &[]),
+            ExecuteMsg::UpdateRewardsCallback {},
+        )
+        .unwrap()
+    };
+
+    let err = update_protocol_fee(deps.as_mut(), "user", 10).unwrap_err();
+    assert_eq!(err, ContractError::Unauthorized {});
+    let err = update_protocol_fee(deps.as_mut(), "gov", 21).unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::InvalidTakeRate(Decimal::percent(21), Decimal::percent(20))
+    );
+    update_protocol_fee(deps.as_mut(), "gov", 10).unwrap();
+
+    deps.querier
+        .update_balance("cosmos2contract", vec![coin(1000, "uluna")]);
+    let res = update_rewards_callback(deps.as_mut());
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(CosmosMsg::Bank(BankMsg::Send {
+            to_address: "collector".to_string(),
+            amount: coins(100, "uluna"),
+        }))]
+    );
+    assert_eq!(
+        query_rewards(deps.as_ref(), "user1", "aWHALE").rewards,
+        Uint128::new(900)
+    );
+
+    // The fee sent to the collector is not counted as rewards again
+    deps.querier
+        .update_balance("cosmos2contract", vec![coin(1400, "uluna")]);
+    update_rewards_callback(deps.as_mut());
+    assert_eq!(
+        query_rewards(deps.as_ref(), "user1", "aWHALE").rewards,
+        Uint128::new(1350)
+    );
+    let res = query(deps.as_ref(), mock_env(), QueryMsg::ProtocolFee {}).unwrap();
+    let res: ProtocolFeeRes = from_json(res).unwrap();
+    assert_eq!(
+        res,
+        ProtocolFeeRes {
+            protocol_fee: Some(ProtocolFee {
+                take_rate: Decimal::percent(10),
+                fee_collector: Addr::unchecked("collector"),
+            }),
+            fees_collected: Uint128::new(150),
+        }
+    );
+}
+
+#[test]
+fn rewards_streamed_over_period() {
+    let mut deps = mock_dependencies_with_balance(&[]);
+    setup_contract(deps.as_mut());
+    set_alliance_asset(deps.as_mut());
+    whitelist_assets(
+        deps.as_mut(),
+        HashMap::from([(
+            "chain-1".to_string(),
+            vec![AssetInfo::Native("aWHALE".to_string())],
+        )]),
+    );
+    ASSET_REWARD_DISTRIBUTION
+        .save(
+            deps.as_mut().storage,
+            &vec![AssetDistribution {
+                asset: AssetInfo::Native("aWHALE".to_string()),
+                distribution: Decimal::percent(100),
+            }],
+        )
+        .unwrap();
+    stake(deps.as_mut(), "user1", 100, "aWHALE");
+    let env_at = |seconds: u64| {
+        let mut env = mock_env();
+        env.block.time = env.block.time.plus_seconds(seconds);
+        env
+    };
+    let pending_rewards = |deps: Deps, user: &str, seconds: u64| {
+        let res: PendingRewardsRes = from_json(
+            query(
+                deps,
+                env_at(seconds),
+                QueryMsg::PendingRewards(AssetQuery {
+                    address: user.to_string(),
+                    asset: AssetInfo::Native("aWHALE".to_string()),
+                }),
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        res.rewards.u128()
+    };
+
+    let msg = ExecuteMsg::UpdateRewardStreamPeriod { seconds: 100 };
+    let err = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("user", &[]),
+        msg.clone(),
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::Unauthorized {});
+    execute(deps.as_mut(), mock_env(), mock_info("gov", &[]), msg).unwrap();
+
+    deps.querier
+        .update_balance("cosmos2contract", vec![coin(1000, "uluna")]);
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("cosmos2contract", &[]),
+        ExecuteMsg::UpdateRewardsCallback {},
+    )
+    .unwrap();
+    assert_eq!(pending_rewards(deps.as_ref(), "user1", 0), 0);
+    assert_eq!(pending_rewards(deps.as_ref(), "user1", 50), 500);
+
+    // Staking midway releases the rewards streamed so far to the previous stakers
+    execute(
+        deps.as_mut(),
+        env_at(50),
+        mock_info("user2", &[coin(100, "aWHALE")]),
+        ExecuteMsg::Stake { recipient: None },
+    )
+    .unwrap();
+    assert_eq!(pending_rewards(deps.as_ref(), "user1", 50), 500);
+    assert_eq!(pending_rewards(deps.as_ref(), "user2", 50), 0);
+    assert_eq!(pending_rewards(deps.as_ref(), "user1", 200), 750);
+    assert_eq!(pending_rewards(deps.as_ref(), "user2", 200), 250);
+
+    let res = execute(
+        deps.as_mut(),
+        env_at(200),
+        mock_info("user1", &[]),
+        ExecuteMsg::ClaimRewards(AssetInfo::Native("aWHALE".to_string())),
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(CosmosMsg::Bank(BankMsg::Send {
+            to_address: "user1".to_string(),
+            amount: coins(750, "uluna"),
+        }))]
+    );
+    assert_eq!(pending_rewards(deps.as_ref(), "user2", 200), 250);
+}
+
+#[test]
+fn rewards_kept_without_distribution() {
+    let mut deps = mock_dependencies_with_balance(&[]);
+    setup_contract(deps.as_mut());
+    set_alliance_asset(deps.as_mut());
+    whitelist_assets(
+        deps.as_mut(),
+        HashMap::from([(
+            "chain-1".to_string(),
+            vec![AssetInfo::Native("aWHALE".to_string())],
+        )]),
+    );
+    stake(deps.as_mut(), "user1", 100, "aWHALE");
+    let update_rewards_callback = |deps: DepsMut| {
+        execute(
+            deps,
+            mock_env(),
+            mock_info("cosmos2contract", &[]),
+            ExecuteMsg::UpdateRewardsCallback {},
+        )
+        .unwrap()
+    };
+
+    // A distribution that adds up to zero does not reserve the rewards
+    ASSET_REWARD_DISTRIBUTION
+        .save(
+            deps.as_mut().storage,
+            &vec![AssetDistribution {
+                asset: AssetInfo::Native("aWHALE".to_string()),
+                distribution: Decimal::zero(),
+            }],
+        )
+        .unwrap();
+    deps.querier
+        .update_balance("cosmos2contract", vec![coin(1000, "uluna")]);
+    let res = update_rewards_callback(deps.as_mut());
+    assert_eq!(
+        res.attributes,
+        vec![
+            attr("action", "update_rewards_callback"),
+            attr("rewards_kept", "1000"),
+        ]
+    );
+    assert_eq!(
+        RESERVED_REWARDS.load(deps.as_ref().storage).unwrap(),
+        Uint128::zero()
+    );
+    assert_eq!(
+        query_rewards(deps.as_ref(), "user1", "aWHALE").rewards,
+        Uint128::zero()
+    );
+
+    // and they are distributed by the next update with a distribution
+    ASSET_REWARD_DISTRIBUTION
+        .save(
+            deps.as_mut().storage,
+            &vec![AssetDistribution {
+                asset: AssetInfo::Native("aWHALE".to_string()),
+                distribution: Decimal::percent(100),
+            }],
+        )
+        .unwrap();
+    deps.querier
+        .update_balance("cosmos2contract", vec![coin(1500, "uluna")]);
+    update_rewards_callback(deps.as_mut());
+    assert_eq!(
+        query_rewards(deps.as_ref(), "user1", "aWHALE").rewards,
+        Uint128::new(1500)
+    );
+    assert_eq!(
+        RESERVED_REWARDS.load(deps.as_ref().storage).unwrap(),
+        Uint128::new(1500)
+    );
+}
+
+#[test]
+fn rewards_kept_when_all_chains_are_stale() {
+    let mut deps = mock_dependencies_with_balance(&[]);
+    setup_contract(deps.as_mut());
+    set_alliance_asset(deps.as_mut());
+    whitelist_assets(
+        deps.as_mut(),
+        HashMap::from([(
+            "chain-1".to_string(),
+            vec![AssetInfo::Native("aWHALE".to_string())],
+        )]),
+    );
+    stake(deps.as_mut(), "user1", 100, "aWHALE");
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("gov", &[]),
+        ExecuteMsg::UpdateProtocolFee(ProtocolFeeParams {
+            take_rate: Decimal::percent(10),
+            fee_collector: "collector".to_string(),
+        }),
+    )
+    .unwrap();
+
+    // With the exclude policy the oracle leaves out the stale chains,
+    // so the distribution is empty when all of them are stale
+    deps.querier.update_wasm(|_| {
+        SystemResult::Ok(ContractResult::Ok(
+            to_json_binary(&Vec::<EmissionsDistribution>::new()).unwrap(),
+        ))
+    });
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("cosmos2contract", &[]),
+        ExecuteMsg::RebalanceEmissionsCallback {},
+    )
+    .unwrap();
+    assert_eq!(
+        ASSET_REWARD_DISTRIBUTION
+            .load(deps.as_ref().storage)
+            .unwrap(),
+        vec![]
+    );
+
+    // No fee is taken and the rewards are kept for the next update
+    deps.querier
+        .update_balance("cosmos2contract", vec![coin(1000, "uluna")]);
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("cosmos2contract", &[]),
+        ExecuteMsg::UpdateRewardsCallback {},
+    )
+    .unwrap();
+    assert_eq!(res.messages, vec![]);
+    assert_eq!(res.attributes[1], attr("rewards_kept", "1000"));
+    let res = query(deps.as_ref(), mock_env(), QueryMsg::ProtocolFee {}).unwrap();
+    let res: ProtocolFeeRes = from_json(res).unwrap();
+    assert_eq!(res.fees_collected, Uint128::zero());
+    assert_eq!(
+        RESERVED_REWARDS.load(deps.as_ref().storage).unwrap(),
+        Uint128::zero()
+    );
+    assert_eq!(
+        query_rewards(deps.as_ref(), "user1", "aWHALE").rewards,
+        Uint128::zero()
+    );
+}
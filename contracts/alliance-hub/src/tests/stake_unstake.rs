@@ -1,12 +1,23 @@
 use crate::contract::execute;
-use crate::models::{ExecuteMsg, StakedBalanceRes};
-use crate::state::{BALANCES, TOTAL_BALANCES};
+use crate::models::{
+    ExecuteMsg, QueryMsg, StakeAllowanceParams, StakeAllowanceQuery, StakedBalanceRes,
+    TransferStakeParams,
+};
+use crate::query::query;
+use crate::state::{ASSET_REWARD_DISTRIBUTION, BALANCES, TOTAL_BALANCES};
 use crate::tests::helpers::{
-    query_all_staked_balances, setup_contract, stake, unstake, whitelist_assets,
+    query_all_staked_balances, query_rewards, set_alliance_asset, setup_contract, stake, unstake,
+    whitelist_assets,
 };
+use alliance_protocol::alliance_protocol::AssetDistribution;
 use alliance_protocol::error::ContractError;
-use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info};
-use cosmwasm_std::{coin, Addr, BankMsg, CosmosMsg, Response, Uint128};
+use alliance_protocol::token_factory::CustomExecuteMsg;
+use cosmwasm_std::testing::{
+    mock_dependencies, mock_dependencies_with_balance, mock_env, mock_info,
+};
+use cosmwasm_std::{
+    coin, from_json, Addr, BankMsg, CosmosMsg, Decimal, DepsMut, Response, Uint128,
+};
 use cw_asset::{Asset, AssetInfo, AssetInfoKey};
 use std::collections::HashMap;
 
@@ -80,6 +91,7 @@ fn test_stake() {
         vec![StakedBalanceRes {
             asset: AssetInfo::Native("asset1".to_string()),
             balance: Uint128::new(200),
+            effective_balance: Uint128::new(200),
         }]
     );
 }
@@ -96,25 +108,35 @@ fn test_stake_invalid() {
         )]),
     );
     // Stake an unwhitelisted asset
-    let msg = ExecuteMsg::Stake {};
+    let msg = ExecuteMsg::Stake { recipient: None };
     let info = mock_info("user1", &[coin(100, "asset2")]);
     let err = execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
     assert_eq!(err, ContractError::AssetNotWhitelisted("native:asset2".to_string()));
 
-    // Stake multiple assets in a single call
-    let msg = ExecuteMsg::Stake {};
+    // Stake multiple assets when one of them is not whitelisted
+    let msg = ExecuteMsg::Stake { recipient: None };
     let info = mock_info("user1", &[coin(100, "asset1"), coin(100, "asset2")]);
     let err = execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
-    assert_eq!(err, ContractError::OnlySingleAssetAllowed {});
+    assert_eq!(
+        err,
+        ContractError::AssetNotWhitelisted("native:asset2".to_string())
+    );
+    assert!(!BALANCES.has(
+        deps.as_ref().storage,
+        (
+            Addr::unchecked("user1"),
+            AssetInfoKey::from(AssetInfo::Native("asset1".to_string())),
+        ),
+    ));
 
     // Stake nothing in a single call
-    let msg = ExecuteMsg::Stake {};
+    let msg = ExecuteMsg::Stake { recipient: None };
     let info = mock_info("user1", &[]);
     let err = execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
-    assert_eq!(err, ContractError::OnlySingleAssetAllowed {});
+    assert_eq!(err, ContractError::AmountCannotBeZero {});
 
     // Stake zero amount
-    let msg = ExecuteMsg::Stake {};
+    let msg = ExecuteMsg::Stake { recipient: None };
     let info = mock_info("user1", &[coin(0, "asset1")]);
     let err = execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
     assert_eq!(err, ContractError::AmountCannotBeZero {});
@@ -215,7 +237,7 @@ fn test_unstake_invalid() {
     let info = mock_info("user2", &[]);
     let msg = ExecuteMsg::Unstake(Asset::native("asset1", 100u128));
     let err = execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
-    assert_eq!(err, ContractError::InsufficientBalance {});
+    assert_eq!(err, ContractError::AssetNotStaked {});
 
     // User unstakes more than they have
     let info = mock_info("user1", &[]);
@@ -229,3 +251,293 @@ fn test_unstake_invalid() {
     let err = execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
     assert_eq!(err, ContractError::AmountCannotBeZero {});
 }
+
+#[test]
+fn test_transfer_stake() {
+    let mut deps = mock_dependencies_with_balance(&[]);
+    setup_contract(deps.as_mut());
+    set_alliance_asset(deps.as_mut());
+    whitelist_assets(
+        deps.as_mut(),
+        HashMap::from([(
+            "chain-1".to_string(),
+            vec![AssetInfo::Native("aWHALE".to_string())],
+        )]),
+    );
+    ASSET_REWARD_DISTRIBUTION
+        .save(
+            deps.as_mut().storage,
+            &vec![AssetDistribution {
+                asset: AssetInfo::Native("aWHALE".to_string()),
+                distribution: Decimal::percent(100),
+            }],
+        )
+        .unwrap();
+    let transfer_stake =
+        |deps: DepsMut, sender: &str, owner: Option<&str>, amount: u128, recipient: &str| {
+            execute(
+                deps,
+                mock_env(),
+                mock_info(sender, &[]),
+                ExecuteMsg::TransferStake(TransferStakeParams {
+                    owner: owner.map(|owner| owner.to_string()),
+                    asset: Asset::native("aWHALE", amount),
+                    recipient: recipient.to_string(),
+                }),
+            )
+        };
+    let update_rewards_callback = |deps: DepsMut| -> Response<CustomExecuteMsg> {
+        execute(
+            deps,
+            mock_env(),
+            mock_info("cosmos2contract", &[]),
+            ExecuteMsg::UpdateRewardsCallback {},
+        )
+        .unwrap()
+    };
+    let balance = |deps: &DepsMut, user: &str| {
+        BALANCES
+            .load(
+                deps.storage,
+                (
+                    Addr::unchecked(user),
+                    AssetInfoKey::from(AssetInfo::Native("aWHALE".to_string())),
+                ),
+            )
+            .unwrap()
+            .u128()
+    };
+
+    stake(deps.as_mut(), "user1", 100, "aWHALE");
+    deps.querier
+        .update_balance("cosmos2contract", vec![coin(1000, "uluna")]);
+    update_rewards_callback(deps.as_mut());
+
+    // The rewards accrued before the transfer stay with the owner
+    let res = transfer_stake(deps.as_mut(), "user1", None, 40, "user2").unwrap();
+    assert_eq!(
+        res,
+        Response::default().add_attributes(vec![
+            ("action", "transfer_stake"),
+            ("sender", "user1"),
+            ("owner", "user1"),
+            ("recipient", "user2"),
+            ("asset", "native:aWHALE"),
+            ("amount", "40"),
+        ])
+    );
+    assert_eq!(balance(&deps.as_mut(), "user1"), 60);
+    assert_eq!(balance(&deps.as_mut(), "user2"), 40);
+    assert_eq!(
+        query_rewards(deps.as_ref(), "user1", "aWHALE").rewards,
+        Uint128::new(1000)
+    );
+    assert_eq!(
+        query_rewards(deps.as_ref(), "user2", "aWHALE").rewards,
+        Uint128::zero()
+    );
+
+    deps.querier
+        .update_balance("cosmos2contract", vec![coin(2000, "uluna")]);
+    update_rewards_callback(deps.as_mut());
+    assert_eq!(
+        query_rewards(deps.as_ref(), "user1", "aWHALE").rewards,
+        Uint128::new(1600)
+    );
+    assert_eq!(
+        query_rewards(deps.as_ref(), "user2", "aWHALE").rewards,
+        Uint128::new(400)
+    );
+
+    let err = transfer_stake(deps.as_mut(), "user1", None, 100, "user2").unwrap_err();
+    assert_eq!(err, ContractError::InsufficientBalance {});
+
+    // A spender can transfer the stake of the owner up to its allowance
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("user2", &[]),
+        ExecuteMsg::SetStakeAllowance(StakeAllowanceParams {
+            spender: "vault".to_string(),
+            asset: Asset::native("aWHALE", 30u128),
+        }),
+    )
+    .unwrap();
+    let err = transfer_stake(deps.as_mut(), "vault", Some("user2"), 40, "user3").unwrap_err();
+    assert_eq!(err, ContractError::InsufficientAllowance {});
+    transfer_stake(deps.as_mut(), "vault", Some("user2"), 30, "user3").unwrap();
+    assert_eq!(balance(&deps.as_mut(), "user2"), 10);
+    assert_eq!(balance(&deps.as_mut(), "user3"), 30);
+    let res = query(
+        deps.as_ref(),
+        mock_env(),
+        QueryMsg::StakeAllowance(StakeAllowanceQuery {
+            owner: "user2".to_string(),
+            spender: "vault".to_string(),
+            asset: AssetInfo::Native("aWHALE".to_string()),
+        }),
+    )
+    .unwrap();
+    let allowance: Uint128 = from_json(res).unwrap();
+    assert_eq!(allowance, Uint128::zero());
+}
+
+#[test]
+fn test_stake_multiple_assets() {
+    let mut deps = mock_dependencies_with_balance(&[]);
+    setup_contract(deps.as_mut());
+    set_alliance_asset(deps.as_mut());
+    whitelist_assets(
+        deps.as_mut(),
+        HashMap::from([(
+            "chain-1".to_string(),
+            vec![
+                AssetInfo::Native("asset1".to_string()),
+                AssetInfo::Native("asset2".to_string()),
+            ],
+        )]),
+    );
+    ASSET_REWARD_DISTRIBUTION
+        .save(
+            deps.as_mut().storage,
+            &vec![AssetDistribution {
+                asset: AssetInfo::Native("asset1".to_string()),
+                distribution: Decimal::percent(100),
+            }],
+        )
+        .unwrap();
+    stake(deps.as_mut(), "user1", 100, "asset1");
+    deps.querier
+        .update_balance("cosmos2contract", vec![coin(1000, "uluna")]);
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("cosmos2contract", &[]),
+        ExecuteMsg::UpdateRewardsCallback {},
+    )
+    .unwrap();
+
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("user1", &[coin(100, "asset1"), coin(50, "asset2")]),
+        ExecuteMsg::Stake { recipient: None },
+    )
+    .unwrap();
+    assert_eq!(
+        res,
+        Response::default().add_attributes(vec![
+            ("action", "stake"),
+            ("user", "user1"),
+            ("asset", "native:asset1"),
+            ("amount", "100"),
+            ("asset", "native:asset2"),
+            ("amount", "50"),
+        ])
+    );
+    assert_eq!(
+        query_all_staked_balances(deps.as_ref()),
+        vec![
+            StakedBalanceRes {
+                asset: AssetInfo::Native("asset1".to_string()),
+                balance: Uint128::new(200),
+                effective_balance: Uint128::new(200),
+            },
+            StakedBalanceRes {
+                asset: AssetInfo::Native("asset2".to_string()),
+                balance: Uint128::new(50),
+                effective_balance: Uint128::new(50),
+            },
+        ]
+    );
+    // Rewards accrued before the stake are kept
+    assert_eq!(
+        query_rewards(deps.as_ref(), "user1", "asset1").rewards,
+        Uint128::new(1000)
+    );
+}
+
+#[test]
+fn test_stake_on_behalf() {
+    let mut deps = mock_dependencies_with_balance(&[]);
+    setup_contract(deps.as_mut());
+    set_alliance_asset(deps.as_mut());
+    whitelist_assets(
+        deps.as_mut(),
+        HashMap::from([(
+            "chain-1".to_string(),
+            vec![AssetInfo::Native("aWHALE".to_string())],
+        )]),
+    );
+    ASSET_REWARD_DISTRIBUTION
+        .save(
+            deps.as_mut().storage,
+            &vec![AssetDistribution {
+                asset: AssetInfo::Native("aWHALE".to_string()),
+                distribution: Decimal::percent(100),
+            }],
+        )
+        .unwrap();
+    stake(deps.as_mut(), "user1", 100, "aWHALE");
+    deps.querier
+        .update_balance("cosmos2contract", vec![coin(1000, "uluna")]);
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("cosmos2contract", &[]),
+        ExecuteMsg::UpdateRewardsCallback {},
+    )
+    .unwrap();
+
+    let err = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("vault", &[coin(100, "aWHALE")]),
+        ExecuteMsg::Stake {
+            recipient: Some("".to_string()),
+        },
+    )
+    .unwrap_err();
+    assert!(matches!(err, ContractError::Std(_)));
+
+    // The rewards of the recipient are settled before its balance increases
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("vault", &[coin(100, "aWHALE")]),
+        ExecuteMsg::Stake {
+            recipient: Some("user1".to_string()),
+        },
+    )
+    .unwrap();
+    assert_eq!(
+        res,
+        Response::default().add_attributes(vec![
+            ("action", "stake"),
+            ("user", "user1"),
+            ("asset", "native:aWHALE"),
+            ("amount", "100"),
+        ])
+    );
+    let balance = BALANCES
+        .load(
+            deps.as_ref().storage,
+            (
+                Addr::unchecked("user1"),
+                AssetInfoKey::from(AssetInfo::Native("aWHALE".to_string())),
+            ),
+        )
+        .unwrap();
+    assert_eq!(balance, Uint128::new(200));
+    assert!(!BALANCES.has(
+        deps.as_ref().storage,
+        (
+            Addr::unchecked("vault"),
+            AssetInfoKey::from(AssetInfo::Native("aWHALE".to_string())),
+        ),
+    ));
+    assert_eq!(
+        query_rewards(deps.as_ref(), "user1", "aWHALE").rewards,
+        Uint128::new(1000)
+    );
+}
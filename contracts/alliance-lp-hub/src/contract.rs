@@ -7,24 +7,24 @@ use alliance_protocol::{
 };
 #[cfg(not(feature = "library"))]
 use cosmwasm_std::entry_point;
-use cosmwasm_std::{to_json_binary, Addr, Binary, Coin as CwCoin, CosmosMsg, Decimal, DepsMut, Empty, Env, MessageInfo, Reply, Response, StdError, StdResult, Storage, SubMsg, Uint128, WasmMsg, Order, BankMsg};
+use cosmwasm_std::{from_json, to_json_binary, Addr, Binary, Coin as CwCoin, CosmosMsg, Decimal, DepsMut, Env, MessageInfo, Reply, Response, StdResult, Storage, SubMsg, SubMsgResponse, Uint128, WasmMsg, Order, BankMsg};
 use cw2::set_contract_version;
 use cw20::{Cw20ExecuteMsg, Cw20ReceiveMsg};
 use cw_asset::{Asset, AssetInfo, AssetInfoKey, AssetInfoUnchecked};
-use cw_utils::parse_instantiate_response_data;
 use std::{collections::HashSet, env};
 use std::str::FromStr;
 use terra_proto_rs::{
     alliance::alliance::{MsgClaimDelegationRewards, MsgDelegate, MsgRedelegate, MsgUndelegate},
     cosmos::base::v1beta1::Coin,
+    osmosis::tokenfactory::v1beta1::MsgCreateDenomResponse,
     traits::Message,
 };
 use alliance_protocol::alliance_oracle_types::EmissionsDistribution;
 
 use crate::{
-    models::{Config, ExecuteMsg, InstantiateMsg, ModifyAsset},
+    models::{Config, Cw20HookMsg, ExecuteMsg, InstantiateMsg, ModifyAsset},
     state::{
-        ASSET_REWARD_RATE, BALANCES, CONFIG, TEMP_BALANCE,
+        ALLIANCE_TOKEN_PARAMS, ASSET_REWARD_RATE, BALANCES, CONFIG, TEMP_BALANCE,
         TOTAL_BALANCES, UNCLAIMED_REWARDS, USER_ASSET_REWARD_RATE, VALIDATORS, WHITELIST,
     }, astro_models::{QueryAstroMsg, RewardInfo, ExecuteAstroMsg, Cw20Msg},
 };
@@ -52,13 +52,12 @@ pub fn instantiate(
     let controller_address = deps.api.addr_validate(msg.controller.as_str())?;
     let astro_incentives_address = deps.api.addr_validate(msg.astro_incentives_address.as_str())?;
     let fee_collector_address =  deps.api.addr_validate(msg.fee_collector_address.as_str())?;
-    let create_msg = TokenExecuteMsg::CreateDenom {
-        subdenom: "ualliancelp".to_string(),
-    };
-    let sub_msg = SubMsg::reply_on_success(
-        CosmosMsg::Custom(CustomExecuteMsg::Token(create_msg)),
-        CREATE_REPLY_ID,
-    );
+    let alliance_token = msg.alliance_token.unwrap_or_default();
+    if alliance_token.initial_supply.is_zero() {
+        return Err(ContractError::AmountCannotBeZero {});
+    }
+    ALLIANCE_TOKEN_PARAMS.save(deps.storage, &alliance_token)?;
+    let sub_msg = create_denom_msg("ualliancelp".to_string());
     let config = Config {
         governance: governance_address,
         controller: controller_address,
@@ -83,25 +82,42 @@ pub fn execute(
     env: Env,
     info: MessageInfo,
     msg: ExecuteMsg,
-) -> Result<Response, ContractError> {
+) -> Result<Response<CustomExecuteMsg>, ContractError> {
     match msg {
         ExecuteMsg::ModifyAssets(assets) => modify_assets(deps, info, assets),
 
         ExecuteMsg::Receive(cw20_msg) => {
             let sender = deps.api.addr_validate(&cw20_msg.sender)?;
+            if cw20_msg.amount.is_zero() {
+                return Err(ContractError::AmountCannotBeZero {});
+            }
+            if cw20_msg.msg.is_empty() {
+                return Err(ContractError::InvalidCw20HookMsg("empty payload".to_string()));
+            }
+            // info.sender is the token contract, which must be whitelisted to be staked
             let received_asset = Asset::cw20(info.sender.clone(), cw20_msg.amount);
+            let hook_msg: Cw20HookMsg = from_json(&cw20_msg.msg)
+                .map_err(|err| ContractError::InvalidCw20HookMsg(err.to_string()))?;
+            let recipient = match hook_msg {
+                Cw20HookMsg::Stake { recipient } => recipient,
+            };
+            let user = match recipient {
+                Some(recipient) => deps.api.addr_validate(&recipient)?,
+                None => sender,
+            };
 
-            stake(deps,env, sender, received_asset)
+            stake(deps,env, user, vec![received_asset])
         }
-        ExecuteMsg::Stake {} => {
-            if info.funds.len() != 1 {
-                return Err(ContractError::OnlySingleAssetAllowed {});
-            }
-            let coin = info.funds[0].clone();
-            if coin.amount.is_zero() {
+        ExecuteMsg::Stake { recipient } => {
+            if info.funds.is_empty() || info.funds.iter().any(|coin| coin.amount.is_zero()) {
                 return Err(ContractError::AmountCannotBeZero {});
             }
-            stake(deps,env, info.sender, coin.into())
+            let user = match recipient {
+                Some(recipient) => deps.api.addr_validate(&recipient)?,
+                None => info.sender,
+            };
+            let assets = info.funds.into_iter().map(Asset::from).collect();
+            stake(deps,env, user, assets)
         }
         
         ExecuteMsg::Unstake(asset) => unstake(deps, info, asset),
@@ -116,6 +132,11 @@ pub fn execute(
 
         ExecuteMsg::UpdateRewardsCallback {} => update_reward_callback(deps, env, info),
         ExecuteMsg::RebalanceEmissionsCallback(distributions) => rebalance_emissions_callback(deps, env, info, distributions),
+
+        ExecuteMsg::MintAllianceTokens { amount } => mint_alliance_tokens(deps, env, info, amount),
+        ExecuteMsg::BurnAllianceTokens { amount } => burn_alliance_tokens(deps, env, info, amount),
+        ExecuteMsg::SetAllianceTokenDenom { denom } => set_alliance_token_denom(deps, env, info, denom),
+        ExecuteMsg::RecreateDenom { subdenom } => recreate_denom(deps, info, subdenom),
     }
 }
 
@@ -125,7 +146,7 @@ fn modify_assets(
     deps: DepsMut,
     info: MessageInfo,
     assets: Vec<ModifyAsset>,
-) -> Result<Response, ContractError> {
+) -> Result<Response<CustomExecuteMsg>, ContractError> {
     let config = CONFIG.load(deps.storage)?;
     is_governance(&info, &config)?;
     let mut attrs = vec![("action".to_string(), "modify_assets".to_string())];
@@ -154,118 +175,124 @@ fn modify_assets(
 }
 
 // This method is used to stake both native and CW20 tokens,
-// it checks if the asset is whitelisted and then proceeds to
-// update the user balance and the total balance for the asset.
+// it checks if all the assets are whitelisted and then proceeds to
+// update the user balance and the total balance for each asset.
 fn stake(
     deps: DepsMut,
     env: Env,
     sender: Addr,
-    received_asset: Asset,
-) -> Result<Response, ContractError> {
-    let asset_key = AssetInfoKey::from(&received_asset.info);
-    WHITELIST
-        .load(deps.storage, asset_key.clone())
-        .map_err(|_| ContractError::AssetNotWhitelisted(received_asset.info.to_string()))?;
-
-    let rewards = _claim_reward(deps.storage, sender.clone(), received_asset.info.clone())?;
-    if !rewards.is_zero() {
-        UNCLAIMED_REWARDS.update(
-            deps.storage,
-            (sender.clone(), asset_key.clone()),
-            |balance| -> Result<_, ContractError> {
-                Ok(balance.unwrap_or(Uint128::zero()) + rewards)
-            },
-        )?;
+    received_assets: Vec<Asset>,
+) -> Result<Response<CustomExecuteMsg>, ContractError> {
+    for received_asset in &received_assets {
+        WHITELIST
+            .load(deps.storage, AssetInfoKey::from(&received_asset.info))
+            .map_err(|_| ContractError::AssetNotWhitelisted(received_asset.info.to_string()))?;
     }
     let config = CONFIG.load(deps.storage)?;
-    
-    // Query astro incentives, to do so we must first remove the prefix 
-    // from the asset info e.g. cw20:asset1 -> asset1 or native:uluna -> uluna
-    let lp_token = received_asset.info.to_string();
-    let astro_incentives: Vec<RewardInfo> = deps.querier.query_wasm_smart(
-        config.astro_incentives.to_string(),
-        &QueryAstroMsg::RewardInfo{
-            lp_token: lp_token.split(':').collect::<Vec<&str>>()[1].to_string(),
-        },
-    ).unwrap_or_default();
-
     let mut res = Response::new().add_attributes(vec![
         ("action", "stake"),
         ("user", sender.as_ref()),
-        ("asset", &received_asset.info.to_string()),
-        ("amount", &received_asset.amount.to_string()),
     ]);
 
-    if !astro_incentives.is_empty() {
-        let msg = match received_asset.info.clone() {
-            AssetInfo::Native(native_asset) => {
-                // If the asset is native, we need to send it to the astro incentives contract
-                // using the ExecuteAstroMsg::Deposit message
-                CosmosMsg::Wasm(WasmMsg::Execute {
-                    contract_addr: config.astro_incentives.to_string(),
-                    msg: to_json_binary(&ExecuteAstroMsg::Deposit {
-                        recipient: None,
-                    })?,
-                    funds: vec![CwCoin {
-                        denom: native_asset,
-                        amount: received_asset.amount,
-                    }],
-                })
-            }
-            AssetInfo::Cw20(cw20_contract_addr) => {
-                // If the asset is a cw20 token, we need to send it to the astro incentives contract
-                // using the ExecuteAstroMsg::Receive message
-                CosmosMsg::Wasm(WasmMsg::Execute {
-                    contract_addr: cw20_contract_addr.to_string(),
-                    msg: to_json_binary(&Cw20ExecuteMsg::Send {
-                        contract: config.astro_incentives.to_string(),
-                        amount: received_asset.amount,
-                        msg: to_json_binary(&Cw20ReceiveMsg {
-                            sender: env.contract.address.to_string(),
+    for received_asset in received_assets {
+        let asset_key = AssetInfoKey::from(&received_asset.info);
+        let rewards = _claim_reward(deps.storage, sender.clone(), received_asset.info.clone())?;
+        if !rewards.is_zero() {
+            UNCLAIMED_REWARDS.update(
+                deps.storage,
+                (sender.clone(), asset_key.clone()),
+                |balance| -> Result<_, ContractError> {
+                    Ok(balance.unwrap_or(Uint128::zero()) + rewards)
+                },
+            )?;
+        }
+
+        // Query astro incentives, to do so we must first remove the prefix
+        // from the asset info e.g. cw20:asset1 -> asset1 or native:uluna -> uluna
+        let lp_token = received_asset.info.to_string();
+        let astro_incentives: Vec<RewardInfo> = deps.querier.query_wasm_smart(
+            config.astro_incentives.to_string(),
+            &QueryAstroMsg::RewardInfo{
+                lp_token: lp_token.split(':').collect::<Vec<&str>>()[1].to_string(),
+            },
+        ).unwrap_or_default();
+
+        res = res.add_attributes(vec![
+            ("asset", received_asset.info.to_string()),
+            ("amount", received_asset.amount.to_string()),
+        ]);
+
+        if !astro_incentives.is_empty() {
+            let msg = match received_asset.info.clone() {
+                AssetInfo::Native(native_asset) => {
+                    // If the asset is native, we need to send it to the astro incentives contract
+                    // using the ExecuteAstroMsg::Deposit message
+                    CosmosMsg::Wasm(WasmMsg::Execute {
+                        contract_addr: config.astro_incentives.to_string(),
+                        msg: to_json_binary(&ExecuteAstroMsg::Deposit {
+                            recipient: None,
+                        })?,
+                        funds: vec![CwCoin {
+                            denom: native_asset,
+                            amount: received_asset.amount,
+                        }],
+                    })
+                }
+                AssetInfo::Cw20(cw20_contract_addr) => {
+                    // If the asset is a cw20 token, we need to send it to the astro incentives contract
+                    // using the ExecuteAstroMsg::Receive message
+                    CosmosMsg::Wasm(WasmMsg::Execute {
+                        contract_addr: cw20_contract_addr.to_string(),
+                        msg: to_json_binary(&Cw20ExecuteMsg::Send {
+                            contract: config.astro_incentives.to_string(),
                             amount: received_asset.amount,
-                            msg: to_json_binary(&Cw20Msg::Deposit {
-                                recipient: None,
+                            msg: to_json_binary(&Cw20ReceiveMsg {
+                                sender: env.contract.address.to_string(),
+                                amount: received_asset.amount,
+                                msg: to_json_binary(&Cw20Msg::Deposit {
+                                    recipient: None,
+                                })?,
                             })?,
                         })?,
-                    })?,
-                    funds: vec![],
-                })
-            },
-            _ => {
-                return Err(ContractError::AssetNotWhitelisted(received_asset.info.to_string()));
-            }
-        };
+                        funds: vec![],
+                    })
+                },
+                _ => {
+                    return Err(ContractError::AssetNotWhitelisted(received_asset.info.to_string()));
+                }
+            };
 
-        res = res.add_message(msg);
-    }
+            res = res.add_message(msg);
+        }
 
-    BALANCES.update(
-        deps.storage,
-        (sender.clone(), asset_key.clone()),
-        |balance| -> Result<_, ContractError> {
-            match balance {
-                Some(balance) => Ok(balance + received_asset.amount),
-                None => Ok(received_asset.amount),
-            }
-        },
-    )?;
-    TOTAL_BALANCES.update(
-        deps.storage,
-        asset_key.clone(),
-        |balance| -> Result<_, ContractError> {
-            Ok(balance.unwrap_or(Uint128::zero()) + received_asset.amount)
-        },
-    )?;
+        BALANCES.update(
+            deps.storage,
+            (sender.clone(), asset_key.clone()),
+            |balance| -> Result<_, ContractError> {
+                match balance {
+                    Some(balance) => Ok(balance + received_asset.amount),
+                    None => Ok(received_asset.amount),
+                }
+            },
+        )?;
+        TOTAL_BALANCES.update(
+            deps.storage,
+            asset_key.clone(),
+            |balance| -> Result<_, ContractError> {
+                Ok(balance.unwrap_or(Uint128::zero()) + received_asset.amount)
+            },
+        )?;
 
-    let asset_reward_rate = ASSET_REWARD_RATE
-        .load(deps.storage, asset_key.clone())
-        .unwrap_or(Decimal::zero());
-    USER_ASSET_REWARD_RATE.save(deps.storage, (sender.clone(), asset_key), &asset_reward_rate)?;
+        let asset_reward_rate = ASSET_REWARD_RATE
+            .load(deps.storage, asset_key.clone())
+            .unwrap_or(Decimal::zero());
+        USER_ASSET_REWARD_RATE.save(deps.storage, (sender.clone(), asset_key), &asset_reward_rate)?;
+    }
 
     Ok(res)
 }
 
-fn unstake(deps: DepsMut, info: MessageInfo, asset: Asset) -> Result<Response, ContractError> {
+fn unstake(deps: DepsMut, info: MessageInfo, asset: Asset) -> Result<Response<CustomExecuteMsg>, ContractError> {
     let asset_key = AssetInfoKey::from(asset.info.clone());
     let sender = info.sender.clone();
     if asset.amount.is_zero() {
@@ -310,7 +337,7 @@ fn unstake(deps: DepsMut, info: MessageInfo, asset: Asset) -> Result<Response, C
         },
     )?;
 
-    let msg = asset.transfer_msg(&info.sender)?;
+    let msg = transfer_msg(&asset, &info.sender)?;
 
     Ok(Response::new()
         .add_attributes(vec![
@@ -326,7 +353,7 @@ fn claim_rewards(
     deps: DepsMut,
     info: MessageInfo,
     asset: AssetInfo,
-) -> Result<Response, ContractError> {
+) -> Result<Response<CustomExecuteMsg>, ContractError> {
     let user = info.sender;
     let config = CONFIG.load(deps.storage)?;
     let rewards = _claim_reward(deps.storage, user.clone(), asset.clone())?;
@@ -352,7 +379,7 @@ fn claim_rewards(
             info: AssetInfo::Native(config.reward_denom),
             amount: final_rewards,
         };
-        Ok(response.add_message(rewards_asset.transfer_msg(&user)?))
+        Ok(response.add_message(transfer_msg(&rewards_asset, &user)?))
     } else {
         Ok(response)
     }
@@ -391,14 +418,15 @@ fn alliance_delegate(
     env: Env,
     info: MessageInfo,
     msg: AllianceDelegateMsg,
-) -> Result<Response, ContractError> {
+) -> Result<Response<CustomExecuteMsg>, ContractError> {
     let config = CONFIG.load(deps.storage)?;
     is_controller(&info, &config)?;
+    ensure_alliance_token_denom(&config)?;
     if msg.delegations.is_empty() {
         return Err(ContractError::EmptyDelegation {});
     }
     let mut validators = VALIDATORS.load(deps.storage)?;
-    let mut msgs: Vec<CosmosMsg<Empty>> = vec![];
+    let mut msgs: Vec<CosmosMsg<CustomExecuteMsg>> = vec![];
     for delegation in msg.delegations {
         let delegate_msg = MsgDelegate {
             amount: Some(Coin {
@@ -425,9 +453,10 @@ fn alliance_undelegate(
     env: Env,
     info: MessageInfo,
     msg: AllianceUndelegateMsg,
-) -> Result<Response, ContractError> {
+) -> Result<Response<CustomExecuteMsg>, ContractError> {
     let config = CONFIG.load(deps.storage)?;
     is_controller(&info, &config)?;
+    ensure_alliance_token_denom(&config)?;
     if msg.undelegations.is_empty() {
         return Err(ContractError::EmptyDelegation {});
     }
@@ -457,9 +486,10 @@ fn alliance_redelegate(
     env: Env,
     info: MessageInfo,
     msg: AllianceRedelegateMsg,
-) -> Result<Response, ContractError> {
+) -> Result<Response<CustomExecuteMsg>, ContractError> {
     let config = CONFIG.load(deps.storage)?;
     is_controller(&info, &config)?;
+    ensure_alliance_token_denom(&config)?;
     if msg.redelegations.is_empty() {
         return Err(ContractError::EmptyDelegation {});
     }
@@ -490,8 +520,9 @@ fn alliance_redelegate(
         .add_messages(msgs))
 }
 
-fn update_rewards(deps: DepsMut, env: Env, info: MessageInfo) -> Result<Response, ContractError> {
+fn update_rewards(deps: DepsMut, env: Env, info: MessageInfo) -> Result<Response<CustomExecuteMsg>, ContractError> {
     let config = CONFIG.load(deps.storage)?;
+    ensure_alliance_token_denom(&config)?;
 
     let reward_sent_in_tx: Option<&CwCoin> =
         info.funds.iter().find(|c| c.denom == config.reward_denom);
@@ -508,7 +539,7 @@ fn update_rewards(deps: DepsMut, env: Env, info: MessageInfo) -> Result<Response
     // since contract balance = previous contract balance + sent balance > sent balance
     TEMP_BALANCE.save(deps.storage, &(contract_balance - sent_balance))?;
     let validators = VALIDATORS.load(deps.storage)?;
-    let sub_msgs: Vec<SubMsg> = validators
+    let sub_msgs: Vec<SubMsg<CustomExecuteMsg>> = validators
         .iter()
         .map(|v| {
             let msg = MsgClaimDelegationRewards {
@@ -540,7 +571,7 @@ fn update_reward_callback(
     deps: DepsMut,
     env: Env,
     info: MessageInfo,
-) -> Result<Response, ContractError> {
+) -> Result<Response<CustomExecuteMsg>, ContractError> {
     if info.sender != env.contract.address {
         return Err(ContractError::Unauthorized {});
     }
@@ -612,7 +643,7 @@ fn rebalance_emissions(
     env: Env,
     info: MessageInfo,
     weights: Vec<EmissionsDistribution>,
-) -> Result<Response, ContractError> {
+) -> Result<Response<CustomExecuteMsg>, ContractError> {
     // Allow execution only from the controller account
     let config = CONFIG.load(deps.storage)?;
     is_controller(&info, &config)?;
@@ -639,15 +670,15 @@ fn rebalance_emissions_callback(
     env: Env,
     info: MessageInfo,
     distributions: Vec<EmissionsDistribution>,
-) -> Result<Response, ContractError> {
+) -> Result<Response<CustomExecuteMsg>, ContractError> {
     if info.sender != env.contract.address {
         return Err(ContractError::Unauthorized {});
     }
 
     let total_distribution = distributions
         .iter()
-        .map(|a| a.distribution.to_decimal().unwrap())
-        .fold(Decimal::zero(), |acc, v| acc + v);
+        .map(|a| a.to_decimal())
+        .sum::<Result<Decimal, ContractError>>()?;
     if total_distribution > Decimal::one() {
         return Err(ContractError::InvalidTotalDistribution(total_distribution));
     }
@@ -657,7 +688,7 @@ fn rebalance_emissions_callback(
         let asset_key = AssetInfoKey::from(asset_info.clone());
         WHITELIST.update(deps.storage, asset_key, |current| -> Result<_, ContractError> {
             if let Some(current) = current {
-                Ok(current + distribution.distribution.to_decimal()?)
+                Ok(current + distribution.to_decimal()?)
             } else {
                 Err(ContractError::AssetNotWhitelisted(asset_info.to_string()))
             }
@@ -682,50 +713,14 @@ pub fn reply(
 ) -> Result<Response<CustomExecuteMsg>, ContractError> {
     match reply.id {
         CREATE_REPLY_ID => {
-            let response = reply.result.unwrap();
-            // It works because the response data is a protobuf encoded string that contains the denom in the first slot (similar to the contract instantiation response)
-            let denom = parse_instantiate_response_data(response.data.unwrap().as_slice())
-                .map_err(|_| ContractError::Std(StdError::generic_err("parse error".to_string())))?
-                .contract_address;
-            let total_supply = Uint128::from(1_000_000_000_000_u128);
-            let sub_msg_mint = SubMsg::new(CosmosMsg::Custom(CustomExecuteMsg::Token(
-                TokenExecuteMsg::MintTokens {
-                    denom: denom.clone(),
-                    amount: total_supply,
-                    mint_to_address: env.contract.address.to_string(),
-                },
-            )));
-            CONFIG.update(deps.storage, |mut config| -> Result<_, ContractError> {
-                config.alliance_token_denom = denom.clone();
-                config.alliance_token_supply = total_supply;
-                Ok(config)
-            })?;
-
-            let sub_msg_metadata = SubMsg::new(CosmosMsg::Custom(CustomExecuteMsg::Token(
-                TokenExecuteMsg::SetMetadata {
-                    denom: denom.clone(),
-                    metadata: Metadata {
-                        description: "Staking token for alliance protocol lp hub contract"
-                            .to_string(),
-                        denom_units: vec![DenomUnit {
-                            denom: denom.clone(),
-                            exponent: 0,
-                            aliases: vec![],
-                        }],
-                        base: denom.to_string(),
-                        display: denom.to_string(),
-                        name: "Alliance LP Token".to_string(),
-                        symbol: "ALLIANCE_LP".to_string(),
-                    },
-                },
-            )));
-            Ok(Response::new()
-                .add_attributes(vec![
-                    ("alliance_token_denom", denom),
-                    ("alliance_token_total_supply", total_supply.to_string()),
-                ])
-                .add_submessage(sub_msg_mint)
-                .add_submessage(sub_msg_metadata))
+            let response = reply
+                .result
+                .into_result()
+                .map_err(ContractError::TokenCreationFailed)?;
+            let denom = parse_create_denom_response(&response)
+                .ok_or(ContractError::TokenCreationReplyParse {})?;
+            validate_alliance_token_denom(&env, &denom)?;
+            setup_alliance_token(deps, env, denom)
         }
         CLAIM_REWARD_ERROR_REPLY_ID => {
             Ok(Response::new().add_attributes(vec![("action", "claim_reward_error")]))
@@ -734,6 +729,197 @@ pub fn reply(
     }
 }
 
+// The denom is decoded from the MsgCreateDenomResponse in the reply data,
+// falling back to the create_denom event when the data is missing
+fn parse_create_denom_response(response: &SubMsgResponse) -> Option<String> {
+    response
+        .data
+        .as_ref()
+        .and_then(|data| MsgCreateDenomResponse::decode(data.as_slice()).ok())
+        .map(|res| res.new_token_denom)
+        .filter(|denom| !denom.is_empty())
+        .or_else(|| {
+            response
+                .events
+                .iter()
+                .filter(|event| event.ty == "create_denom")
+                .flat_map(|event| event.attributes.iter())
+                .find(|attr| attr.key == "new_token_denom" && !attr.value.is_empty())
+                .map(|attr| attr.value.clone())
+        })
+}
+
+// Mints the initial supply of the alliance token and sets its metadata
+// once the denom has been created by the token factory
+fn setup_alliance_token(
+    deps: DepsMut,
+    env: Env,
+    denom: String,
+) -> Result<Response<CustomExecuteMsg>, ContractError> {
+    let alliance_token = ALLIANCE_TOKEN_PARAMS
+        .may_load(deps.storage)?
+        .unwrap_or_default();
+    ALLIANCE_TOKEN_PARAMS.remove(deps.storage);
+    let total_supply = alliance_token.initial_supply;
+    let sub_msg_mint = SubMsg::new(CosmosMsg::Custom(CustomExecuteMsg::Token(
+        TokenExecuteMsg::MintTokens {
+            denom: denom.clone(),
+            amount: total_supply,
+            mint_to_address: env.contract.address.to_string(),
+        },
+    )));
+    CONFIG.update(deps.storage, |mut config| -> Result<_, ContractError> {
+        config.alliance_token_denom = denom.clone();
+        config.alliance_token_supply = total_supply;
+        Ok(config)
+    })?;
+
+    let sub_msg_metadata = SubMsg::new(CosmosMsg::Custom(CustomExecuteMsg::Token(
+        TokenExecuteMsg::SetMetadata {
+            denom: denom.clone(),
+            metadata: Metadata {
+                description: alliance_token.description,
+                denom_units: vec![DenomUnit {
+                    denom: denom.clone(),
+                    exponent: 0,
+                    aliases: vec![],
+                }],
+                base: denom.to_string(),
+                display: denom.to_string(),
+                name: alliance_token.name,
+                symbol: alliance_token.symbol,
+            },
+        },
+    )));
+    Ok(Response::new()
+        .add_attributes(vec![
+            ("alliance_token_denom", denom),
+            ("alliance_token_total_supply", total_supply.to_string()),
+        ])
+        .add_submessage(sub_msg_mint)
+        .add_submessage(sub_msg_metadata))
+}
+
+// Recovers the alliance token when the denom was created but could
+// not be stored from the token factory reply
+fn set_alliance_token_denom(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    denom: String,
+) -> Result<Response<CustomExecuteMsg>, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    is_governance(&info, &config)?;
+    if !config.alliance_token_denom.is_empty() {
+        return Err(ContractError::AllianceTokenDenomAlreadySet {});
+    }
+    validate_alliance_token_denom(&env, &denom)?;
+    let res = setup_alliance_token(deps, env, denom)?;
+    Ok(res.add_attribute("action", "set_alliance_token_denom"))
+}
+
+// Creates the alliance token again when the denom was never created
+fn recreate_denom(
+    deps: DepsMut,
+    info: MessageInfo,
+    subdenom: String,
+) -> Result<Response<CustomExecuteMsg>, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    is_governance(&info, &config)?;
+    if !config.alliance_token_denom.is_empty() {
+        return Err(ContractError::AllianceTokenDenomAlreadySet {});
+    }
+    Ok(Response::new()
+        .add_attributes(vec![("action", "recreate_denom"), ("subdenom", &subdenom)])
+        .add_submessage(create_denom_msg(subdenom)))
+}
+
+fn create_denom_msg(subdenom: String) -> SubMsg<CustomExecuteMsg> {
+    SubMsg::reply_on_success(
+        CosmosMsg::Custom(CustomExecuteMsg::Token(TokenExecuteMsg::CreateDenom {
+            subdenom,
+        })),
+        CREATE_REPLY_ID,
+    )
+}
+
+// Token factory denoms created by this contract are prefixed with its address
+fn validate_alliance_token_denom(env: &Env, denom: &str) -> Result<(), ContractError> {
+    let prefix = format!("factory/{}/", env.contract.address);
+    match denom.strip_prefix(&prefix) {
+        Some(subdenom) if !subdenom.is_empty() => Ok(()),
+        _ => Err(ContractError::InvalidAllianceTokenDenom(denom.to_string())),
+    }
+}
+
+// Minted tokens stay in the contract until the controller delegates them
+fn mint_alliance_tokens(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    amount: Uint128,
+) -> Result<Response<CustomExecuteMsg>, ContractError> {
+    let mut config = CONFIG.load(deps.storage)?;
+    is_governance(&info, &config)?;
+    ensure_alliance_token_denom(&config)?;
+    if amount.is_zero() {
+        return Err(ContractError::AmountCannotBeZero {});
+    }
+    config.alliance_token_supply = config.alliance_token_supply.checked_add(amount)?;
+    CONFIG.save(deps.storage, &config)?;
+
+    let msg = CosmosMsg::Custom(CustomExecuteMsg::Token(TokenExecuteMsg::MintTokens {
+        denom: config.alliance_token_denom,
+        amount,
+        mint_to_address: env.contract.address.to_string(),
+    }));
+    Ok(Response::new()
+        .add_attributes(vec![
+            ("action", "mint_alliance_tokens"),
+            ("amount", &amount.to_string()),
+            ("alliance_token_total_supply", &config.alliance_token_supply.to_string()),
+        ])
+        .add_message(msg))
+}
+
+// Only the tokens held by the contract, which are not delegated
+// or being undelegated, can be burned
+fn burn_alliance_tokens(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    amount: Uint128,
+) -> Result<Response<CustomExecuteMsg>, ContractError> {
+    let mut config = CONFIG.load(deps.storage)?;
+    is_governance(&info, &config)?;
+    ensure_alliance_token_denom(&config)?;
+    if amount.is_zero() {
+        return Err(ContractError::AmountCannotBeZero {});
+    }
+    let undelegated = deps
+        .querier
+        .query_balance(env.contract.address.clone(), &config.alliance_token_denom)?
+        .amount;
+    if undelegated < amount {
+        return Err(ContractError::InsufficientBalance {});
+    }
+    config.alliance_token_supply = config.alliance_token_supply.checked_sub(amount)?;
+    CONFIG.save(deps.storage, &config)?;
+
+    let msg = CosmosMsg::Custom(CustomExecuteMsg::Token(TokenExecuteMsg::BurnTokens {
+        denom: config.alliance_token_denom,
+        amount,
+        burn_from_address: env.contract.address.to_string(),
+    }));
+    Ok(Response::new()
+        .add_attributes(vec![
+            ("action", "burn_alliance_tokens"),
+            ("amount", &amount.to_string()),
+            ("alliance_token_total_supply", &config.alliance_token_supply.to_string()),
+        ])
+        .add_message(msg))
+}
+
 // Controller is used to perform administrative operations that deals with delegating the virtual
 // tokens to the expected validators
 fn is_controller(info: &MessageInfo, config: &Config) -> Result<(), ContractError> {
@@ -750,3 +936,34 @@ fn is_governance(info: &MessageInfo, config: &Config) -> Result<(), ContractErro
     }
     Ok(())
 }
+
+// Alliance messages cannot be sent until the alliance token denom is created
+fn ensure_alliance_token_denom(config: &Config) -> Result<(), ContractError> {
+    if config.alliance_token_denom.is_empty() {
+        return Err(ContractError::AllianceTokenDenomNotSet {});
+    }
+    Ok(())
+}
+
+// Staked assets and rewards are sent with the custom responses, so the
+// transfers are built here instead of with cw_asset
+fn transfer_msg(asset: &Asset, to: &Addr) -> Result<CosmosMsg<CustomExecuteMsg>, ContractError> {
+    match &asset.info {
+        AssetInfo::Native(denom) => Ok(CosmosMsg::Bank(BankMsg::Send {
+            to_address: to.to_string(),
+            amount: vec![CwCoin {
+                denom: denom.clone(),
+                amount: asset.amount,
+            }],
+        })),
+        AssetInfo::Cw20(contract_addr) => Ok(CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: contract_addr.to_string(),
+            msg: to_json_binary(&Cw20ExecuteMsg::Transfer {
+                recipient: to.to_string(),
+                amount: asset.amount,
+            })?,
+            funds: vec![],
+        })),
+        _ => Err(ContractError::AssetNotWhitelisted(asset.info.to_string())),
+    }
+}
@@ -28,6 +28,26 @@ pub struct InstantiateMsg {
     pub fee_collector_address: String,
     pub astro_incentives_address: String,
     pub reward_denom: String,
+    pub alliance_token: Option<AllianceTokenParams>,
+}
+
+#[cw_serde]
+pub struct AllianceTokenParams {
+    pub initial_supply: Uint128,
+    pub name: String,
+    pub symbol: String,
+    pub description: String,
+}
+
+impl Default for AllianceTokenParams {
+    fn default() -> Self {
+        AllianceTokenParams {
+            initial_supply: Uint128::new(1_000_000_000_000),
+            name: "Alliance LP Token".to_string(),
+            symbol: "ALLIANCE_LP".to_string(),
+            description: "Staking token for alliance protocol lp hub contract".to_string(),
+        }
+    }
 }
 
 #[cw_serde]
@@ -35,7 +55,8 @@ pub enum ExecuteMsg {
     // Both functions are used to stake,
     // - Stake is used for CosmosSDK::Coin
     // - Receive is used for CW20 tokens
-    Stake {},
+    // The funds are credited to the recipient, the sender by default
+    Stake { recipient: Option<String> },
     Receive(Cw20ReceiveMsg),
 
     // Used to do the other operations
@@ -53,6 +74,16 @@ pub enum ExecuteMsg {
     AllianceRedelegate(AllianceRedelegateMsg),
     RebalanceEmissions(Vec<EmissionsDistribution>),
     RebalanceEmissionsCallback(Vec<EmissionsDistribution>),
+    MintAllianceTokens { amount: Uint128 },
+    BurnAllianceTokens { amount: Uint128 },
+    SetAllianceTokenDenom { denom: String },
+    RecreateDenom { subdenom: String },
+}
+
+// Message expected in the msg field of Cw20ReceiveMsg
+#[cw_serde]
+pub enum Cw20HookMsg {
+    Stake { recipient: Option<String> },
 }
 
 #[cw_serde]
@@ -1,10 +1,12 @@
-use crate::models::Config;
+use crate::models::{AllianceTokenParams, Config};
 use cosmwasm_std::{Addr, Decimal, Uint128};
 use cw_asset::AssetInfoKey;
 use cw_storage_plus::{Item, Map};
 use std::collections::HashSet;
 
 pub const CONFIG: Item<Config> = Item::new("config");
+// Parameters of the alliance token until its denom is created
+pub const ALLIANCE_TOKEN_PARAMS: Item<AllianceTokenParams> = Item::new("alliance_token_params");
 pub const WHITELIST: Map<AssetInfoKey, Decimal> = Map::new("whitelist");
 
 pub const BALANCES: Map<(Addr, AssetInfoKey), Uint128> = Map::new("balances");
@@ -2,14 +2,19 @@ use crate::contract::execute;
 use crate::models::{Config, ExecuteMsg};
 use crate::state::{CONFIG, VALIDATORS};
 use crate::tests::helpers::{
-    alliance_delegate, alliance_redelegate, alliance_undelegate, setup_contract,
+    alliance_delegate, alliance_redelegate, alliance_undelegate, set_alliance_asset,
+    setup_contract, DENOM,
 };
 use alliance_protocol::alliance_protocol::{
-    AllianceDelegateMsg, AllianceDelegation, AllianceUndelegateMsg,
+    AllianceDelegateMsg, AllianceDelegation, AllianceRedelegateMsg, AllianceRedelegation,
+    AllianceUndelegateMsg,
 };
 use alliance_protocol::error::ContractError;
-use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info};
-use cosmwasm_std::{Binary, CosmosMsg, StdResult, SubMsg, Uint128};
+use alliance_protocol::token_factory::{CustomExecuteMsg, TokenExecuteMsg};
+use cosmwasm_std::testing::{
+    mock_dependencies, mock_dependencies_with_balance, mock_env, mock_info,
+};
+use cosmwasm_std::{coin, Binary, CosmosMsg, Response, StdResult, SubMsg, Uint128};
 use std::collections::HashSet;
 use terra_proto_rs::alliance::alliance::{MsgDelegate, MsgRedelegate};
 use terra_proto_rs::cosmos::base::v1beta1::Coin;
@@ -288,3 +293,155 @@ fn test_alliance_redelegate() {
         HashSet::from(["validator2".to_string(), "validator3".to_string()])
     );
 }
+
+#[test]
+fn test_alliance_messages_without_denom() {
+    let mut deps = mock_dependencies();
+    setup_contract(deps.as_mut());
+
+    let err = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("controller", &[]),
+        ExecuteMsg::AllianceDelegate(AllianceDelegateMsg {
+            delegations: vec![AllianceDelegation {
+                validator: "validator1".to_string(),
+                amount: Uint128::new(100),
+            }],
+        }),
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::AllianceTokenDenomNotSet {});
+
+    let err = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("controller", &[]),
+        ExecuteMsg::AllianceUndelegate(AllianceUndelegateMsg {
+            undelegations: vec![AllianceDelegation {
+                validator: "validator1".to_string(),
+                amount: Uint128::new(100),
+            }],
+        }),
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::AllianceTokenDenomNotSet {});
+
+    let err = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("controller", &[]),
+        ExecuteMsg::AllianceRedelegate(AllianceRedelegateMsg {
+            redelegations: vec![AllianceRedelegation {
+                src_validator: "validator1".to_string(),
+                dst_validator: "validator2".to_string(),
+                amount: Uint128::new(100),
+            }],
+        }),
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::AllianceTokenDenomNotSet {});
+
+    let err = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("user", &[]),
+        ExecuteMsg::UpdateRewards {},
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::AllianceTokenDenomNotSet {});
+}
+
+#[test]
+fn test_mint_alliance_tokens() {
+    let mut deps = mock_dependencies();
+    setup_contract(deps.as_mut());
+
+    let msg = ExecuteMsg::MintAllianceTokens {
+        amount: Uint128::new(500),
+    };
+    let err = execute(deps.as_mut(), mock_env(), mock_info("gov", &[]), msg.clone()).unwrap_err();
+    assert_eq!(err, ContractError::AllianceTokenDenomNotSet {});
+
+    set_alliance_asset(deps.as_mut());
+    let err = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("controller", &[]),
+        msg.clone(),
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::Unauthorized {});
+
+    let res = execute(deps.as_mut(), mock_env(), mock_info("gov", &[]), msg).unwrap();
+    assert_eq!(
+        res,
+        Response::new()
+            .add_attributes(vec![
+                ("action", "mint_alliance_tokens"),
+                ("amount", "500"),
+                ("alliance_token_total_supply", "1000000000500"),
+            ])
+            .add_message(CosmosMsg::Custom(CustomExecuteMsg::Token(
+                TokenExecuteMsg::MintTokens {
+                    denom: DENOM.to_string(),
+                    amount: Uint128::new(500),
+                    mint_to_address: "cosmos2contract".to_string(),
+                }
+            )))
+    );
+    let config = CONFIG.load(deps.as_ref().storage).unwrap();
+    assert_eq!(config.alliance_token_supply, Uint128::new(1000000000500));
+}
+
+#[test]
+fn test_burn_alliance_tokens() {
+    // Only 1000 tokens are not delegated
+    let mut deps = mock_dependencies_with_balance(&[coin(1000, DENOM)]);
+    setup_contract(deps.as_mut());
+    set_alliance_asset(deps.as_mut());
+
+    let err = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("gov", &[]),
+        ExecuteMsg::BurnAllianceTokens {
+            amount: Uint128::new(1001),
+        },
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::InsufficientBalance {});
+
+    let err = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("gov", &[]),
+        ExecuteMsg::BurnAllianceTokens {
+            amount: Uint128::zero(),
+        },
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::AmountCannotBeZero {});
+
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("gov", &[]),
+        ExecuteMsg::BurnAllianceTokens {
+            amount: Uint128::new(1000),
+        },
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(CosmosMsg::Custom(CustomExecuteMsg::Token(
+            TokenExecuteMsg::BurnTokens {
+                denom: DENOM.to_string(),
+                amount: Uint128::new(1000),
+                burn_from_address: "cosmos2contract".to_string(),
+            }
+        )))]
+    );
+    let config = CONFIG.load(deps.as_ref().storage).unwrap();
+    assert_eq!(config.alliance_token_supply, Uint128::new(999999999000));
+}
@@ -1,6 +1,6 @@
 use crate::contract::{execute, instantiate};
 use crate::models::{
-    AllPendingRewardsQuery, AssetQuery, Config, ExecuteMsg, InstantiateMsg, PendingRewardsRes,
+    AllPendingRewardsQuery, AssetQuery, Config, Cw20HookMsg, ExecuteMsg, InstantiateMsg, PendingRewardsRes,
     QueryMsg, StakedBalanceRes, ModifyAsset,
 };
 use crate::query::query;
@@ -11,7 +11,7 @@ use alliance_protocol::alliance_protocol::{
 };
 use alliance_protocol::token_factory::CustomExecuteMsg;
 use cosmwasm_std::testing::{mock_env, mock_info};
-use cosmwasm_std::{coin, from_json, Deps, DepsMut, Response, StdResult, Uint128, Binary, Addr};
+use cosmwasm_std::{coin, from_json, to_json_binary, Deps, DepsMut, Response, StdResult, Uint128, Addr};
 use cw20::Cw20ReceiveMsg;
 use cw_asset::{Asset, AssetInfo};
 
@@ -27,6 +27,7 @@ pub fn setup_contract(deps: DepsMut) -> Response<CustomExecuteMsg> {
         astro_incentives_address : "astro_incentives".to_string(),
         controller: "controller".to_string(),
         reward_denom: "uluna".to_string(),
+        alliance_token: None,
     };
     instantiate(deps, env, info, init_msg).unwrap()
 }
@@ -43,7 +44,7 @@ pub fn set_alliance_asset(deps: DepsMut) {
         .unwrap();
 }
 
-pub fn modify_asset(deps: DepsMut, assets: Vec<ModifyAsset>) -> Response {
+pub fn modify_asset(deps: DepsMut, assets: Vec<ModifyAsset>) -> Response<CustomExecuteMsg> {
     let info = mock_info("gov", &[]);
     let env = mock_env();
 
@@ -52,27 +53,27 @@ pub fn modify_asset(deps: DepsMut, assets: Vec<ModifyAsset>) -> Response {
 }
 
 
-pub fn stake(deps: DepsMut, user: &str, amount: u128, denom: &str) -> Response {
+pub fn stake(deps: DepsMut, user: &str, amount: u128, denom: &str) -> Response<CustomExecuteMsg> {
     let info = mock_info(user, &[coin(amount, denom)]);
     let env = mock_env();
-    let msg = ExecuteMsg::Stake {};
+    let msg = ExecuteMsg::Stake { recipient: None };
     execute(deps, env, info, msg).unwrap()
 }
 
 
-pub fn stake_cw20(deps: DepsMut, user: &str, amount: u128, denom: &str) -> Response {
+pub fn stake_cw20(deps: DepsMut, user: &str, amount: u128, denom: &str) -> Response<CustomExecuteMsg> {
     let mut info = mock_info(user, &[]);
     let env = mock_env();
     let msg = ExecuteMsg::Receive(Cw20ReceiveMsg{
         sender: String::from(user),
         amount: Uint128::new(amount),
-        msg: Binary::default(),
+        msg: to_json_binary(&Cw20HookMsg::Stake { recipient: None }).unwrap(),
     });
     info.sender = Addr::unchecked(denom.to_owned());
     execute(deps, env, info, msg).unwrap()
 }
 
-pub fn unstake(deps: DepsMut, user: &str, asset: Asset) -> Response {
+pub fn unstake(deps: DepsMut, user: &str, asset: Asset) -> Response<CustomExecuteMsg> {
     let info = mock_info(user, &[]);
     let env = mock_env();
     let msg = ExecuteMsg::Unstake(asset);
@@ -81,7 +82,7 @@ pub fn unstake(deps: DepsMut, user: &str, asset: Asset) -> Response {
     res.unwrap()
 }
 
-pub fn alliance_delegate(deps: DepsMut, delegations: Vec<(&str, u128)>) -> Response {
+pub fn alliance_delegate(deps: DepsMut, delegations: Vec<(&str, u128)>) -> Response<CustomExecuteMsg> {
     let info = mock_info("controller", &[]);
     let env = mock_env();
     let delegations: Vec<AllianceDelegation> = delegations
@@ -95,7 +96,7 @@ pub fn alliance_delegate(deps: DepsMut, delegations: Vec<(&str, u128)>) -> Respo
     execute(deps, env, info, msg).unwrap()
 }
 
-pub fn alliance_undelegate(deps: DepsMut, delegations: Vec<(&str, u128)>) -> Response {
+pub fn alliance_undelegate(deps: DepsMut, delegations: Vec<(&str, u128)>) -> Response<CustomExecuteMsg> {
     let info = mock_info("controller", &[]);
     let env = mock_env();
     let delegations: Vec<AllianceDelegation> = delegations
@@ -111,7 +112,7 @@ pub fn alliance_undelegate(deps: DepsMut, delegations: Vec<(&str, u128)>) -> Res
     execute(deps, env, info, msg).unwrap()
 }
 
-pub fn alliance_redelegate(deps: DepsMut, redelegations: Vec<(&str, &str, u128)>) -> Response {
+pub fn alliance_redelegate(deps: DepsMut, redelegations: Vec<(&str, &str, u128)>) -> Response<CustomExecuteMsg> {
     let info = mock_info("controller", &[]);
     let env = mock_env();
     let redelegations: Vec<AllianceRedelegation> = redelegations
@@ -126,7 +127,7 @@ pub fn alliance_redelegate(deps: DepsMut, redelegations: Vec<(&str, &str, u128)>
     execute(deps, env, info, msg).unwrap()
 }
 
-pub fn claim_rewards(deps: DepsMut, user: &str, denom: &str) -> Response {
+pub fn claim_rewards(deps: DepsMut, user: &str, denom: &str) -> Response<CustomExecuteMsg> {
     let info = mock_info(user, &[]);
     let env = mock_env();
     let msg = ExecuteMsg::ClaimRewards(AssetInfo::Native(denom.to_string()));
@@ -1,12 +1,14 @@
-use crate::contract::reply;
-use crate::models::{Config, QueryMsg};
+use crate::contract::{execute, instantiate, reply};
+use crate::models::{AllianceTokenParams, Config, ExecuteMsg, InstantiateMsg, QueryMsg};
 use crate::query::query;
+use crate::state::CONFIG;
 use crate::tests::helpers::setup_contract;
+use alliance_protocol::error::ContractError;
 use alliance_protocol::token_factory::{CustomExecuteMsg, DenomUnit, Metadata, TokenExecuteMsg};
-use cosmwasm_std::testing::{mock_dependencies, mock_env};
+use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info};
 use cosmwasm_std::{
-    from_json, Addr, Binary, CosmosMsg, Reply, Response, SubMsg, SubMsgResponse, SubMsgResult,
-    Uint128,
+    attr, from_json, Addr, Binary, CosmosMsg, Reply, Response, SubMsg, SubMsgResponse,
+    SubMsgResult, Uint128,
 };
 use terra_proto_rs::traits::MessageExt;
 
@@ -117,3 +119,210 @@ fn test_reply_create_token() {
         }
     );
 }
+
+#[test]
+fn test_reply_create_token_without_data() {
+    let mut deps = mock_dependencies();
+    setup_contract(deps.as_mut());
+
+    let msg = Reply {
+        id: 1,
+        result: SubMsgResult::Ok(SubMsgResponse {
+            events: vec![],
+            data: None,
+        }),
+    };
+    let err = reply(deps.as_mut(), mock_env(), msg).unwrap_err();
+    assert_eq!(err, ContractError::TokenCreationReplyParse {});
+}
+
+#[test]
+fn test_reply_create_token_failed() {
+    let mut deps = mock_dependencies();
+    setup_contract(deps.as_mut());
+
+    let msg = Reply {
+        id: 1,
+        result: SubMsgResult::Err("denom already exists".to_string()),
+    };
+    let err = reply(deps.as_mut(), mock_env(), msg).unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::TokenCreationFailed("denom already exists".to_string())
+    );
+}
+
+#[test]
+fn test_set_alliance_token_denom() {
+    let mut deps = mock_dependencies();
+    setup_contract(deps.as_mut());
+
+    let msg = ExecuteMsg::SetAllianceTokenDenom {
+        denom: "factory/cosmos2contract/ualliancelp".to_string(),
+    };
+    let err = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("controller", &[]),
+        msg.clone(),
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::Unauthorized {});
+
+    let err = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("gov", &[]),
+        ExecuteMsg::SetAllianceTokenDenom {
+            denom: "factory/other/ualliancelp".to_string(),
+        },
+    )
+    .unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::InvalidAllianceTokenDenom("factory/other/ualliancelp".to_string())
+    );
+
+    let res = execute(deps.as_mut(), mock_env(), mock_info("gov", &[]), msg.clone()).unwrap();
+    assert_eq!(res.attributes[2], attr("action", "set_alliance_token_denom"));
+    assert_eq!(
+        res.messages[0],
+        SubMsg::new(CosmosMsg::Custom(CustomExecuteMsg::Token(
+            TokenExecuteMsg::MintTokens {
+                amount: Uint128::from(1000000000000u128),
+                denom: "factory/cosmos2contract/ualliancelp".to_string(),
+                mint_to_address: "cosmos2contract".to_string(),
+            },
+        )))
+    );
+    let config = CONFIG.load(deps.as_ref().storage).unwrap();
+    assert_eq!(config.alliance_token_denom, "factory/cosmos2contract/ualliancelp");
+    assert_eq!(config.alliance_token_supply, Uint128::new(1000000000000));
+
+    let err = execute(deps.as_mut(), mock_env(), mock_info("gov", &[]), msg).unwrap_err();
+    assert_eq!(err, ContractError::AllianceTokenDenomAlreadySet {});
+}
+
+#[test]
+fn test_recreate_denom() {
+    let mut deps = mock_dependencies();
+    setup_contract(deps.as_mut());
+
+    let msg = ExecuteMsg::RecreateDenom {
+        subdenom: "ualliancelp2".to_string(),
+    };
+    let err = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("controller", &[]),
+        msg.clone(),
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::Unauthorized {});
+
+    let res = execute(deps.as_mut(), mock_env(), mock_info("gov", &[]), msg.clone()).unwrap();
+    assert_eq!(
+        res,
+        Response::default()
+            .add_attributes(vec![("action", "recreate_denom"), ("subdenom", "ualliancelp2")])
+            .add_submessage(SubMsg::reply_on_success(
+                CosmosMsg::Custom(CustomExecuteMsg::Token(TokenExecuteMsg::CreateDenom {
+                    subdenom: "ualliancelp2".to_string(),
+                })),
+                1
+            ))
+    );
+
+    let msg_reply = Reply {
+        id: 1,
+        result: SubMsgResult::Ok(SubMsgResponse {
+            events: vec![],
+            data: Some(Binary::from(
+                String::from("factory/cosmos2contract/ualliancelp2")
+                    .to_bytes()
+                    .unwrap(),
+            )),
+        }),
+    };
+    reply(deps.as_mut(), mock_env(), msg_reply).unwrap();
+    let err = execute(deps.as_mut(), mock_env(), mock_info("gov", &[]), msg).unwrap_err();
+    assert_eq!(err, ContractError::AllianceTokenDenomAlreadySet {});
+}
+
+#[test]
+fn test_reply_create_token_with_custom_params() {
+    let mut deps = mock_dependencies();
+    let msg = InstantiateMsg {
+        governance: "gov".to_string(),
+        fee_collector_address: "collector_address".to_string(),
+        astro_incentives_address: "astro_incentives".to_string(),
+        controller: "controller".to_string(),
+        reward_denom: "uluna".to_string(),
+        alliance_token: Some(AllianceTokenParams {
+            initial_supply: Uint128::zero(),
+            name: "Custom LP Token".to_string(),
+            symbol: "CUSTOM_LP".to_string(),
+            description: "Custom lp staking token".to_string(),
+        }),
+    };
+    let err = instantiate(deps.as_mut(), mock_env(), mock_info("admin", &[]), msg.clone()).unwrap_err();
+    assert_eq!(err, ContractError::AmountCannotBeZero {});
+
+    instantiate(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("admin", &[]),
+        InstantiateMsg {
+            alliance_token: Some(AllianceTokenParams {
+                initial_supply: Uint128::new(5_000_000),
+                ..msg.alliance_token.unwrap()
+            }),
+            ..msg
+        },
+    )
+    .unwrap();
+
+    let msg = Reply {
+        id: 1,
+        result: SubMsgResult::Ok(SubMsgResponse {
+            events: vec![],
+            data: Some(Binary::from(
+                String::from("factory/cosmos2contract/ualliancelp")
+                    .to_bytes()
+                    .unwrap(),
+            )),
+        }),
+    };
+    let res = reply(deps.as_mut(), mock_env(), msg).unwrap();
+    assert_eq!(
+        res.messages,
+        vec![
+            SubMsg::new(CosmosMsg::Custom(CustomExecuteMsg::Token(
+                TokenExecuteMsg::MintTokens {
+                    amount: Uint128::new(5_000_000),
+                    denom: "factory/cosmos2contract/ualliancelp".to_string(),
+                    mint_to_address: "cosmos2contract".to_string(),
+                },
+            ))),
+            SubMsg::new(CosmosMsg::Custom(CustomExecuteMsg::Token(
+                TokenExecuteMsg::SetMetadata {
+                    denom: "factory/cosmos2contract/ualliancelp".to_string(),
+                    metadata: Metadata {
+                        description: "Custom lp staking token".to_string(),
+                        denom_units: vec![DenomUnit {
+                            denom: "factory/cosmos2contract/ualliancelp".to_string(),
+                            exponent: 0,
+                            aliases: vec![],
+                        }],
+                        base: "factory/cosmos2contract/ualliancelp".to_string(),
+                        display: "factory/cosmos2contract/ualliancelp".to_string(),
+                        name: "Custom LP Token".to_string(),
+                        symbol: "CUSTOM_LP".to_string(),
+                    },
+                },
+            ))),
+        ]
+    );
+    let config = CONFIG.load(deps.as_ref().storage).unwrap();
+    assert_eq!(config.alliance_token_supply, Uint128::new(5_000_000));
+}
@@ -5,6 +5,9 @@ use crate::tests::helpers::{
     claim_rewards, query_all_rewards, query_rewards, set_alliance_asset, setup_contract, stake,
     unstake, modify_asset, DENOM,
 };
+use alliance_protocol::alliance_oracle_types::EmissionsDistribution;
+use alliance_protocol::error::ContractError;
+use alliance_protocol::signed_decimal::SignedDecimal;
 use cosmwasm_std::testing::{mock_dependencies_with_balance, mock_env, mock_info};
 use cosmwasm_std::{
     coin, coins, to_json_binary, Addr, BankMsg, Binary, CosmosMsg, Decimal, Response, SubMsg,
@@ -12,6 +15,7 @@ use cosmwasm_std::{
 };
 use cw_asset::{AssetInfo, AssetInfoKey, Asset};
 use std::collections::HashSet;
+use std::str::FromStr;
 use terra_proto_rs::alliance::alliance::MsgClaimDelegationRewards;
 use terra_proto_rs::traits::Message;
 
@@ -570,3 +574,34 @@ fn claim_rewards_after_rebalancing_emissions() {
     let rewards = query_rewards(deps.as_ref(), "user2", "bWHALE");
     assert_eq!(rewards.rewards, Uint128::new(500000));
 }
+
+#[test]
+fn rebalance_emissions_with_negative_distribution() {
+    let mut deps = mock_dependencies_with_balance(&[coin(2000000, "uluna")]);
+    setup_contract(deps.as_mut());
+    set_alliance_asset(deps.as_mut());
+    modify_asset(
+        deps.as_mut(),
+        Vec::from([ModifyAsset {
+            asset_info: AssetInfo::Native("aWHALE".to_string()),
+            delete: false,
+        }]),
+    );
+
+    let err = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("cosmos2contract", &[]),
+        ExecuteMsg::RebalanceEmissionsCallback(vec![EmissionsDistribution {
+            denom: "aWHALE".to_string(),
+            distribution: SignedDecimal::from_str("-0.5").unwrap(),
+        }]),
+    )
+    .unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::NegativeDistribution {
+            denom: "aWHALE".to_string()
+        }
+    );
+}
@@ -1,6 +1,6 @@
 use crate::astro_models::{ExecuteAstroMsg, Cw20Msg};
 use crate::contract::execute;
-use crate::models::{ExecuteMsg, ModifyAsset, StakedBalanceRes};
+use crate::models::{Cw20HookMsg, ExecuteMsg, ModifyAsset, StakedBalanceRes};
 use crate::state::{BALANCES, TOTAL_BALANCES};
 use crate::tests::helpers::{
     modify_asset, query_all_staked_balances, setup_contract, stake, stake_cw20, unstake,
@@ -9,7 +9,7 @@ use crate::tests::mock_querier::mock_dependencies as astro_mock_dependencies;
 use alliance_protocol::error::ContractError;
 use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info};
 use cosmwasm_std::{
-    coin, to_json_binary, Addr, BankMsg, Coin, CosmosMsg, Response, Uint128, WasmMsg,
+    coin, to_json_binary, Addr, BankMsg, Binary, Coin, CosmosMsg, Response, Uint128, WasmMsg,
 };
 use cw20::{Cw20ReceiveMsg, Cw20ExecuteMsg};
 use cw_asset::{Asset, AssetInfo, AssetInfoKey};
@@ -134,6 +134,71 @@ fn test_stake_astro_token() {
 
 }
 
+#[test]
+fn test_stake_multiple_assets() {
+    let mut deps = mock_dependencies();
+    setup_contract(deps.as_mut());
+    modify_asset(
+        deps.as_mut(),
+        vec![
+            ModifyAsset {
+                asset_info: AssetInfo::Native("asset1".to_string()),
+                delete: false,
+            },
+            ModifyAsset {
+                asset_info: AssetInfo::Native("asset2".to_string()),
+                delete: false,
+            },
+        ],
+    );
+
+    // Nothing is staked when one of the assets is not whitelisted
+    let err = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("user1", &[coin(100, "asset1"), coin(100, "asset3")]),
+        ExecuteMsg::Stake { recipient: None },
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::AssetNotWhitelisted("native:asset3".to_string()));
+    assert!(!TOTAL_BALANCES.has(
+        deps.as_ref().storage,
+        AssetInfoKey::from(AssetInfo::Native("asset1".to_string())),
+    ));
+
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("user1", &[coin(100, "asset1"), coin(50, "asset2")]),
+        ExecuteMsg::Stake { recipient: None },
+    )
+    .unwrap();
+    assert_eq!(
+        res,
+        Response::default().add_attributes(vec![
+            ("action", "stake"),
+            ("user", "user1"),
+            ("asset", "native:asset1"),
+            ("amount", "100"),
+            ("asset", "native:asset2"),
+            ("amount", "50"),
+        ])
+    );
+    assert_eq!(
+        query_all_staked_balances(deps.as_ref()),
+        vec![
+            StakedBalanceRes {
+                asset: AssetInfo::Native("asset1".to_string()),
+                balance: Uint128::new(100),
+            },
+            StakedBalanceRes {
+                asset: AssetInfo::Native("asset2".to_string()),
+                balance: Uint128::new(50),
+            },
+        ]
+    );
+}
+
 #[test]
 fn test_stake_cw20() {
     let mut deps = mock_dependencies();
@@ -208,6 +273,151 @@ fn test_stake_cw20() {
     );
 }
 
+#[test]
+fn test_stake_cw20_invalid_hook() {
+    let mut deps = mock_dependencies();
+    setup_contract(deps.as_mut());
+    modify_asset(
+        deps.as_mut(),
+        vec![ModifyAsset {
+            asset_info: AssetInfo::Cw20(Addr::unchecked("cw20_asset")),
+            delete: false,
+        }],
+    );
+
+    let receive = |msg: Binary| {
+        ExecuteMsg::Receive(Cw20ReceiveMsg {
+            sender: "user1".to_string(),
+            amount: Uint128::new(100),
+            msg,
+        })
+    };
+    let err = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("cw20_asset", &[]),
+        receive(Binary::default()),
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::InvalidCw20HookMsg("empty payload".to_string()));
+
+    let err = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("cw20_asset", &[]),
+        receive(to_json_binary(&ExecuteMsg::UpdateRewards {}).unwrap()),
+    )
+    .unwrap_err();
+    assert!(matches!(err, ContractError::InvalidCw20HookMsg(_)));
+
+    let err = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("cw20_asset", &[]),
+        ExecuteMsg::Receive(Cw20ReceiveMsg {
+            sender: "user1".to_string(),
+            amount: Uint128::zero(),
+            msg: to_json_binary(&Cw20HookMsg::Stake { recipient: None }).unwrap(),
+        }),
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::AmountCannotBeZero {});
+
+    // Tokens of a contract that is not whitelisted are rejected
+    let err = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("fake_cw20", &[]),
+        receive(to_json_binary(&Cw20HookMsg::Stake { recipient: None }).unwrap()),
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::AssetNotWhitelisted("cw20:fake_cw20".to_string()));
+}
+
+#[test]
+fn test_stake_on_behalf() {
+    let mut deps = mock_dependencies();
+    setup_contract(deps.as_mut());
+    modify_asset(
+        deps.as_mut(),
+        vec![
+            ModifyAsset {
+                asset_info: AssetInfo::Native("native_asset".to_string()),
+                delete: false,
+            },
+            ModifyAsset {
+                asset_info: AssetInfo::Cw20(Addr::unchecked("cw20_asset")),
+                delete: false,
+            },
+        ],
+    );
+
+    let err = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("vault", &[coin(100, "native_asset")]),
+        ExecuteMsg::Stake { recipient: Some("".to_string()) },
+    )
+    .unwrap_err();
+    assert!(matches!(err, ContractError::Std(_)));
+
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("vault", &[coin(100, "native_asset")]),
+        ExecuteMsg::Stake { recipient: Some("user1".to_string()) },
+    )
+    .unwrap();
+    assert_eq!(
+        res,
+        Response::default().add_attributes(vec![
+            ("action", "stake"),
+            ("user", "user1"),
+            ("asset", "native:native_asset"),
+            ("amount", "100"),
+        ])
+    );
+
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("cw20_asset", &[]),
+        ExecuteMsg::Receive(Cw20ReceiveMsg {
+            sender: "vault".to_string(),
+            amount: Uint128::new(50),
+            msg: to_json_binary(&Cw20HookMsg::Stake { recipient: Some("user1".to_string()) }).unwrap(),
+        }),
+    )
+    .unwrap();
+    assert_eq!(
+        res,
+        Response::default().add_attributes(vec![
+            ("action", "stake"),
+            ("user", "user1"),
+            ("asset", "cw20:cw20_asset"),
+            ("amount", "50"),
+        ])
+    );
+
+    let balance = BALANCES
+        .load(
+            deps.as_ref().storage,
+            (
+                Addr::unchecked("user1"),
+                AssetInfoKey::from(AssetInfo::Cw20(Addr::unchecked("cw20_asset"))),
+            ),
+        )
+        .unwrap();
+    assert_eq!(balance, Uint128::new(50));
+    assert!(!BALANCES.has(
+        deps.as_ref().storage,
+        (
+            Addr::unchecked("vault"),
+            AssetInfoKey::from(AssetInfo::Native("native_asset".to_string())),
+        ),
+    ));
+}
+
 #[test]
 fn test_stake_astro_token_cw20() {
     let mut deps = astro_mock_dependencies(&[Coin::new(1000, "token")]);
@@ -3,20 +3,18 @@ use std::env;
 
 use alliance_protocol::{
     alliance_oracle_types::{
-        AssetStaked, ChainId, ChainInfo, ChainsInfo, Config, EmissionsDistribution, ExecuteMsg,
-        Expire, InstantiateMsg, MigrateMsg, QueryMsg,
+        AssetStaked, ChainId, ChainInfo, ChainsInfo, Config, EmissionsStrategy, ExecuteMsg, Expire,
+        InstantiateMsg, MigrateMsg, QueryMsg, StaleChainsPolicy,
     },
     error::ContractError,
-    signed_decimal::{Sign, SignedDecimal},
 };
 #[cfg(not(feature = "library"))]
 use cosmwasm_std::entry_point;
-use cosmwasm_std::{
-    to_json_binary, Binary, Decimal, Deps, DepsMut, Env, MessageInfo, Response, StdError, StdResult,
-};
+use cosmwasm_std::{to_json_binary, Binary, Deps, DepsMut, Env, MessageInfo, Response, StdResult};
 use cw2::set_contract_version;
 
-use crate::state::{CHAINS_INFO, CONFIG, LUNA_INFO};
+use crate::emissions;
+use crate::state::{CHAINS_INFO, CONFIG, LEGACY_CONFIG, LUNA_INFO};
 use crate::utils;
 
 // version info for migration info
@@ -24,7 +22,37 @@ const CONTRACT_NAME: &str = "crates.io:terra-alliance-oracle";
 const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION");
 
 #[cfg_attr(not(feature = "library"), entry_point)]
-pub fn migrate(_deps: DepsMut, _env: Env, _msg: MigrateMsg) -> StdResult<Response> {
+pub fn migrate(deps: DepsMut, _env: Env, msg: MigrateMsg) -> Result<Response, ContractError> {
+    let governance_addr = msg
+        .governance_addr
+        .map(|addr| deps.api.addr_validate(&addr))
+        .transpose()?;
+
+    let mut config = match CONFIG.load(deps.storage) {
+        Ok(config) => config,
+        // Split the single data expiry of the previous versions into
+        // the LUNA price and the chains info expiries and set the
+        // governance which did not exist in the previous versions.
+        Err(_) => {
+            let legacy_config = LEGACY_CONFIG.load(deps.storage)?;
+            Config {
+                luna_price_expiry_seconds: legacy_config.data_expiry_seconds,
+                chains_info_expiry_seconds: legacy_config.data_expiry_seconds,
+                stale_chains_policy: StaleChainsPolicy::default(),
+                governance_addr: governance_addr
+                    .clone()
+                    .ok_or(ContractError::MissingGovernanceAddr {})?,
+                controller_addr: legacy_config.controller_addr,
+                emissions_strategy: legacy_config.emissions_strategy,
+            }
+        }
+    };
+    if let Some(governance_addr) = governance_addr {
+        config.governance_addr = governance_addr;
+    }
+    CONFIG.save(deps.storage, &config)?;
+    set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
+
     Ok(Response::default())
 }
 
@@ -36,19 +64,35 @@ pub fn instantiate(
     msg: InstantiateMsg,
 ) -> Result<Response, ContractError> {
     set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
+    let governance_addr = deps.api.addr_validate(&msg.governance_addr)?;
     let controller_addr = deps.api.addr_validate(&msg.controller_addr)?;
+    let emissions_strategy = msg.emissions_strategy.unwrap_or_default();
+    emissions::validate_emissions_strategy(&emissions_strategy)?;
+    let stale_chains_policy = msg.stale_chains_policy.unwrap_or_default();
 
     CONFIG.save(
         deps.storage,
         &Config {
-            data_expiry_seconds: msg.data_expiry_seconds,
+            luna_price_expiry_seconds: msg.luna_price_expiry_seconds,
+            chains_info_expiry_seconds: msg.chains_info_expiry_seconds,
+            stale_chains_policy,
+            governance_addr,
             controller_addr,
+            emissions_strategy,
         },
     )?;
 
     Ok(Response::new()
         .add_attribute("action", "instantiate")
-        .add_attribute("data_expiry_seconds", msg.data_expiry_seconds.to_string())
+        .add_attribute(
+            "luna_price_expiry_seconds",
+            msg.luna_price_expiry_seconds.to_string(),
+        )
+        .add_attribute(
+            "chains_info_expiry_seconds",
+            msg.chains_info_expiry_seconds.to_string(),
+        )
+        .add_attribute("governance_addr", msg.governance_addr)
         .add_attribute("controller_addr", msg.controller_addr))
 }
 
@@ -63,6 +107,24 @@ pub fn execute(
         ExecuteMsg::UpdateChainsInfo { chains_info } => {
             update_chains_info(deps, env, info, chains_info)
         }
+        ExecuteMsg::UpdateEmissionsStrategy { emissions_strategy } => {
+            update_emissions_strategy(deps, info, emissions_strategy)
+        }
+        ExecuteMsg::UpdateConfig {
+            governance_addr,
+            controller_addr,
+            luna_price_expiry_seconds,
+            chains_info_expiry_seconds,
+            stale_chains_policy,
+        } => update_config(
+            deps,
+            info,
+            governance_addr,
+            controller_addr,
+            luna_price_expiry_seconds,
+            chains_info_expiry_seconds,
+            stale_chains_policy,
+        ),
     }
 }
 
@@ -89,10 +151,70 @@ fn update_chains_info(
     Ok(Response::new().add_attribute("action", "update_chains_info"))
 }
 
+fn update_emissions_strategy(
+    deps: DepsMut,
+    info: MessageInfo,
+    emissions_strategy: EmissionsStrategy,
+) -> Result<Response, ContractError> {
+    let mut config = CONFIG.load(deps.storage)?;
+    utils::authorize_governance(&config, info.sender)?;
+    emissions::validate_emissions_strategy(&emissions_strategy)?;
+
+    config.emissions_strategy = emissions_strategy;
+    CONFIG.save(deps.storage, &config)?;
+
+    Ok(Response::new().add_attribute("action", "update_emissions_strategy"))
+}
+
+fn update_config(
+    deps: DepsMut,
+    info: MessageInfo,
+    governance_addr: Option<String>,
+    controller_addr: Option<String>,
+    luna_price_expiry_seconds: Option<u64>,
+    chains_info_expiry_seconds: Option<u64>,
+    stale_chains_policy: Option<StaleChainsPolicy>,
+) -> Result<Response, ContractError> {
+    let mut config = CONFIG.load(deps.storage)?;
+    utils::authorize_governance(&config, info.sender)?;
+    let mut response = Response::new().add_attribute("action", "update_config");
+
+    if let Some(governance_addr) = governance_addr {
+        config.governance_addr = deps.api.addr_validate(&governance_addr)?;
+        response = response.add_attribute("governance_addr", governance_addr);
+    }
+    if let Some(controller_addr) = controller_addr {
+        config.controller_addr = deps.api.addr_validate(&controller_addr)?;
+        response = response.add_attribute("controller_addr", controller_addr);
+    }
+    if let Some(luna_price_expiry_seconds) = luna_price_expiry_seconds {
+        config.luna_price_expiry_seconds = luna_price_expiry_seconds;
+        response = response.add_attribute(
+            "luna_price_expiry_seconds",
+            luna_price_expiry_seconds.to_string(),
+        );
+    }
+    if let Some(chains_info_expiry_seconds) = chains_info_expiry_seconds {
+        config.chains_info_expiry_seconds = chains_info_expiry_seconds;
+        response = response.add_attribute(
+            "chains_info_expiry_seconds",
+            chains_info_expiry_seconds.to_string(),
+        );
+    }
+    if let Some(stale_chains_policy) = stale_chains_policy {
+        response = response.add_attribute("stale_chains_policy", stale_chains_policy.to_string());
+        config.stale_chains_policy = stale_chains_policy;
+    }
+    CONFIG.save(deps.storage, &config)?;
+
+    Ok(response)
+}
+
 #[cfg_attr(not(feature = "library"), entry_point)]
-pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> StdResult<Binary> {
+pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> Result<Binary, ContractError> {
     Ok(match msg {
         QueryMsg::QueryConfig {} => get_config(deps)?,
+        QueryMsg::QueryGovernance {} => get_governance(deps)?,
         QueryMsg::QueryLunaInfo {} => get_luna_info(deps, env)?,
         QueryMsg::QueryChainInfo { chain_id } => get_chain_info(deps, env, chain_id)?,
         QueryMsg::QueryChainsInfo {} => get_chains_info(deps, env)?,
@@ -100,6 +222,13 @@ pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> StdResult<Binary> {
         QueryMsg::QueryEmissionsDistributions(query) => {
             get_emissions_distribution_info(deps, env, query)?
         }
+        QueryMsg::QuerySimulateEmissionsDistributions {
+            chains_info,
+            assets_staked,
+        } => get_simulated_emissions_distribution_info(deps, env, chains_info, assets_staked)?,
+        QueryMsg::QueryEmissionsDistributionsVerbose(query) => {
+            get_emissions_distribution_verbose_info(deps, env, query)?
+        }
     })
 }
 
@@ -109,39 +238,42 @@ pub fn get_config(deps: Deps) -> StdResult<Binary> {
     to_json_binary(&cfg)
 }
 
-pub fn get_luna_info(deps: Deps, env: Env) -> StdResult<Binary> {
+pub fn get_governance(deps: Deps) -> StdResult<Binary> {
+    let cfg = CONFIG.load(deps.storage)?;
+
+    to_json_binary(&cfg.governance_addr)
+}
+
+pub fn get_luna_info(deps: Deps, env: Env) -> Result<Binary, ContractError> {
     let luna_info = LUNA_INFO.load(deps.storage)?;
     let cfg = CONFIG.load(deps.storage)?;
 
-    luna_info.is_expired(cfg.data_expiry_seconds, env.block.time)?;
+    luna_info.is_expired(cfg.luna_price_expiry_seconds, env.block.time)?;
 
-    to_json_binary(&luna_info)
+    Ok(to_json_binary(&luna_info)?)
 }
 
-pub fn get_chain_info(deps: Deps, env: Env, chain_id: ChainId) -> StdResult<Binary> {
+pub fn get_chain_info(deps: Deps, env: Env, chain_id: ChainId) -> Result<Binary, ContractError> {
     let chains_info = CHAINS_INFO.load(deps.storage)?;
     let cfg = CONFIG.load(deps.storage)?;
 
     for chain_info in &chains_info {
         if chain_info.chain_id == chain_id {
-            chain_info.is_expired(cfg.data_expiry_seconds, env.block.time)?;
-            return to_json_binary(&chain_info);
+            chain_info.is_expired(cfg.chains_info_expiry_seconds, env.block.time)?;
+            return Ok(to_json_binary(&chain_info)?);
         }
     }
 
-    let string_error = format!("Chain not available by id: {:?}", chain_id);
-    Err(StdError::generic_err(string_error))
+    Err(ContractError::ChainNotFound { chain_id })
 }
 
-pub fn get_chains_info(deps: Deps, env: Env) -> StdResult<Binary> {
+pub fn get_chains_info(deps: Deps, env: Env) -> Result<Binary, ContractError> {
     let chains_info = CHAINS_INFO.load(deps.storage)?;
     let cfg = CONFIG.load(deps.storage)?;
 
-    for chain_info in &chains_info {
-        chain_info.is_expired(cfg.data_expiry_seconds, env.block.time)?;
-    }
+    let chains_info = utils::filter_stale_chains(&cfg, &env, chains_info)?;
 
-    to_json_binary(&chains_info)
+    Ok(to_json_binary(&chains_info)?)
 }
 
 pub fn get_chains_info_unsafe(deps: Deps) -> StdResult<Binary> {
@@ -151,93 +283,84 @@ pub fn get_chains_info_unsafe(deps: Deps) -> StdResult<Binary> {
 
 pub fn get_emissions_distribution_info(
     deps: Deps,
-    _env: Env,
+    env: Env,
     chains: HashMap<ChainId, Vec<AssetStaked>>,
-) -> StdResult<Binary> {
+) -> Result<Binary, ContractError> {
     // Information posted on chain periodically from oracle-feeder-go
     // https://github.com/terra-money/oracle-feeder-go.
     let chains_info = CHAINS_INFO.load(deps.storage)?;
     let luna = LUNA_INFO.load(deps.storage)?;
+    let cfg = CONFIG.load(deps.storage)?;
+    let chains_info = utils::check_emissions_data_expiry(&cfg, &env, chains_info, &luna, &chains)?;
 
-    // Incognitas to discover in the first for loop:
-    let mut chains_value: Vec<(ChainInfo, SignedDecimal)> = vec![];
-    let mut denom_rebase: HashMap<String, Decimal> = HashMap::new();
-
-    // First go through all chains and calculate the average yield for all alliances that accepts LUNA as a staking asset
-    for chain_info in chains_info {
-        if chains.contains_key(&chain_info.chain_id) {
-            // Accumulated amount of USD distributed to the Terra minus
-            // the value of LUNA taken by take_rate
-            let mut chain_accumulated_value = SignedDecimal::zero();
-
-            for alliance in chain_info.luna_alliances.clone() {
-                // Calculate the amount of chain native tokens distributed
-                // to the alliance in a year denominated in USD.
-                let tokens_distributed_to_alliance = chain_info.native_token.annual_provisions
-                    * alliance.normalized_reward_weight
-                    * chain_info.native_token.token_price;
-
-                // Calculate the total amount of LUNA staked with this alliance
-                // on this chain based on the amount of LSD's staked and their rebase factor.
-                let total_luna_staked = alliance.total_lsd_staked * alliance.rebase_factor;
-
-                // Calculate the amount of USD distributed to the Terra minus
-                // the value of LUNA taken by take_rate
-                let value =
-                    SignedDecimal::from_decimal(tokens_distributed_to_alliance, Sign::Positive)
-                        - (alliance.annual_take_rate * total_luna_staked * luna.luna_price);
-
-                chain_accumulated_value += value;
-            }
-            chains_value.push((chain_info.clone(), chain_accumulated_value));
+    let emission_distribution = emissions::compute_emissions_distributions(
+        &cfg.emissions_strategy,
+        &chains_info,
+        &luna,
+        &chains,
+    )?;
+
+    Ok(to_json_binary(&emission_distribution)?)
+}
+
+pub fn get_emissions_distribution_verbose_info(
+    deps: Deps,
+    env: Env,
+    chains: HashMap<ChainId, Vec<AssetStaked>>,
+) -> Result<Binary, ContractError> {
+    let chains_info = CHAINS_INFO.load(deps.storage)?;
+    let luna = LUNA_INFO.load(deps.storage)?;
+    let cfg = CONFIG.load(deps.storage)?;
+    let chains_info = utils::check_emissions_data_expiry(&cfg, &env, chains_info, &luna, &chains)?;
+
+    let breakdown = emissions::compute_emissions_breakdown(
+        &cfg.emissions_strategy,
+        &chains_info,
+        &luna,
+        &chains,
+    )?;
+
+    Ok(to_json_binary(&breakdown)?)
+}
 
-            for alliance in chain_info.chain_alliances_on_phoenix.clone() {
-                denom_rebase.insert(alliance.ibc_denom.clone(), alliance.rebase_factor);
+pub fn get_simulated_emissions_distribution_info(
+    deps: Deps,
+    env: Env,
+    chains_info_overrides: Option<ChainsInfo>,
+    assets_staked: HashMap<ChainId, Vec<AssetStaked>>,
+) -> Result<Binary, ContractError> {
+    let mut chains_info = CHAINS_INFO.may_load(deps.storage)?.unwrap_or_default();
+    let luna = match &chains_info_overrides {
+        Some(overrides) => overrides.to_luna_info(env.block.time),
+        None => LUNA_INFO.load(deps.storage)?,
+    };
+
+    // Hypothetical chains replace the stored ones with the same
+    // chain_id and the rest are simulated as newly added chains.
+    if let Some(overrides) = chains_info_overrides {
+        for chain_info_msg in &overrides.protocols_info {
+            let chain_info = chain_info_msg.to_chain_info(env.block.time);
+            match chains_info
+                .iter_mut()
+                .find(|c| c.chain_id == chain_info.chain_id)
+            {
+                Some(stored_chain_info) => *stored_chain_info = chain_info,
+                None => chains_info.push(chain_info),
             }
         }
     }
 
-    let mut emission_distribution = vec![];
-    for (chain_info, chain_value) in chains_value {
-        // Get the whitelisted asset base on the function parameter chains.ChainId
-        let whitelisted_assets = chains
-            .get(&chain_info.chain_id)
-            .ok_or(StdError::generic_err(format!(
-                "Error getting whitelisted assets for chain {:?}",
-                &chain_info.chain_id
-            )))?;
-
-        let mut total_staked = Decimal::zero();
-        for asset in whitelisted_assets {
-            let staked = Decimal::from_atomics(asset.amount, 0).map_err(|_| {
-                StdError::generic_err(format!(
-                    "Error converting staked amount to decimal for asset {:?}",
-                    asset.amount
-                ))
-            })?;
-            total_staked += staked * denom_rebase.get(&asset.denom).unwrap_or(&Decimal::one());
-        }
-        for asset in whitelisted_assets {
-            // If rebase is not set, use 1 as the rebase factor
-            let denom_rebase = *denom_rebase.get(&asset.denom).unwrap_or(&Decimal::one());
-            let staked_before_rebase = Decimal::from_atomics(asset.amount, 0).map_err(|_| {
-                StdError::generic_err(format!(
-                    "Error converting staked amount to decimal for asset {:?}",
-                    asset.amount
-                ))
-            })?;
-            let staked = staked_before_rebase * denom_rebase;
-            let distribution = if staked.is_zero() {
-                SignedDecimal::zero()
-            } else {
-                chain_value * staked / total_staked
-            };
-            emission_distribution.push(EmissionsDistribution {
-                denom: asset.denom.to_string(),
-                distribution,
-            });
-        }
-    }
+    // Overridden data is as fresh as the current block so only
+    // the stored data that has not been overridden can be stale.
+    let cfg = CONFIG.load(deps.storage)?;
+    let chains_info =
+        utils::check_emissions_data_expiry(&cfg, &env, chains_info, &luna, &assets_staked)?;
+    let emission_distribution = emissions::compute_emissions_distribution(
+        &cfg.emissions_strategy,
+        &chains_info,
+        &luna,
+        &assets_staked,
+    )?;
 
-    to_json_binary(&emission_distribution)
+    Ok(to_json_binary(&emission_distribution)?)
 }
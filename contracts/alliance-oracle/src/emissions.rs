@@ -0,0 +1,365 @@
+use std::collections::{HashMap, HashSet};
+use std::convert::TryFrom;
+
+use alliance_protocol::{
+    alliance_oracle_types::{
+        AssetEmissionsBreakdown, AssetStaked, ChainEmissionsBreakdown, ChainId, ChainInfo,
+        ChainWeight, EmissionsDistribution, EmissionsStrategy, LunaInfo,
+        SimulatedEmissionsDistribution,
+    },
+    error::ContractError,
+    signed_decimal::SignedDecimal,
+    signed_decimal_256::SignedDecimal256,
+};
+use cosmwasm_std::{ConversionOverflowError, Decimal, Decimal256, Uint128};
+
+// Values of a chain kept in 256 bits until the breakdown is built,
+// so that large supplies with 18 decimals do not overflow.
+struct ChainValues {
+    chain_id: ChainId,
+    tokens_distributed_value: Decimal256,
+    take_rate_cost: Decimal256,
+    chain_value: SignedDecimal256,
+    chain_weight: SignedDecimal256,
+    total_staked: Decimal256,
+    assets: Vec<AssetValues>,
+}
+
+struct AssetValues {
+    denom: String,
+    rebase_factor: Decimal,
+    staked: Uint128,
+    rebase_adjusted_stake: Decimal256,
+    distribution: SignedDecimal256,
+}
+
+// Computes the weight of each chain using the emissions strategy
+// and splits it between the whitelisted assets of the chain
+// pro rata to the amount staked adjusted by the rebase factor.
+// Only the chains present in the chains parameter are taken into account.
+fn compute_chains_values(
+    strategy: &EmissionsStrategy,
+    chains_info: &[ChainInfo],
+    luna: &LunaInfo,
+    chains: &HashMap<ChainId, Vec<AssetStaked>>,
+) -> Result<Vec<ChainValues>, ContractError> {
+    // Incognitas to discover in the first for loop:
+    let mut chains_value: Vec<ChainValues> = vec![];
+    let mut denom_rebase: HashMap<String, Decimal> = HashMap::new();
+    let luna_price = Decimal256::from(luna.luna_price);
+
+    // First go through all chains and calculate the average yield for all alliances that accepts LUNA as a staking asset
+    for chain_info in chains_info {
+        if chains.contains_key(&chain_info.chain_id) {
+            // Accumulated amount of USD distributed to the Terra minus
+            // the value of LUNA taken by take_rate
+            let mut chain_accumulated_value = SignedDecimal256::zero();
+            let mut tokens_distributed_value = Decimal256::zero();
+            let mut take_rate_cost = Decimal256::zero();
+
+            for alliance in &chain_info.luna_alliances {
+                // Calculate the amount of chain native tokens distributed
+                // to the alliance in a year denominated in USD.
+                let tokens_distributed_to_alliance =
+                    Decimal256::from(chain_info.native_token.annual_provisions)
+                        .checked_mul(alliance.normalized_reward_weight.into())?
+                        .checked_mul(chain_info.native_token.token_price.into())?;
+
+                // Calculate the total amount of LUNA staked with this alliance
+                // on this chain based on the amount of LSD's staked and their rebase factor.
+                let total_luna_staked = Decimal256::from(alliance.total_lsd_staked)
+                    .checked_mul(alliance.rebase_factor.into())?;
+                let alliance_take_rate_cost = Decimal256::from(alliance.annual_take_rate)
+                    .checked_mul(total_luna_staked)?
+                    .checked_mul(luna_price)?;
+
+                // Calculate the amount of USD distributed to the Terra minus
+                // the value of LUNA taken by take_rate
+                let value = SignedDecimal256::from(tokens_distributed_to_alliance)
+                    .checked_sub(alliance_take_rate_cost.into())?;
+
+                chain_accumulated_value = chain_accumulated_value.checked_add(value)?;
+                tokens_distributed_value =
+                    tokens_distributed_value.checked_add(tokens_distributed_to_alliance)?;
+                take_rate_cost = take_rate_cost.checked_add(alliance_take_rate_cost)?;
+            }
+            chains_value.push(ChainValues {
+                chain_id: chain_info.chain_id.clone(),
+                tokens_distributed_value,
+                take_rate_cost,
+                chain_value: chain_accumulated_value,
+                chain_weight: SignedDecimal256::zero(),
+                total_staked: Decimal256::zero(),
+                assets: vec![],
+            });
+
+            for alliance in &chain_info.chain_alliances_on_phoenix {
+                denom_rebase.insert(alliance.ibc_denom.clone(), alliance.rebase_factor);
+            }
+        }
+    }
+
+    // Then compute the rebase adjusted stake of every asset
+    for chain in chains_value.iter_mut() {
+        // Get the whitelisted asset base on the function parameter chains.ChainId
+        let whitelisted_assets =
+            chains
+                .get(&chain.chain_id)
+                .ok_or_else(|| ContractError::ChainNotFound {
+                    chain_id: chain.chain_id.clone(),
+                })?;
+
+        for asset in whitelisted_assets {
+            // If rebase is not set, use 1 as the rebase factor
+            let denom_rebase = *denom_rebase.get(&asset.denom).unwrap_or(&Decimal::one());
+            let staked_before_rebase = Decimal256::from_atomics(asset.amount, 0).map_err(|_| {
+                ContractError::DecimalConversion {
+                    value: asset.amount.to_string(),
+                }
+            })?;
+            let staked = staked_before_rebase.checked_mul(denom_rebase.into())?;
+            chain.total_staked = chain.total_staked.checked_add(staked)?;
+            chain.assets.push(AssetValues {
+                denom: asset.denom.to_string(),
+                rebase_factor: denom_rebase,
+                staked: asset.amount,
+                rebase_adjusted_stake: staked,
+                distribution: SignedDecimal256::zero(),
+            });
+        }
+    }
+
+    // Finally weight each chain based on the strategy and
+    // split the weight between the assets of the chain
+    let chains_weight = compute_chains_weight(strategy, &chains_value);
+    for (chain, chain_weight) in chains_value.iter_mut().zip(chains_weight) {
+        chain.chain_weight = chain_weight;
+        for asset in chain.assets.iter_mut() {
+            asset.distribution = if asset.rebase_adjusted_stake.is_zero() {
+                SignedDecimal256::zero()
+            } else {
+                chain_weight
+                    .checked_mul(asset.rebase_adjusted_stake.into())?
+                    .checked_div(chain.total_staked.into())?
+            };
+        }
+    }
+
+    Ok(chains_value)
+}
+
+// Only the distributions are converted down so the amounts staked
+// do not need to fit in a Decimal once adjusted by the rebase factor.
+pub fn compute_emissions_distributions(
+    strategy: &EmissionsStrategy,
+    chains_info: &[ChainInfo],
+    luna: &LunaInfo,
+    chains: &HashMap<ChainId, Vec<AssetStaked>>,
+) -> Result<Vec<EmissionsDistribution>, ContractError> {
+    let mut emission_distribution = vec![];
+    for chain in compute_chains_values(strategy, chains_info, luna, chains)? {
+        for asset in chain.assets {
+            emission_distribution.push(EmissionsDistribution {
+                denom: asset.denom,
+                distribution: SignedDecimal::try_from(asset.distribution)?,
+            });
+        }
+    }
+
+    Ok(emission_distribution)
+}
+
+pub fn compute_emissions_breakdown(
+    strategy: &EmissionsStrategy,
+    chains_info: &[ChainInfo],
+    luna: &LunaInfo,
+    chains: &HashMap<ChainId, Vec<AssetStaked>>,
+) -> Result<Vec<ChainEmissionsBreakdown>, ContractError> {
+    let mut breakdown = vec![];
+    for chain in compute_chains_values(strategy, chains_info, luna, chains)? {
+        let mut assets = vec![];
+        for asset in chain.assets {
+            assets.push(AssetEmissionsBreakdown {
+                denom: asset.denom,
+                rebase_factor: asset.rebase_factor,
+                staked: asset.staked,
+                rebase_adjusted_stake: to_decimal(asset.rebase_adjusted_stake)?,
+                distribution: SignedDecimal::try_from(asset.distribution)?,
+            });
+        }
+        breakdown.push(ChainEmissionsBreakdown {
+            chain_id: chain.chain_id,
+            tokens_distributed_value: to_decimal(chain.tokens_distributed_value)?,
+            take_rate_cost: to_decimal(chain.take_rate_cost)?,
+            chain_value: SignedDecimal::try_from(chain.chain_value)?,
+            chain_weight: SignedDecimal::try_from(chain.chain_weight)?,
+            assets,
+        });
+    }
+
+    Ok(breakdown)
+}
+
+fn to_decimal(value: Decimal256) -> Result<Decimal, ContractError> {
+    Decimal::try_from(value).map_err(|_| {
+        ConversionOverflowError::new("Decimal256", "Decimal", value.to_string()).into()
+    })
+}
+
+fn compute_chains_weight(
+    strategy: &EmissionsStrategy,
+    chains: &[ChainValues],
+) -> Vec<SignedDecimal256> {
+    match strategy {
+        EmissionsStrategy::ValueBased => chains.iter().map(|c| c.chain_value).collect(),
+        EmissionsStrategy::StakeProportional => chains
+            .iter()
+            .map(|c| SignedDecimal256::from(c.total_staked))
+            .collect(),
+        EmissionsStrategy::EqualWeightPerChain => {
+            chains.iter().map(|_| SignedDecimal256::one()).collect()
+        }
+        EmissionsStrategy::FixedWeights { weights } => {
+            let chain_weights: Vec<Option<&ChainWeight>> = chains
+                .iter()
+                .map(|c| weights.iter().find(|w| w.chain_id == c.chain_id))
+                .collect();
+            clamp_shares(&chain_weights)
+                .into_iter()
+                .map(SignedDecimal256::from)
+                .collect()
+        }
+    }
+}
+
+// Normalizes the weights into shares that add up to one and clamps
+// the shares by the caps and floors. Every time a share gets clamped
+// the remaining shares are normalized again over what is left, until
+// no share violates its bounds. Chains without weight get a zero share.
+fn clamp_shares(chain_weights: &[Option<&ChainWeight>]) -> Vec<Decimal256> {
+    let mut shares = vec![Decimal256::zero(); chain_weights.len()];
+    let mut clamped = vec![false; chain_weights.len()];
+
+    loop {
+        let mut free_share = Decimal256::one();
+        let mut free_weight = Decimal256::zero();
+        for (i, chain_weight) in chain_weights.iter().enumerate() {
+            if clamped[i] {
+                free_share = free_share.saturating_sub(shares[i]);
+            } else if let Some(chain_weight) = chain_weight {
+                free_weight += Decimal256::from(chain_weight.weight);
+            }
+        }
+
+        let mut violation = false;
+        for (i, chain_weight) in chain_weights.iter().enumerate() {
+            if clamped[i] {
+                continue;
+            }
+            let Some(chain_weight) = chain_weight else {
+                continue;
+            };
+            let share = if free_weight.is_zero() {
+                Decimal256::zero()
+            } else {
+                free_share * Decimal256::from(chain_weight.weight) / free_weight
+            };
+            shares[i] = share;
+            if let Some(cap) = chain_weight.cap {
+                if share > cap.into() {
+                    shares[i] = cap.into();
+                    clamped[i] = true;
+                    violation = true;
+                }
+            }
+            if let Some(floor) = chain_weight.floor {
+                if share < floor.into() {
+                    shares[i] = floor.into();
+                    clamped[i] = true;
+                    violation = true;
+                }
+            }
+        }
+
+        if !violation {
+            return shares;
+        }
+    }
+}
+
+pub fn validate_emissions_strategy(strategy: &EmissionsStrategy) -> Result<(), ContractError> {
+    if let EmissionsStrategy::FixedWeights { weights } = strategy {
+        if weights.is_empty() {
+            return Err(ContractError::InvalidEmissionsStrategy(
+                "fixed weights cannot be empty".to_string(),
+            ));
+        }
+        let mut chain_ids = HashSet::new();
+        let mut total_floor = Decimal::zero();
+        for w in weights {
+            if !chain_ids.insert(&w.chain_id) {
+                return Err(ContractError::InvalidEmissionsStrategy(format!(
+                    "duplicated weight for chain {}",
+                    w.chain_id
+                )));
+            }
+            let cap = w.cap.unwrap_or(Decimal::one());
+            let floor = w.floor.unwrap_or(Decimal::zero());
+            if cap > Decimal::one() || floor > cap {
+                return Err(ContractError::InvalidEmissionsStrategy(format!(
+                    "invalid cap {} and floor {} for chain {}",
+                    cap, floor, w.chain_id
+                )));
+            }
+            total_floor += floor;
+        }
+        if total_floor > Decimal::one() {
+            return Err(ContractError::InvalidEmissionsStrategy(format!(
+                "sum of floors {} is greater than 1",
+                total_floor
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+// Flattens the breakdown per asset and adds the share of the
+// total emissions that each asset ends up receiving.
+pub fn compute_emissions_distribution(
+    strategy: &EmissionsStrategy,
+    chains_info: &[ChainInfo],
+    luna: &LunaInfo,
+    chains: &HashMap<ChainId, Vec<AssetStaked>>,
+) -> Result<Vec<SimulatedEmissionsDistribution>, ContractError> {
+    let mut emission_distribution = vec![];
+    for chain in compute_emissions_breakdown(strategy, chains_info, luna, chains)? {
+        for asset in chain.assets {
+            emission_distribution.push(SimulatedEmissionsDistribution {
+                chain_id: chain.chain_id.clone(),
+                denom: asset.denom,
+                chain_value: chain.chain_value,
+                rebase_adjusted_stake: asset.rebase_adjusted_stake,
+                distribution: asset.distribution,
+                share: SignedDecimal::zero(),
+            });
+        }
+    }
+
+    // The hub normalizes the distributions by their sum,
+    // so the share is what each asset ends up receiving.
+    let total_distribution = emission_distribution
+        .iter()
+        .try_fold(SignedDecimal256::zero(), |acc, d| {
+            acc.checked_add(d.distribution.into())
+        })?;
+    if !total_distribution.is_zero() {
+        for asset_distribution in emission_distribution.iter_mut() {
+            let share = SignedDecimal256::from(asset_distribution.distribution)
+                .checked_div(total_distribution)?;
+            asset_distribution.share = SignedDecimal::try_from(share)?;
+        }
+    }
+
+    Ok(emission_distribution)
+}
@@ -1,4 +1,5 @@
 pub mod contract;
+pub mod emissions;
 pub mod state;
 #[cfg(test)]
 pub mod tests;
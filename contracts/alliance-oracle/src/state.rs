@@ -1,6 +1,20 @@
-use alliance_protocol::alliance_oracle_types::{ChainInfo, Config, LunaInfo};
+use alliance_protocol::alliance_oracle_types::{ChainInfo, Config, EmissionsStrategy, LunaInfo};
+use cosmwasm_schema::cw_serde;
+use cosmwasm_std::Addr;
 use cw_storage_plus::Item;
 
 pub const CONFIG: Item<Config> = Item::new("config");
 pub const CHAINS_INFO: Item<Vec<ChainInfo>> = Item::new("chains_info");
 pub const LUNA_INFO: Item<LunaInfo> = Item::new("luna_info");
+
+// Config stored by the versions with a single data expiry,
+// only used to migrate it to the current Config.
+#[cw_serde]
+pub struct LegacyConfig {
+    pub data_expiry_seconds: u64,
+    pub controller_addr: Addr,
+    #[serde(default)]
+    pub emissions_strategy: EmissionsStrategy,
+}
+
+pub const LEGACY_CONFIG: Item<LegacyConfig> = Item::new("config");
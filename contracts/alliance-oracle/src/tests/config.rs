@@ -0,0 +1,149 @@
+use crate::contract::{execute, query};
+use crate::tests::test_utils;
+use alliance_protocol::alliance_oracle_types::{
+    ChainsInfo, Config, ExecuteMsg, QueryMsg, StaleChainsPolicy,
+};
+use alliance_protocol::error::ContractError;
+use cosmwasm_std::{
+    from_json,
+    testing::{mock_env, mock_info},
+    to_json_string, Addr, Attribute, Decimal,
+};
+
+#[test]
+fn test_query_governance() {
+    let deps = test_utils::setup_contract();
+
+    let res = query(deps.as_ref(), mock_env(), QueryMsg::QueryGovernance {}).unwrap();
+    let governance: Addr = from_json(res).unwrap();
+    assert_eq!(governance, Addr::unchecked("governance_addr"));
+}
+
+#[test]
+fn test_stale_chains_policy_display_matches_serde() {
+    for policy in [StaleChainsPolicy::Fail, StaleChainsPolicy::Exclude] {
+        assert_eq!(to_json_string(&policy).unwrap(), format!("\"{}\"", policy));
+    }
+}
+
+#[test]
+fn test_update_config() {
+    let mut deps = test_utils::setup_contract();
+
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("governance_addr", &[]),
+        ExecuteMsg::UpdateConfig {
+            governance_addr: Some("new_governance_addr".to_string()),
+            controller_addr: Some("new_controller_addr".to_string()),
+            luna_price_expiry_seconds: Some(30),
+            chains_info_expiry_seconds: Some(600),
+            stale_chains_policy: Some(StaleChainsPolicy::Exclude),
+        },
+    )
+    .unwrap();
+    assert_eq!(
+        res.attributes,
+        vec![
+            Attribute::new("action", "update_config"),
+            Attribute::new("governance_addr", "new_governance_addr"),
+            Attribute::new("controller_addr", "new_controller_addr"),
+            Attribute::new("luna_price_expiry_seconds", "30"),
+            Attribute::new("chains_info_expiry_seconds", "600"),
+            Attribute::new("stale_chains_policy", "exclude"),
+        ]
+    );
+
+    let res = query(deps.as_ref(), mock_env(), QueryMsg::QueryConfig {}).unwrap();
+    let cfg: Config = from_json(res).unwrap();
+    assert_eq!(cfg.governance_addr, Addr::unchecked("new_governance_addr"));
+    assert_eq!(cfg.controller_addr, Addr::unchecked("new_controller_addr"));
+    assert_eq!(cfg.luna_price_expiry_seconds, 30);
+    assert_eq!(cfg.chains_info_expiry_seconds, 600);
+    assert_eq!(cfg.stale_chains_policy, StaleChainsPolicy::Exclude);
+
+    // Only the changed values are emitted
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("new_governance_addr", &[]),
+        ExecuteMsg::UpdateConfig {
+            governance_addr: None,
+            controller_addr: None,
+            luna_price_expiry_seconds: Some(45),
+            chains_info_expiry_seconds: None,
+            stale_chains_policy: None,
+        },
+    )
+    .unwrap();
+    assert_eq!(
+        res.attributes,
+        vec![
+            Attribute::new("action", "update_config"),
+            Attribute::new("luna_price_expiry_seconds", "45"),
+        ]
+    );
+}
+
+#[test]
+fn test_update_config_unauthorized() {
+    let mut deps = test_utils::setup_contract();
+
+    let msg = ExecuteMsg::UpdateConfig {
+        governance_addr: None,
+        controller_addr: Some("controller_addr".to_string()),
+        luna_price_expiry_seconds: None,
+        chains_info_expiry_seconds: None,
+        stale_chains_policy: None,
+    };
+    let err = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("controller_addr", &[]),
+        msg,
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::Unauthorized {});
+}
+
+#[test]
+fn test_rotate_controller() {
+    let mut deps = test_utils::setup_contract();
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("governance_addr", &[]),
+        ExecuteMsg::UpdateConfig {
+            governance_addr: None,
+            controller_addr: Some("new_controller_addr".to_string()),
+            luna_price_expiry_seconds: None,
+            chains_info_expiry_seconds: None,
+            stale_chains_policy: None,
+        },
+    )
+    .unwrap();
+
+    let msg = ExecuteMsg::UpdateChainsInfo {
+        chains_info: ChainsInfo {
+            luna_price: Decimal::one(),
+            protocols_info: vec![],
+        },
+    };
+    let err = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("controller_addr", &[]),
+        msg.clone(),
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::Unauthorized {});
+
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("new_controller_addr", &[]),
+        msg,
+    )
+    .unwrap();
+}
@@ -0,0 +1,292 @@
+use crate::contract::{execute, instantiate, migrate, query};
+use crate::state::{LegacyConfig, CHAINS_INFO, CONFIG, LEGACY_CONFIG, LUNA_INFO};
+use alliance_protocol::alliance_oracle_types::{
+    AssetStaked, BaseAlliance, ChainInfo, Config, EmissionsDistribution, EmissionsStrategy,
+    ExecuteMsg, InstantiateMsg, LunaAlliance, LunaInfo, MigrateMsg, NativeToken, QueryMsg,
+    StaleChainsPolicy,
+};
+use alliance_protocol::error::ContractError;
+use cosmwasm_std::{
+    from_json,
+    testing::{mock_dependencies, mock_env, mock_info, MockApi, MockQuerier, MockStorage},
+    Addr, Decimal, Empty, Env, OwnedDeps, Timestamp, Uint128,
+};
+use std::collections::HashMap;
+use std::str::FromStr;
+
+fn setup_contract(
+    stale_chains_policy: StaleChainsPolicy,
+) -> OwnedDeps<MockStorage, MockApi, MockQuerier, Empty> {
+    let mut deps = mock_dependencies();
+    instantiate(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("creator", &[]),
+        InstantiateMsg {
+            governance_addr: "governance_addr".to_string(),
+            controller_addr: "controller_addr".to_string(),
+            luna_price_expiry_seconds: 30,
+            chains_info_expiry_seconds: 120,
+            stale_chains_policy: Some(stale_chains_policy),
+            emissions_strategy: None,
+        },
+    )
+    .unwrap();
+    deps
+}
+
+fn chain_info(chain_id: &str, phoenix_denom: &str, update_timestamp: Timestamp) -> ChainInfo {
+    ChainInfo {
+        chain_id: chain_id.to_string(),
+        update_timestamp,
+        native_token: NativeToken {
+            denom: "udenom".to_string(),
+            token_price: Decimal::from_str("0.006").unwrap(),
+            annual_provisions: Decimal::from_str("40000000").unwrap(),
+        },
+        luna_alliances: vec![LunaAlliance {
+            ibc_denom: String::from("ibc/luna_alliance"),
+            normalized_reward_weight: Decimal::from_str("0.01").unwrap(),
+            annual_take_rate: Decimal::from_str("0.003").unwrap(),
+            total_lsd_staked: Decimal::from_str("21979").unwrap(),
+            rebase_factor: Decimal::from_str("1").unwrap(),
+        }],
+        chain_alliances_on_phoenix: vec![BaseAlliance {
+            ibc_denom: phoenix_denom.to_string(),
+            rebase_factor: Decimal::one(),
+        }],
+    }
+}
+
+// chain-1 is updated 200 seconds before chain-2
+// and LUNA price is updated with chain-1.
+fn save_oracle_data(deps: &mut OwnedDeps<MockStorage, MockApi, MockQuerier, Empty>) -> Env {
+    let mut env = mock_env();
+    let chain_1_update = env.block.time;
+    env.block.time = env.block.time.plus_seconds(200);
+    CHAINS_INFO
+        .save(
+            deps.as_mut().storage,
+            &vec![
+                chain_info("chain-1", "ibc/denom1", chain_1_update),
+                chain_info("chain-2", "ibc/denom2", env.block.time),
+            ],
+        )
+        .unwrap();
+    LUNA_INFO
+        .save(
+            deps.as_mut().storage,
+            &LunaInfo {
+                luna_price: Decimal::from_str("0.61").unwrap(),
+                update_timestamp: env.block.time,
+            },
+        )
+        .unwrap();
+    env
+}
+
+fn assets_staked() -> HashMap<String, Vec<AssetStaked>> {
+    HashMap::from([
+        (
+            "chain-1".to_string(),
+            vec![AssetStaked {
+                denom: "ibc/denom1".to_string(),
+                amount: Uint128::new(1_000_000),
+            }],
+        ),
+        (
+            "chain-2".to_string(),
+            vec![AssetStaked {
+                denom: "ibc/denom2".to_string(),
+                amount: Uint128::new(1_000_000),
+            }],
+        ),
+    ])
+}
+
+#[test]
+fn test_emissions_fail_with_stale_chain() {
+    let mut deps = setup_contract(StaleChainsPolicy::Fail);
+    let env = save_oracle_data(&mut deps);
+
+    let err = query(
+        deps.as_ref(),
+        env.clone(),
+        QueryMsg::QueryEmissionsDistributions(assets_staked()),
+    )
+    .unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::DataExpired {
+            update: mock_env().block.time,
+            expiry: mock_env().block.time.plus_seconds(120),
+            now: env.block.time,
+        }
+    );
+
+    // Chains that are not part of the query are not checked
+    let mut assets_staked = assets_staked();
+    assets_staked.remove("chain-1");
+    let res = query(
+        deps.as_ref(),
+        env.clone(),
+        QueryMsg::QueryEmissionsDistributions(assets_staked),
+    )
+    .unwrap();
+    let res: Vec<EmissionsDistribution> = from_json(res).unwrap();
+    assert_eq!(res.len(), 1);
+
+    query(deps.as_ref(), env, QueryMsg::QueryChainsInfo {}).unwrap_err();
+}
+
+#[test]
+fn test_emissions_exclude_stale_chain() {
+    let mut deps = setup_contract(StaleChainsPolicy::Exclude);
+    let env = save_oracle_data(&mut deps);
+
+    let res = query(
+        deps.as_ref(),
+        env.clone(),
+        QueryMsg::QueryEmissionsDistributions(assets_staked()),
+    )
+    .unwrap();
+    let res: Vec<EmissionsDistribution> = from_json(res).unwrap();
+    assert_eq!(res.len(), 1);
+    assert_eq!(res[0].denom, "ibc/denom2");
+
+    let res = query(deps.as_ref(), env.clone(), QueryMsg::QueryChainsInfo {}).unwrap();
+    let res: Vec<ChainInfo> = from_json(res).unwrap();
+    assert_eq!(res.len(), 1);
+    assert_eq!(res[0].chain_id, "chain-2");
+
+    // Querying the stale chain by id always fails
+    let err = query(
+        deps.as_ref(),
+        env.clone(),
+        QueryMsg::QueryChainInfo {
+            chain_id: "chain-1".to_string(),
+        },
+    )
+    .unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::DataExpired {
+            update: mock_env().block.time,
+            expiry: mock_env().block.time.plus_seconds(120),
+            now: env.block.time,
+        }
+    );
+
+    let err = query(
+        deps.as_ref(),
+        env,
+        QueryMsg::QueryChainInfo {
+            chain_id: "chain-3".to_string(),
+        },
+    )
+    .unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::ChainNotFound {
+            chain_id: "chain-3".to_string()
+        }
+    );
+}
+
+#[test]
+fn test_emissions_luna_price_expiry() {
+    let mut deps = setup_contract(StaleChainsPolicy::Exclude);
+    let mut env = save_oracle_data(&mut deps);
+    env.block.time = env.block.time.plus_seconds(31);
+
+    // LUNA price is older than 30 seconds while chain-2 data is still valid
+    query(
+        deps.as_ref(),
+        env.clone(),
+        QueryMsg::QueryEmissionsDistributions(assets_staked()),
+    )
+    .unwrap_err();
+    query(deps.as_ref(), env.clone(), QueryMsg::QueryLunaInfo {}).unwrap_err();
+
+    // LUNA price is not used by the stake proportional strategy
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("governance_addr", &[]),
+        ExecuteMsg::UpdateEmissionsStrategy {
+            emissions_strategy: EmissionsStrategy::StakeProportional,
+        },
+    )
+    .unwrap();
+    let res = query(
+        deps.as_ref(),
+        env,
+        QueryMsg::QueryEmissionsDistributions(assets_staked()),
+    )
+    .unwrap();
+    let res: Vec<EmissionsDistribution> = from_json(res).unwrap();
+    assert_eq!(res.len(), 1);
+}
+
+#[test]
+fn test_migrate_legacy_config() {
+    let mut deps = mock_dependencies();
+    LEGACY_CONFIG
+        .save(
+            deps.as_mut().storage,
+            &LegacyConfig {
+                data_expiry_seconds: 90,
+                controller_addr: Addr::unchecked("controller_addr"),
+                emissions_strategy: EmissionsStrategy::ValueBased,
+            },
+        )
+        .unwrap();
+
+    let err = migrate(
+        deps.as_mut(),
+        mock_env(),
+        MigrateMsg {
+            governance_addr: None,
+        },
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::MissingGovernanceAddr {});
+
+    migrate(
+        deps.as_mut(),
+        mock_env(),
+        MigrateMsg {
+            governance_addr: Some("governance_addr".to_string()),
+        },
+    )
+    .unwrap();
+
+    assert_eq!(
+        CONFIG.load(deps.as_ref().storage).unwrap(),
+        Config {
+            luna_price_expiry_seconds: 90,
+            chains_info_expiry_seconds: 90,
+            stale_chains_policy: StaleChainsPolicy::Fail,
+            governance_addr: Addr::unchecked("governance_addr"),
+            controller_addr: Addr::unchecked("controller_addr"),
+            emissions_strategy: EmissionsStrategy::ValueBased,
+        }
+    );
+
+    // Migrating an up to date config keeps it untouched
+    migrate(
+        deps.as_mut(),
+        mock_env(),
+        MigrateMsg {
+            governance_addr: None,
+        },
+    )
+    .unwrap();
+    assert_eq!(
+        CONFIG
+            .load(deps.as_ref().storage)
+            .unwrap()
+            .luna_price_expiry_seconds,
+        90
+    );
+}
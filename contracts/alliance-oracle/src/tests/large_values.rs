@@ -0,0 +1,143 @@
+use crate::contract::{execute, query};
+use crate::tests::test_utils;
+use alliance_protocol::alliance_oracle_types::{
+    AssetStaked, BaseAlliance, ChainInfoMsg, ChainsInfo, EmissionsDistribution, ExecuteMsg,
+    LunaAlliance, NativeToken, QueryMsg,
+};
+use alliance_protocol::error::ContractError;
+use alliance_protocol::signed_decimal::SignedDecimal;
+use cosmwasm_std::{
+    from_json,
+    testing::{mock_env, mock_info, MockApi, MockQuerier, MockStorage},
+    ConversionOverflowError, Decimal, Empty, OverflowError, OverflowOperation, OwnedDeps, StdError,
+    Uint128,
+};
+use std::collections::HashMap;
+use std::str::FromStr;
+
+fn setup_chain(
+    token_price: &str,
+    annual_provisions: &str,
+    annual_take_rate: &str,
+    total_lsd_staked: &str,
+) -> OwnedDeps<MockStorage, MockApi, MockQuerier, Empty> {
+    let mut deps = test_utils::setup_contract();
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("controller_addr", &[]),
+        ExecuteMsg::UpdateChainsInfo {
+            chains_info: ChainsInfo {
+                luna_price: Decimal::from_str("0.61").unwrap(),
+                protocols_info: vec![ChainInfoMsg {
+                    chain_id: "chain-1".to_string(),
+                    native_token: NativeToken {
+                        denom: "udenom".to_string(),
+                        token_price: Decimal::from_str(token_price).unwrap(),
+                        annual_provisions: Decimal::from_str(annual_provisions).unwrap(),
+                    },
+                    luna_alliances: vec![LunaAlliance {
+                        ibc_denom: String::from("ibc/luna_alliance"),
+                        normalized_reward_weight: Decimal::from_str("0.01").unwrap(),
+                        annual_take_rate: Decimal::from_str(annual_take_rate).unwrap(),
+                        total_lsd_staked: Decimal::from_str(total_lsd_staked).unwrap(),
+                        rebase_factor: Decimal::from_str("1.5").unwrap(),
+                    }],
+                    chain_alliances_on_phoenix: vec![BaseAlliance {
+                        ibc_denom: "ibc/denom1".to_string(),
+                        rebase_factor: Decimal::one(),
+                    }],
+                }],
+            },
+        },
+    )
+    .unwrap();
+    deps
+}
+
+// 18 decimals tokens with a supply of billions
+fn assets_staked() -> HashMap<String, Vec<AssetStaked>> {
+    HashMap::from([(
+        "chain-1".to_string(),
+        vec![
+            AssetStaked {
+                denom: "ibc/denom1".to_string(),
+                amount: Uint128::new(2_000_000_000_000_000_000_000_000_000),
+            },
+            AssetStaked {
+                denom: "ibc/denom2".to_string(),
+                amount: Uint128::new(1_000_000_000_000_000_000_000_000_000),
+            },
+        ],
+    )])
+}
+
+#[test]
+fn test_emissions_with_large_values() {
+    // total_lsd_staked * rebase_factor and the rebase adjusted
+    // stakes do not fit in a Decimal but the distributions do
+    let deps = setup_chain(
+        "0.006",
+        "1000000000000",
+        "0.0000000000001",
+        "300000000000000000000",
+    );
+
+    let res = query(
+        deps.as_ref(),
+        mock_env(),
+        QueryMsg::QueryEmissionsDistributions(assets_staked()),
+    )
+    .unwrap();
+    let res: Vec<EmissionsDistribution> = from_json(res).unwrap();
+    assert_eq!(
+        res,
+        vec![
+            EmissionsDistribution {
+                denom: "ibc/denom1".to_string(),
+                distribution: SignedDecimal::from_str("21700000").unwrap(),
+            },
+            EmissionsDistribution {
+                denom: "ibc/denom2".to_string(),
+                distribution: SignedDecimal::from_str("10850000").unwrap(),
+            },
+        ]
+    );
+
+    // The verbose breakdown includes the rebase adjusted stakes
+    let err = query(
+        deps.as_ref(),
+        mock_env(),
+        QueryMsg::QueryEmissionsDistributionsVerbose(assets_staked()),
+    )
+    .unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::ConversionOverflow(ConversionOverflowError::new(
+            "Decimal256",
+            "Decimal",
+            "2000000000000000000000000000"
+        ))
+    );
+}
+
+#[test]
+fn test_emissions_overflow_is_an_error() {
+    // Splitting the value of the chain between the assets overflows
+    let deps = setup_chain("300000000000000000000", "100000000000000000000", "0", "1");
+
+    let err = query(
+        deps.as_ref(),
+        mock_env(),
+        QueryMsg::QueryEmissionsDistributions(assets_staked()),
+    )
+    .unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::Std(StdError::overflow(OverflowError::new(
+            OverflowOperation::Mul,
+            "300000000000000000000000000000000000000",
+            "2000000000000000000000000000"
+        )))
+    );
+}
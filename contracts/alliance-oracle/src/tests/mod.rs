@@ -1,8 +1,9 @@
 use crate::contract::{execute, query};
 use crate::state::{CHAINS_INFO, LUNA_INFO};
 use alliance_protocol::alliance_oracle_types::{
-    AssetStaked, BaseAlliance, ChainInfo, ChainInfoMsg, ChainsInfo, EmissionsDistribution,
-    ExecuteMsg, LunaAlliance, LunaInfo, NativeToken, QueryMsg,
+    AssetEmissionsBreakdown, AssetStaked, BaseAlliance, ChainEmissionsBreakdown, ChainInfo,
+    ChainInfoMsg, ChainsInfo, EmissionsDistribution, ExecuteMsg, LunaAlliance, LunaInfo,
+    NativeToken, QueryMsg,
 };
 use alliance_protocol::signed_decimal::SignedDecimal;
 use cosmwasm_std::{
@@ -13,6 +14,11 @@ use cosmwasm_std::{
 use std::collections::HashMap;
 use std::str::FromStr;
 
+mod config;
+mod expiry;
+mod large_values;
+mod simulate;
+mod strategy;
 pub mod test_utils;
 
 #[test]
@@ -191,6 +197,34 @@ fn test_emissions_distribution() {
             denom: "ibc/randomd_denom".to_string(),
             distribution: SignedDecimal::from_str("2359.77843").unwrap(),
         }]
+    );
+
+    let msg = QueryMsg::QueryEmissionsDistributionsVerbose(HashMap::from([(
+        "chain-1".to_string(),
+        vec![AssetStaked {
+            denom: "ibc/randomd_denom".to_string(),
+            amount: Uint128::new(1_000_000),
+        }],
+    )]));
+
+    let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+    let res_parsed: Vec<ChainEmissionsBreakdown> = from_json(res).unwrap();
+    assert_eq!(
+        res_parsed,
+        vec![ChainEmissionsBreakdown {
+            chain_id: "chain-1".to_string(),
+            tokens_distributed_value: Decimal::from_str("2400").unwrap(),
+            take_rate_cost: Decimal::from_str("40.22157").unwrap(),
+            chain_value: SignedDecimal::from_str("2359.77843").unwrap(),
+            chain_weight: SignedDecimal::from_str("2359.77843").unwrap(),
+            assets: vec![AssetEmissionsBreakdown {
+                denom: "ibc/randomd_denom".to_string(),
+                rebase_factor: Decimal::one(),
+                staked: Uint128::new(1_000_000),
+                rebase_adjusted_stake: Decimal::from_str("1000000").unwrap(),
+                distribution: SignedDecimal::from_str("2359.77843").unwrap(),
+            }],
+        }]
     )
 }
 
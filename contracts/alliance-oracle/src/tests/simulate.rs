@@ -0,0 +1,234 @@
+use crate::contract::{execute, query};
+use crate::state::{CHAINS_INFO, LUNA_INFO};
+use crate::tests::test_utils;
+use alliance_protocol::alliance_oracle_types::{
+    AssetStaked, BaseAlliance, ChainInfoMsg, ChainsInfo, EmissionsDistribution, ExecuteMsg,
+    LunaAlliance, NativeToken, QueryMsg, SimulatedEmissionsDistribution,
+};
+use alliance_protocol::signed_decimal::SignedDecimal;
+use cosmwasm_std::{
+    from_json,
+    testing::{mock_env, mock_info},
+    Decimal, Uint128,
+};
+use std::collections::HashMap;
+use std::str::FromStr;
+
+fn chain_info_msg(chain_id: &str, token_price: &str, phoenix_denom: &str) -> ChainInfoMsg {
+    ChainInfoMsg {
+        chain_id: chain_id.to_string(),
+        native_token: NativeToken {
+            denom: "udenom".to_string(),
+            token_price: Decimal::from_str(token_price).unwrap(),
+            annual_provisions: Decimal::from_str("40000000").unwrap(),
+        },
+        luna_alliances: vec![LunaAlliance {
+            ibc_denom: String::from("ibc/luna_alliance"),
+            normalized_reward_weight: Decimal::from_str("0.01").unwrap(),
+            annual_take_rate: Decimal::from_str("0.003").unwrap(),
+            total_lsd_staked: Decimal::from_str("21979").unwrap(),
+            rebase_factor: Decimal::from_str("1").unwrap(),
+        }],
+        chain_alliances_on_phoenix: vec![BaseAlliance {
+            ibc_denom: phoenix_denom.to_string(),
+            rebase_factor: Decimal::from_str("1.2").unwrap(),
+        }],
+    }
+}
+
+fn assets_staked() -> HashMap<String, Vec<AssetStaked>> {
+    HashMap::from([
+        (
+            "chain-1".to_string(),
+            vec![
+                AssetStaked {
+                    denom: "ibc/denom1".to_string(),
+                    amount: Uint128::new(1_000_000),
+                },
+                AssetStaked {
+                    denom: "ibc/unknown_rebase".to_string(),
+                    amount: Uint128::new(3_000_000),
+                },
+            ],
+        ),
+        (
+            "chain-2".to_string(),
+            vec![AssetStaked {
+                denom: "ibc/denom2".to_string(),
+                amount: Uint128::new(2_000_000),
+            }],
+        ),
+    ])
+}
+
+fn update_chains_info(deps: cosmwasm_std::DepsMut, chains_info: ChainsInfo) {
+    execute(
+        deps,
+        mock_env(),
+        mock_info("controller_addr", &[]),
+        ExecuteMsg::UpdateChainsInfo { chains_info },
+    )
+    .unwrap();
+}
+
+#[test]
+fn test_simulate_without_overrides_matches_emissions_distributions() {
+    let mut deps = test_utils::setup_contract();
+    update_chains_info(
+        deps.as_mut(),
+        ChainsInfo {
+            luna_price: Decimal::from_str("0.61").unwrap(),
+            protocols_info: vec![
+                chain_info_msg("chain-1", "0.006", "ibc/denom1"),
+                chain_info_msg("chain-2", "0.02", "ibc/denom2"),
+            ],
+        },
+    );
+
+    let res = query(
+        deps.as_ref(),
+        mock_env(),
+        QueryMsg::QueryEmissionsDistributions(assets_staked()),
+    )
+    .unwrap();
+    let distributions: Vec<EmissionsDistribution> = from_json(res).unwrap();
+
+    let res = query(
+        deps.as_ref(),
+        mock_env(),
+        QueryMsg::QuerySimulateEmissionsDistributions {
+            chains_info: None,
+            assets_staked: assets_staked(),
+        },
+    )
+    .unwrap();
+    let simulation: Vec<SimulatedEmissionsDistribution> = from_json(res).unwrap();
+
+    assert_eq!(simulation.len(), 3);
+    for (simulated, expected) in simulation.iter().zip(distributions.iter()) {
+        assert_eq!(simulated.denom, expected.denom);
+        assert_eq!(simulated.distribution, expected.distribution);
+    }
+
+    // Rebase factor of 1.2 is applied for known denoms and 1 otherwise
+    assert_eq!(simulation[0].chain_id, "chain-1");
+    assert_eq!(
+        simulation[0].rebase_adjusted_stake,
+        Decimal::from_str("1200000").unwrap()
+    );
+    assert_eq!(
+        simulation[1].rebase_adjusted_stake,
+        Decimal::from_str("3000000").unwrap()
+    );
+    assert_eq!(simulation[0].chain_value, simulation[1].chain_value);
+    assert_eq!(simulation[2].chain_id, "chain-2");
+    assert_eq!(
+        simulation[2].rebase_adjusted_stake,
+        Decimal::from_str("2400000").unwrap()
+    );
+
+    let total_share = simulation
+        .iter()
+        .fold(SignedDecimal::zero(), |acc, s| acc + s.share);
+    let rounding_error = (total_share - Decimal::one()).to_string();
+    assert!(
+        rounding_error.starts_with("0.00000000000000")
+            || rounding_error.starts_with("-0.00000000000000"),
+        "{}",
+        rounding_error
+    );
+}
+
+#[test]
+fn test_simulate_with_overrides_does_not_persist() {
+    let mut deps = test_utils::setup_contract();
+    update_chains_info(
+        deps.as_mut(),
+        ChainsInfo {
+            luna_price: Decimal::from_str("0.61").unwrap(),
+            protocols_info: vec![chain_info_msg("chain-1", "0.006", "ibc/denom1")],
+        },
+    );
+    let chains_info_before = CHAINS_INFO.load(deps.as_ref().storage).unwrap();
+    let luna_info_before = LUNA_INFO.load(deps.as_ref().storage).unwrap();
+
+    // Simulate a new LUNA price, a new token price for chain-1 and a new chain-2
+    let overrides = ChainsInfo {
+        luna_price: Decimal::from_str("0.4").unwrap(),
+        protocols_info: vec![
+            chain_info_msg("chain-1", "0.01", "ibc/denom1"),
+            chain_info_msg("chain-2", "0.02", "ibc/denom2"),
+        ],
+    };
+    let res = query(
+        deps.as_ref(),
+        mock_env(),
+        QueryMsg::QuerySimulateEmissionsDistributions {
+            chains_info: Some(overrides.clone()),
+            assets_staked: assets_staked(),
+        },
+    )
+    .unwrap();
+    let simulation: Vec<SimulatedEmissionsDistribution> = from_json(res).unwrap();
+
+    assert_eq!(
+        CHAINS_INFO.load(deps.as_ref().storage).unwrap(),
+        chains_info_before
+    );
+    assert_eq!(
+        LUNA_INFO.load(deps.as_ref().storage).unwrap(),
+        luna_info_before
+    );
+
+    // The simulation must match the result of actually posting the overrides
+    update_chains_info(deps.as_mut(), overrides);
+    let res = query(
+        deps.as_ref(),
+        mock_env(),
+        QueryMsg::QueryEmissionsDistributions(assets_staked()),
+    )
+    .unwrap();
+    let distributions: Vec<EmissionsDistribution> = from_json(res).unwrap();
+    assert_eq!(
+        simulation
+            .into_iter()
+            .map(|s| EmissionsDistribution {
+                denom: s.denom,
+                distribution: s.distribution,
+            })
+            .collect::<Vec<EmissionsDistribution>>(),
+        distributions
+    );
+}
+
+#[test]
+fn test_simulate_without_stored_data() {
+    let deps = test_utils::setup_contract();
+
+    let res = query(
+        deps.as_ref(),
+        mock_env(),
+        QueryMsg::QuerySimulateEmissionsDistributions {
+            chains_info: Some(ChainsInfo {
+                luna_price: Decimal::from_str("0.61").unwrap(),
+                protocols_info: vec![chain_info_msg("chain-2", "0.02", "ibc/denom2")],
+            }),
+            assets_staked: assets_staked(),
+        },
+    )
+    .unwrap();
+    let simulation: Vec<SimulatedEmissionsDistribution> = from_json(res).unwrap();
+    assert_eq!(simulation.len(), 1);
+    assert_eq!(simulation[0].denom, "ibc/denom2");
+    assert_eq!(simulation[0].share, SignedDecimal::from_str("1").unwrap());
+
+    query(
+        deps.as_ref(),
+        mock_env(),
+        QueryMsg::QuerySimulateEmissionsDistributions {
+            chains_info: None,
+            assets_staked: assets_staked(),
+        },
+    )
+    .unwrap_err();
+}
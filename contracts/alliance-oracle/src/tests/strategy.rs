@@ -0,0 +1,341 @@
+use crate::contract::{execute, query};
+use crate::tests::test_utils;
+use alliance_protocol::alliance_oracle_types::{
+    AssetStaked, BaseAlliance, ChainInfoMsg, ChainWeight, ChainsInfo, Config,
+    EmissionsDistribution, EmissionsStrategy, ExecuteMsg, LunaAlliance, NativeToken, QueryMsg,
+};
+use alliance_protocol::error::ContractError;
+use alliance_protocol::signed_decimal::SignedDecimal;
+use cosmwasm_std::{
+    from_json,
+    testing::{mock_env, mock_info, MockApi, MockQuerier, MockStorage},
+    Decimal, Empty, OwnedDeps, Uint128,
+};
+use std::collections::HashMap;
+use std::str::FromStr;
+
+fn chain_info_msg(chain_id: &str, phoenix_denom: &str) -> ChainInfoMsg {
+    ChainInfoMsg {
+        chain_id: chain_id.to_string(),
+        native_token: NativeToken {
+            denom: "udenom".to_string(),
+            token_price: Decimal::from_str("0.006").unwrap(),
+            annual_provisions: Decimal::from_str("40000000").unwrap(),
+        },
+        luna_alliances: vec![LunaAlliance {
+            ibc_denom: String::from("ibc/luna_alliance"),
+            normalized_reward_weight: Decimal::from_str("0.01").unwrap(),
+            annual_take_rate: Decimal::from_str("0.003").unwrap(),
+            total_lsd_staked: Decimal::from_str("21979").unwrap(),
+            rebase_factor: Decimal::from_str("1").unwrap(),
+        }],
+        chain_alliances_on_phoenix: vec![BaseAlliance {
+            ibc_denom: phoenix_denom.to_string(),
+            rebase_factor: Decimal::from_str("1.2").unwrap(),
+        }],
+    }
+}
+
+fn setup_chains() -> OwnedDeps<MockStorage, MockApi, MockQuerier, Empty> {
+    let mut deps = test_utils::setup_contract();
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("controller_addr", &[]),
+        ExecuteMsg::UpdateChainsInfo {
+            chains_info: ChainsInfo {
+                luna_price: Decimal::from_str("0.61").unwrap(),
+                protocols_info: vec![
+                    chain_info_msg("chain-1", "ibc/denom1"),
+                    chain_info_msg("chain-2", "ibc/denom2"),
+                    chain_info_msg("chain-3", "ibc/denom3"),
+                ],
+            },
+        },
+    )
+    .unwrap();
+    deps
+}
+
+fn update_strategy(
+    deps: &mut OwnedDeps<MockStorage, MockApi, MockQuerier, Empty>,
+    emissions_strategy: EmissionsStrategy,
+) -> Result<(), ContractError> {
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("governance_addr", &[]),
+        ExecuteMsg::UpdateEmissionsStrategy { emissions_strategy },
+    )
+    .map(|_| ())
+}
+
+fn query_distributions(
+    deps: &OwnedDeps<MockStorage, MockApi, MockQuerier, Empty>,
+) -> Vec<EmissionsDistribution> {
+    let assets_staked = HashMap::from([
+        (
+            "chain-1".to_string(),
+            vec![
+                AssetStaked {
+                    denom: "ibc/denom1".to_string(),
+                    amount: Uint128::new(1_000_000),
+                },
+                AssetStaked {
+                    denom: "ibc/other1".to_string(),
+                    amount: Uint128::new(600_000),
+                },
+            ],
+        ),
+        (
+            "chain-2".to_string(),
+            vec![AssetStaked {
+                denom: "ibc/denom2".to_string(),
+                amount: Uint128::new(2_000_000),
+            }],
+        ),
+        (
+            "chain-3".to_string(),
+            vec![AssetStaked {
+                denom: "ibc/denom3".to_string(),
+                amount: Uint128::new(1_000_000),
+            }],
+        ),
+    ]);
+    let res = query(
+        deps.as_ref(),
+        mock_env(),
+        QueryMsg::QueryEmissionsDistributions(assets_staked),
+    )
+    .unwrap();
+    from_json(res).unwrap()
+}
+
+fn distribution(denom: &str, value: &str) -> EmissionsDistribution {
+    EmissionsDistribution {
+        denom: denom.to_string(),
+        distribution: SignedDecimal::from_str(value).unwrap(),
+    }
+}
+
+#[test]
+fn test_default_strategy_is_value_based() {
+    let deps = setup_chains();
+    let cfg: Config =
+        from_json(query(deps.as_ref(), mock_env(), QueryMsg::QueryConfig {}).unwrap()).unwrap();
+    assert_eq!(cfg.emissions_strategy, EmissionsStrategy::ValueBased);
+
+    // All chains have the same value so chain-1 assets split
+    // the value of the chain based on the rebase adjusted stake
+    assert_eq!(
+        query_distributions(&deps),
+        vec![
+            distribution("ibc/denom1", "1573.18562"),
+            distribution("ibc/other1", "786.59281"),
+            distribution("ibc/denom2", "2359.77843"),
+            distribution("ibc/denom3", "2359.77843"),
+        ]
+    );
+}
+
+#[test]
+fn test_stake_proportional_strategy() {
+    let mut deps = setup_chains();
+    update_strategy(&mut deps, EmissionsStrategy::StakeProportional).unwrap();
+
+    assert_eq!(
+        query_distributions(&deps),
+        vec![
+            distribution("ibc/denom1", "1200000"),
+            distribution("ibc/other1", "600000"),
+            distribution("ibc/denom2", "2400000"),
+            distribution("ibc/denom3", "1200000"),
+        ]
+    );
+}
+
+#[test]
+fn test_equal_weight_per_chain_strategy() {
+    let mut deps = setup_chains();
+    update_strategy(&mut deps, EmissionsStrategy::EqualWeightPerChain).unwrap();
+
+    assert_eq!(
+        query_distributions(&deps),
+        vec![
+            distribution("ibc/denom1", "0.666666666666666666"),
+            distribution("ibc/other1", "0.333333333333333333"),
+            distribution("ibc/denom2", "1"),
+            distribution("ibc/denom3", "1"),
+        ]
+    );
+}
+
+#[test]
+fn test_fixed_weights_strategy_with_caps_and_floors() {
+    let mut deps = setup_chains();
+    update_strategy(
+        &mut deps,
+        EmissionsStrategy::FixedWeights {
+            weights: vec![
+                ChainWeight {
+                    chain_id: "chain-1".to_string(),
+                    weight: Decimal::from_str("6").unwrap(),
+                    cap: Some(Decimal::from_str("0.5").unwrap()),
+                    floor: None,
+                },
+                ChainWeight {
+                    chain_id: "chain-2".to_string(),
+                    weight: Decimal::from_str("3").unwrap(),
+                    cap: None,
+                    floor: None,
+                },
+                ChainWeight {
+                    chain_id: "chain-3".to_string(),
+                    weight: Decimal::from_str("1").unwrap(),
+                    cap: None,
+                    floor: Some(Decimal::from_str("0.2").unwrap()),
+                },
+            ],
+        },
+    )
+    .unwrap();
+
+    // Normalized weights are 0.6, 0.3 and 0.1 so chain-1 is capped
+    // to 0.5, chain-3 is raised to 0.2 and chain-2 keeps what is left
+    assert_eq!(
+        query_distributions(&deps),
+        vec![
+            distribution("ibc/denom1", "0.333333333333333333"),
+            distribution("ibc/other1", "0.166666666666666666"),
+            distribution("ibc/denom2", "0.3"),
+            distribution("ibc/denom3", "0.2"),
+        ]
+    );
+
+    // The share removed by the cap is redistributed pro rata to the
+    // weights of the chains that are not capped. Chains without weight
+    // do not receive emissions.
+    update_strategy(
+        &mut deps,
+        EmissionsStrategy::FixedWeights {
+            weights: vec![
+                ChainWeight {
+                    chain_id: "chain-1".to_string(),
+                    weight: Decimal::from_str("8").unwrap(),
+                    cap: Some(Decimal::from_str("0.6").unwrap()),
+                    floor: None,
+                },
+                ChainWeight {
+                    chain_id: "chain-2".to_string(),
+                    weight: Decimal::from_str("1").unwrap(),
+                    cap: None,
+                    floor: None,
+                },
+            ],
+        },
+    )
+    .unwrap();
+    assert_eq!(
+        query_distributions(&deps),
+        vec![
+            distribution("ibc/denom1", "0.4"),
+            distribution("ibc/other1", "0.2"),
+            distribution("ibc/denom2", "0.4"),
+            distribution("ibc/denom3", "0"),
+        ]
+    );
+}
+
+#[test]
+fn test_update_strategy_validation() {
+    let mut deps = setup_chains();
+
+    let err = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("user", &[]),
+        ExecuteMsg::UpdateEmissionsStrategy {
+            emissions_strategy: EmissionsStrategy::EqualWeightPerChain,
+        },
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::Unauthorized {});
+
+    // The strategy is set by governance, not by the controller
+    let err = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("controller_addr", &[]),
+        ExecuteMsg::UpdateEmissionsStrategy {
+            emissions_strategy: EmissionsStrategy::EqualWeightPerChain,
+        },
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::Unauthorized {});
+
+    let err = update_strategy(
+        &mut deps,
+        EmissionsStrategy::FixedWeights { weights: vec![] },
+    )
+    .unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::InvalidEmissionsStrategy("fixed weights cannot be empty".to_string())
+    );
+
+    let weight = ChainWeight {
+        chain_id: "chain-1".to_string(),
+        weight: Decimal::one(),
+        cap: Some(Decimal::from_str("0.4").unwrap()),
+        floor: Some(Decimal::from_str("0.6").unwrap()),
+    };
+    let err = update_strategy(
+        &mut deps,
+        EmissionsStrategy::FixedWeights {
+            weights: vec![weight.clone()],
+        },
+    )
+    .unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::InvalidEmissionsStrategy(
+            "invalid cap 0.4 and floor 0.6 for chain chain-1".to_string()
+        )
+    );
+
+    let weight = ChainWeight {
+        cap: None,
+        ..weight
+    };
+    let err = update_strategy(
+        &mut deps,
+        EmissionsStrategy::FixedWeights {
+            weights: vec![weight.clone(), weight.clone()],
+        },
+    )
+    .unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::InvalidEmissionsStrategy("duplicated weight for chain chain-1".to_string())
+    );
+
+    let err = update_strategy(
+        &mut deps,
+        EmissionsStrategy::FixedWeights {
+            weights: vec![
+                weight,
+                ChainWeight {
+                    chain_id: "chain-2".to_string(),
+                    weight: Decimal::one(),
+                    cap: None,
+                    floor: Some(Decimal::from_str("0.5").unwrap()),
+                },
+            ],
+        },
+    )
+    .unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::InvalidEmissionsStrategy("sum of floors 1.1 is greater than 1".to_string())
+    );
+}
@@ -10,8 +10,12 @@ use crate::contract::{instantiate, query};
 pub fn setup_contract() -> OwnedDeps<MockStorage, MockApi, MockQuerier, Empty> {
     let mut deps = mock_dependencies();
     let msg = InstantiateMsg {
-        data_expiry_seconds: 60,
+        luna_price_expiry_seconds: 60,
+        chains_info_expiry_seconds: 60,
+        stale_chains_policy: None,
+        governance_addr: "governance_addr".to_string(),
         controller_addr: "controller_addr".to_string(),
+        emissions_strategy: None,
     };
     let info = mock_info("creator", &[]);
     let res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
@@ -20,8 +24,10 @@ pub fn setup_contract() -> OwnedDeps<MockStorage, MockApi, MockQuerier, Empty> {
     let cfg = query(deps.as_ref(), mock_env(), QueryMsg::QueryConfig {}).unwrap();
 
     let cfg: Config = from_json(cfg).unwrap();
+    assert_eq!("governance_addr", cfg.governance_addr);
     assert_eq!("controller_addr", cfg.controller_addr);
-    assert_eq!(60, cfg.data_expiry_seconds);
+    assert_eq!(60, cfg.luna_price_expiry_seconds);
+    assert_eq!(60, cfg.chains_info_expiry_seconds);
 
     deps
 }
@@ -1,6 +1,10 @@
-use alliance_protocol::alliance_oracle_types::Config;
+use std::collections::HashMap;
+
+use alliance_protocol::alliance_oracle_types::{
+    AssetStaked, ChainId, ChainInfo, Config, EmissionsStrategy, Expire, LunaInfo, StaleChainsPolicy,
+};
 use alliance_protocol::error::ContractError;
-use cosmwasm_std::Addr;
+use cosmwasm_std::{Addr, Env};
 
 pub fn authorize_execution(config: Config, addr: Addr) -> Result<(), ContractError> {
     if addr != config.controller_addr {
@@ -9,3 +13,57 @@ pub fn authorize_execution(config: Config, addr: Addr) -> Result<(), ContractErr
 
     Ok(())
 }
+
+pub fn authorize_governance(config: &Config, addr: Addr) -> Result<(), ContractError> {
+    if addr != config.governance_addr {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    Ok(())
+}
+
+// Rejects or removes the stale chains based on the stale chains policy.
+pub fn filter_stale_chains(
+    config: &Config,
+    env: &Env,
+    chains_info: Vec<ChainInfo>,
+) -> Result<Vec<ChainInfo>, ContractError> {
+    match config.stale_chains_policy {
+        StaleChainsPolicy::Fail => {
+            for chain_info in &chains_info {
+                chain_info.is_expired(config.chains_info_expiry_seconds, env.block.time)?;
+            }
+            Ok(chains_info)
+        }
+        StaleChainsPolicy::Exclude => Ok(chains_info
+            .into_iter()
+            .filter(|chain_info| {
+                chain_info
+                    .is_expired(config.chains_info_expiry_seconds, env.block.time)
+                    .is_ok()
+            })
+            .collect()),
+    }
+}
+
+// Checks the expiry of the data used to compute the emissions distribution.
+// LUNA price is only used by the value based strategy and only the
+// chains with assets staked are subject to the stale chains policy.
+pub fn check_emissions_data_expiry(
+    config: &Config,
+    env: &Env,
+    chains_info: Vec<ChainInfo>,
+    luna: &LunaInfo,
+    chains: &HashMap<ChainId, Vec<AssetStaked>>,
+) -> Result<Vec<ChainInfo>, ContractError> {
+    if config.emissions_strategy == EmissionsStrategy::ValueBased {
+        luna.is_expired(config.luna_price_expiry_seconds, env.block.time)?;
+    }
+
+    let chains_info = chains_info
+        .into_iter()
+        .filter(|chain_info| chains.contains_key(&chain_info.chain_id))
+        .collect();
+
+    filter_stale_chains(config, env, chains_info)
+}
@@ -1,12 +1,73 @@
+use crate::error::ContractError;
 use crate::signed_decimal::SignedDecimal;
 use cosmwasm_schema::{cw_serde, QueryResponses};
-use cosmwasm_std::{Addr, Decimal, StdError, Timestamp, Uint128};
+use cosmwasm_std::{Addr, Decimal, Timestamp, Uint128};
 use std::collections::HashMap;
+use std::fmt;
 
 #[cw_serde]
 pub struct Config {
-    pub data_expiry_seconds: u64,
+    pub luna_price_expiry_seconds: u64,
+    pub chains_info_expiry_seconds: u64,
+    #[serde(default)]
+    pub stale_chains_policy: StaleChainsPolicy,
+    pub governance_addr: Addr,
     pub controller_addr: Addr,
+    #[serde(default)]
+    pub emissions_strategy: EmissionsStrategy,
+}
+
+// Defines what happens with the chains which data
+// is older than chains_info_expiry_seconds.
+#[cw_serde]
+#[derive(Default)]
+pub enum StaleChainsPolicy {
+    // Queries depending on a stale chain return an error.
+    #[default]
+    Fail,
+    // Stale chains are left out of the queries results.
+    Exclude,
+}
+
+// Matches the serde names so the event attributes are stable
+impl fmt::Display for StaleChainsPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StaleChainsPolicy::Fail => write!(f, "fail"),
+            StaleChainsPolicy::Exclude => write!(f, "exclude"),
+        }
+    }
+}
+
+// Defines how the weight of each chain is computed before being split
+// between the chain assets pro rata to the rebase adjusted stake.
+#[cw_serde]
+#[derive(Default)]
+pub enum EmissionsStrategy {
+    // Net USD value distributed by the chain to the LUNA alliances
+    // minus the value of the LUNA taken by the take rate.
+    #[default]
+    ValueBased,
+    // Rebase adjusted stake of the chain, so all assets are
+    // weighted by their stake regardless of the chain.
+    StakeProportional,
+    // Every chain receives the same weight.
+    EqualWeightPerChain,
+    // Weights defined by governance, normalized over the chains
+    // being distributed and clamped by the optional caps and floors.
+    FixedWeights {
+        weights: Vec<ChainWeight>,
+    },
+}
+
+#[cw_serde]
+pub struct ChainWeight {
+    pub chain_id: ChainId,
+    pub weight: Decimal,
+    // Maximum share of the total emissions for the chain
+    pub cap: Option<Decimal>,
+    // Minimum share of the total emissions for the chain
+    pub floor: Option<Decimal>,
 }
 
 #[cw_serde]
@@ -23,13 +84,29 @@ impl Expire for LunaInfo {
 
 #[cw_serde]
 pub struct InstantiateMsg {
+    pub governance_addr: String,
     pub controller_addr: String,
-    pub data_expiry_seconds: u64,
+    pub luna_price_expiry_seconds: u64,
+    pub chains_info_expiry_seconds: u64,
+    pub stale_chains_policy: Option<StaleChainsPolicy>,
+    pub emissions_strategy: Option<EmissionsStrategy>,
 }
 
 #[cw_serde]
 pub enum ExecuteMsg {
-    UpdateChainsInfo { chains_info: ChainsInfo },
+    UpdateChainsInfo {
+        chains_info: ChainsInfo,
+    },
+    UpdateEmissionsStrategy {
+        emissions_strategy: EmissionsStrategy,
+    },
+    UpdateConfig {
+        governance_addr: Option<String>,
+        controller_addr: Option<String>,
+        luna_price_expiry_seconds: Option<u64>,
+        chains_info_expiry_seconds: Option<u64>,
+        stale_chains_policy: Option<StaleChainsPolicy>,
+    },
 }
 
 #[cw_serde]
@@ -90,18 +167,16 @@ pub trait Expire {
         &self,
         data_expiry_seconds: u64,
         current_blocktime: Timestamp,
-    ) -> Result<(), StdError> {
-        let data_expiry_time = self
-            .get_update_timestamp()
-            .plus_seconds(data_expiry_seconds);
-
-        if data_expiry_time < current_blocktime {
-            let string_error = format!(
-                "Data expired, current_blocktime: {}, data_expiry_time: {}",
-                current_blocktime, data_expiry_time,
-            );
-
-            return Err(StdError::generic_err(string_error));
+    ) -> Result<(), ContractError> {
+        let update = self.get_update_timestamp();
+        let expiry = update.plus_seconds(data_expiry_seconds);
+
+        if expiry < current_blocktime {
+            return Err(ContractError::DataExpired {
+                update,
+                expiry,
+                now: current_blocktime,
+            });
         }
 
         Ok(())
@@ -142,11 +217,63 @@ pub struct EmissionsDistribution {
     pub distribution: SignedDecimal,
 }
 
+impl EmissionsDistribution {
+    // Hubs can only distribute positive amounts
+    pub fn to_decimal(&self) -> Result<Decimal, ContractError> {
+        self.distribution
+            .to_decimal()
+            .map_err(|_| ContractError::NegativeDistribution {
+                denom: self.denom.clone(),
+            })
+    }
+}
+
+#[cw_serde]
+pub struct SimulatedEmissionsDistribution {
+    pub chain_id: ChainId,
+    pub denom: String,
+    // Net USD value of the chain the asset belongs to
+    pub chain_value: SignedDecimal,
+    // Amount staked multiplied by the rebase factor of the asset
+    pub rebase_adjusted_stake: Decimal,
+    // Same value that QueryEmissionsDistributions would return for the asset
+    pub distribution: SignedDecimal,
+    // Distribution normalized over the sum of all distributions
+    pub share: SignedDecimal,
+}
+
+#[cw_serde]
+pub struct ChainEmissionsBreakdown {
+    pub chain_id: ChainId,
+    // Yearly USD value of the native tokens distributed to the LUNA alliances
+    pub tokens_distributed_value: Decimal,
+    // Yearly USD value of the LUNA taken by the alliances take rate
+    pub take_rate_cost: Decimal,
+    // tokens_distributed_value minus take_rate_cost
+    pub chain_value: SignedDecimal,
+    // Weight given to the chain by the emissions strategy
+    // which is split between the assets of the chain
+    pub chain_weight: SignedDecimal,
+    pub assets: Vec<AssetEmissionsBreakdown>,
+}
+
+#[cw_serde]
+pub struct AssetEmissionsBreakdown {
+    pub denom: String,
+    // Rebase factor of the asset, 1 when the asset is unknown to the oracle
+    pub rebase_factor: Decimal,
+    pub staked: Uint128,
+    pub rebase_adjusted_stake: Decimal,
+    pub distribution: SignedDecimal,
+}
+
 #[cw_serde]
 #[derive(QueryResponses)]
 pub enum QueryMsg {
     #[returns(Config)]
     QueryConfig {},
+    #[returns(Addr)]
+    QueryGovernance {},
     #[returns(LunaInfo)]
     QueryLunaInfo {},
     #[returns(ChainInfo)]
@@ -157,7 +284,22 @@ pub enum QueryMsg {
     QueryChainsInfoUnsafe {},
     #[returns(Vec<EmissionsDistribution>)]
     QueryEmissionsDistributions(HashMap<ChainId, Vec<AssetStaked>>),
+    // Runs the emissions distribution algorithm on the stored data
+    // replaced by the optional chains_info overrides, without
+    // persisting anything.
+    #[returns(Vec<SimulatedEmissionsDistribution>)]
+    QuerySimulateEmissionsDistributions {
+        chains_info: Option<ChainsInfo>,
+        assets_staked: HashMap<ChainId, Vec<AssetStaked>>,
+    },
+    // Same as QueryEmissionsDistributions including the
+    // intermediate values used to compute each distribution.
+    #[returns(Vec<ChainEmissionsBreakdown>)]
+    QueryEmissionsDistributionsVerbose(HashMap<ChainId, Vec<AssetStaked>>),
 }
 
 #[cw_serde]
-pub struct MigrateMsg {}
+pub struct MigrateMsg {
+    // Required when migrating from a version without governance
+    pub governance_addr: Option<String>,
+}
@@ -1,4 +1,7 @@
-use cosmwasm_std::{Decimal, DecimalRangeExceeded, StdError};
+use cosmwasm_std::{
+    ConversionOverflowError, Decimal, DecimalRangeExceeded, OverflowError, StdError, Timestamp,
+    Uint128,
+};
 use thiserror::Error;
 
 #[derive(Error, Debug, PartialEq)]
@@ -9,6 +12,12 @@ pub enum ContractError {
     #[error("{0}")]
     DecimalRangeExceeded(#[from] DecimalRangeExceeded),
 
+    #[error("{0}")]
+    Overflow(#[from] OverflowError),
+
+    #[error("{0}")]
+    ConversionOverflow(#[from] ConversionOverflowError),
+
     #[error("Unauthorized")]
     Unauthorized {},
 
@@ -42,4 +51,101 @@ pub enum ContractError {
 
     #[error("Asset not staked")]
     AssetNotStaked {},
+
+    #[error("Invalid emissions strategy: {0}")]
+    InvalidEmissionsStrategy(String),
+
+    #[error("governance_addr is required to migrate the config")]
+    MissingGovernanceAddr {},
+
+    #[error("Data expired, update: {update}, expiry: {expiry}, current_blocktime: {now}")]
+    DataExpired {
+        update: Timestamp,
+        expiry: Timestamp,
+        now: Timestamp,
+    },
+
+    #[error("Chain not available by id: {chain_id}")]
+    ChainNotFound { chain_id: String },
+
+    #[error("Cannot convert {value} to a decimal")]
+    DecimalConversion { value: String },
+
+    #[error("Negative distribution for denom '{denom}'")]
+    NegativeDistribution { denom: String },
+
+    #[error("Cannot parse the token creation reply")]
+    TokenCreationReplyParse {},
+
+    #[error("Token creation failed: {0}")]
+    TokenCreationFailed(String),
+
+    #[error("Alliance token denom is not set")]
+    AllianceTokenDenomNotSet {},
+
+    #[error("Alliance token denom is already set")]
+    AllianceTokenDenomAlreadySet {},
+
+    #[error("Invalid alliance token denom '{0}'")]
+    InvalidAllianceTokenDenom(String),
+
+    #[error("Invalid delegation strategy: {0}")]
+    InvalidDelegationStrategy(String),
+
+    #[error("Delegation strategy is not set")]
+    DelegationStrategyNotSet {},
+
+    #[error("Invalid validator address '{0}'")]
+    InvalidValidatorAddress(String),
+
+    #[error("Validator '{0}' does not exist or is not bonded")]
+    ValidatorNotBonded(String),
+
+    #[error("Validator '{0}' is not allowed")]
+    ValidatorNotAllowed(String),
+
+    #[error("Only {delegated} alliance tokens are delegated to validator '{validator}'")]
+    InsufficientDelegation {
+        validator: String,
+        delegated: Uint128,
+    },
+
+    #[error("Alliance token delegations are already tracked")]
+    DelegationsAlreadyTracked {},
+
+    #[error("Dust recipient is not set")]
+    DustRecipientNotSet {},
+
+    #[error("Invalid take rate '{0}', the maximum is '{1}'")]
+    InvalidTakeRate(Decimal, Decimal),
+
+    #[error("Invalid lock tiers: {0}")]
+    InvalidLockTiers(String),
+
+    #[error("Lock tier of {0} seconds not found")]
+    LockTierNotFound(u64),
+
+    #[error("Locked position not found")]
+    LockedPositionNotFound {},
+
+    #[error("Position is locked until {0}")]
+    PositionLocked(Timestamp),
+
+    #[error("Insufficient allowance")]
+    InsufficientAllowance {},
+
+    #[error("Receipt denom of '{0}' is not set")]
+    ReceiptDenomNotSet(String),
+
+    #[error("Receipt denom of '{0}' is already set")]
+    ReceiptDenomAlreadySet(String),
+
+    #[error("Insufficient rewards to buy into the receipt rewards, {missing} more are needed")]
+    InsufficientRewards { missing: Uint128 },
+
+    #[error("Only '{0}' can be sent to buy into the rewards owed to the receipts")]
+    InvalidBuyInFunds(String),
+
+    #[error("Invalid CW20 hook message: {0}")]
+    InvalidCw20HookMsg(String),
 }
@@ -2,4 +2,5 @@ pub mod alliance_oracle_types;
 pub mod alliance_protocol;
 pub mod error;
 pub mod signed_decimal;
+pub mod signed_decimal_256;
 pub mod token_factory;
@@ -1,9 +1,15 @@
-use cosmwasm_std::{Decimal, DecimalRangeExceeded, StdError, StdResult, Uint128};
+use cosmwasm_std::{
+    ConversionOverflowError, Decimal, DecimalRangeExceeded, DivideByZeroError, Int256,
+    OverflowError, OverflowOperation, StdError, StdResult, Uint128,
+};
 use schemars::JsonSchema;
 use serde::{de, ser, Deserialize, Deserializer, Serialize};
+use std::cmp::Ordering;
+use std::convert::TryFrom;
 use std::fmt;
 use std::fmt::Write;
-use std::ops::{Add, AddAssign, Div, Mul, Sub};
+use std::iter::Sum;
+use std::ops::{Add, AddAssign, Div, DivAssign, Mul, MulAssign, Neg, Sub, SubAssign};
 use std::str::FromStr;
 
 #[derive(Copy, Clone, Default, PartialEq, Eq, PartialOrd, Ord, JsonSchema)]
@@ -13,397 +19,1043 @@ pub enum Sign {
     Negative,
 }
 
-#[derive(Copy, Clone, Default, PartialEq, Eq, PartialOrd, Ord, JsonSchema)]
-pub struct SignedDecimal {
-    value: Decimal,
-    sign: Sign,
-}
+impl Sign {
+    pub(crate) fn flip(self) -> Self {
+        match self {
+            Sign::Positive => Sign::Negative,
+            Sign::Negative => Sign::Positive,
+        }
+    }
 
-impl SignedDecimal {
-    pub fn from_atomics(
-        atomics: impl Into<Uint128>,
-        decimal_places: u32,
-        sign: Sign,
-    ) -> Result<Self, DecimalRangeExceeded> {
-        let value = Decimal::from_atomics(atomics.into(), decimal_places)?;
-        Ok(Self { value, sign })
+    pub(crate) fn mul(self, rhs: Self) -> Self {
+        if self == rhs {
+            Sign::Positive
+        } else {
+            Sign::Negative
+        }
     }
+}
+
+// Implements a signed decimal named $name on top of the unsigned $decimal,
+// whose atomics are $uint and whose range error is $range_exceeded.
+// $to_int256 converts the integer part of $decimal to an Int256. The items
+// used by the implementation are expected to be imported by the caller, as
+// in this module.
+macro_rules! impl_signed_decimal {
+    ($name:ident, $decimal:ident, $range_exceeded:ident, $uint:ident, $to_int256:path) => {
+        // Zero is always stored with a positive sign so that
+        // equality and ordering can rely on the (value, sign) pair.
+        #[derive(Copy, Clone, Default, PartialEq, Eq, JsonSchema)]
+        pub struct $name {
+            value: $decimal,
+            sign: Sign,
+        }
 
-    pub fn from_decimal(decimal: Decimal, sign: Sign) -> Self {
-        if decimal.is_zero() {
-            return Self {
-                value: Decimal::zero(),
+        impl $name {
+            pub const MAX: Self = Self {
+                value: $decimal::MAX,
                 sign: Sign::Positive,
             };
+            pub const MIN: Self = Self {
+                value: $decimal::MAX,
+                sign: Sign::Negative,
+            };
+
+            pub fn from_atomics(
+                atomics: impl Into<$uint>,
+                decimal_places: u32,
+                sign: Sign,
+            ) -> Result<Self, $range_exceeded> {
+                let value = $decimal::from_atomics(atomics.into(), decimal_places)?;
+                Ok(Self::from_decimal(value, sign))
+            }
+
+            pub fn from_decimal(decimal: $decimal, sign: Sign) -> Self {
+                if decimal.is_zero() {
+                    return Self {
+                        value: $decimal::zero(),
+                        sign: Sign::Positive,
+                    };
+                }
+                Self {
+                    value: decimal,
+                    sign,
+                }
+            }
+
+            pub fn is_positive(&self) -> bool {
+                match self.sign {
+                    Sign::Positive => true,
+                    Sign::Negative => false,
+                }
+            }
+
+            pub fn is_negative(&self) -> bool {
+                match self.sign {
+                    Sign::Positive => false,
+                    Sign::Negative => true,
+                }
+            }
+
+            pub fn is_zero(&self) -> bool {
+                self.value.is_zero()
+            }
+
+            pub fn zero() -> Self {
+                Self {
+                    value: $decimal::zero(),
+                    sign: Sign::Positive,
+                }
+            }
+
+            pub fn one() -> Self {
+                Self {
+                    value: $decimal::one(),
+                    sign: Sign::Positive,
+                }
+            }
+
+            pub fn sign(&self) -> Sign {
+                self.sign
+            }
+
+            pub fn abs(&self) -> Self {
+                Self::from_decimal(self.value, Sign::Positive)
+            }
+
+            // Absolute value as an unsigned decimal
+            pub fn unsigned_abs(&self) -> $decimal {
+                self.value
+            }
+
+            pub fn to_decimal(&self) -> StdResult<$decimal> {
+                if self.is_negative() {
+                    Err(StdError::generic_err("negative decimal"))
+                } else {
+                    Ok(self.value)
+                }
+            }
+
+            pub fn checked_add(self, rhs: Self) -> StdResult<Self> {
+                if self.sign == rhs.sign {
+                    let value = self
+                        .value
+                        .checked_add(rhs.value)
+                        .map_err(|_| overflow(OverflowOperation::Add, self, rhs))?;
+                    return Ok(Self::from_decimal(value, self.sign));
+                }
+                // Different signs can never overflow, the result has
+                // the sign of the operand with the largest magnitude
+                if self.value >= rhs.value {
+                    Ok(Self::from_decimal(self.value - rhs.value, self.sign))
+                } else {
+                    Ok(Self::from_decimal(rhs.value - self.value, rhs.sign))
+                }
+            }
+
+            pub fn checked_sub(self, rhs: Self) -> StdResult<Self> {
+                self.checked_add(-rhs)
+                    .map_err(|_| overflow(OverflowOperation::Sub, self, rhs))
+            }
+
+            pub fn checked_mul(self, rhs: Self) -> StdResult<Self> {
+                let value = self
+                    .value
+                    .checked_mul(rhs.value)
+                    .map_err(|_| overflow(OverflowOperation::Mul, self, rhs))?;
+                Ok(Self::from_decimal(value, self.sign.mul(rhs.sign)))
+            }
+
+            pub fn checked_div(self, rhs: Self) -> StdResult<Self> {
+                if rhs.is_zero() {
+                    return Err(StdError::divide_by_zero(DivideByZeroError::new(self)));
+                }
+                let value = self.value.checked_div(rhs.value).map_err(|e| {
+                    StdError::generic_err(format!("Cannot divide {} by {}: {}", self, rhs, e))
+                })?;
+                Ok(Self::from_decimal(value, self.sign.mul(rhs.sign)))
+            }
+
+            pub fn checked_pow(self, exp: u32) -> StdResult<Self> {
+                let value = Self::checked_pow_abs(self.value, exp)
+                    .ok_or_else(|| overflow(OverflowOperation::Pow, self, exp))?;
+                Ok(Self::from_decimal(value, self.pow_sign(exp)))
+            }
+
+            pub fn saturating_add(self, rhs: Self) -> Self {
+                self.checked_add(rhs)
+                    .unwrap_or_else(|_| Self::saturated(self.sign))
+            }
+
+            pub fn saturating_sub(self, rhs: Self) -> Self {
+                self.checked_sub(rhs)
+                    .unwrap_or_else(|_| Self::saturated(self.sign))
+            }
+
+            pub fn saturating_mul(self, rhs: Self) -> Self {
+                self.checked_mul(rhs)
+                    .unwrap_or_else(|_| Self::saturated(self.sign.mul(rhs.sign)))
+            }
+
+            pub fn saturating_pow(self, exp: u32) -> Self {
+                self.checked_pow(exp)
+                    .unwrap_or_else(|_| Self::saturated(self.pow_sign(exp)))
+            }
+
+            pub fn pow(self, exp: u32) -> Self {
+                self.checked_pow(exp).unwrap()
+            }
+
+            // Rounds towards negative infinity
+            pub fn checked_floor(&self) -> StdResult<Self> {
+                match self.sign {
+                    Sign::Positive => Ok(Self::from_decimal(self.value.floor(), Sign::Positive)),
+                    Sign::Negative => Ok(Self::from_decimal(
+                        self.value
+                            .checked_ceil()
+                            .map_err(|e| StdError::generic_err(e.to_string()))?,
+                        Sign::Negative,
+                    )),
+                }
+            }
+
+            // Rounds towards positive infinity
+            pub fn checked_ceil(&self) -> StdResult<Self> {
+                match self.sign {
+                    Sign::Positive => Ok(Self::from_decimal(
+                        self.value
+                            .checked_ceil()
+                            .map_err(|e| StdError::generic_err(e.to_string()))?,
+                        Sign::Positive,
+                    )),
+                    Sign::Negative => Ok(Self::from_decimal(self.value.floor(), Sign::Negative)),
+                }
+            }
+
+            pub fn floor(&self) -> Self {
+                self.checked_floor().unwrap()
+            }
+
+            pub fn ceil(&self) -> Self {
+                self.checked_ceil().unwrap()
+            }
+
+            // Converts to an integer rounding towards negative infinity
+            pub fn to_int_floor(&self) -> Int256 {
+                match self.sign {
+                    Sign::Positive => $to_int256(self.value.to_uint_floor()),
+                    Sign::Negative => -$to_int256(self.value.to_uint_ceil()),
+                }
+            }
+
+            // Converts to an integer rounding towards positive infinity
+            pub fn to_int_ceil(&self) -> Int256 {
+                match self.sign {
+                    Sign::Positive => $to_int256(self.value.to_uint_ceil()),
+                    Sign::Negative => -$to_int256(self.value.to_uint_floor()),
+                }
+            }
+
+            // Converts to an integer rounding towards zero
+            pub fn to_int_trunc(&self) -> Int256 {
+                match self.sign {
+                    Sign::Positive => $to_int256(self.value.to_uint_floor()),
+                    Sign::Negative => -$to_int256(self.value.to_uint_floor()),
+                }
+            }
+
+            fn pow_sign(&self, exp: u32) -> Sign {
+                if exp % 2 == 1 {
+                    self.sign
+                } else {
+                    Sign::Positive
+                }
+            }
+
+            fn saturated(sign: Sign) -> Self {
+                match sign {
+                    Sign::Positive => Self::MAX,
+                    Sign::Negative => Self::MIN,
+                }
+            }
+
+            // Exponentiation by squaring over the magnitude. $decimal::checked_pow
+            // is not used because its last multiplication can panic on overflow.
+            fn checked_pow_abs(mut x: $decimal, mut n: u32) -> Option<$decimal> {
+                let mut y = $decimal::one();
+                while n > 1 {
+                    if n % 2 == 1 {
+                        y = x.checked_mul(y).ok()?;
+                    }
+                    x = x.checked_mul(x).ok()?;
+                    n /= 2;
+                }
+                if n == 0 {
+                    return Some(y);
+                }
+                x.checked_mul(y).ok()
+            }
+        }
+
+        impl From<$decimal> for $name {
+            fn from(value: $decimal) -> Self {
+                Self::from_decimal(value, Sign::Positive)
+            }
         }
-        Self {
-            value: decimal,
-            sign,
+
+        impl TryFrom<Int256> for $name {
+            type Error = ConversionOverflowError;
+
+            fn try_from(value: Int256) -> Result<Self, Self::Error> {
+                let err =
+                    || ConversionOverflowError::new("Int256", stringify!($name), value.to_string());
+                let atomics = $uint::try_from(value.unsigned_abs()).map_err(|_| err())?;
+                let value_abs = $decimal::from_atomics(atomics, 0).map_err(|_| err())?;
+                let sign = if value.is_negative() {
+                    Sign::Negative
+                } else {
+                    Sign::Positive
+                };
+                Ok(Self::from_decimal(value_abs, sign))
+            }
         }
-    }
 
-    pub fn is_positive(&self) -> bool {
-        match self.sign {
-            Sign::Positive => true,
-            Sign::Negative => false,
+        impl Ord for $name {
+            fn cmp(&self, other: &Self) -> Ordering {
+                match (self.sign, other.sign) {
+                    (Sign::Positive, Sign::Positive) => self.value.cmp(&other.value),
+                    (Sign::Negative, Sign::Negative) => other.value.cmp(&self.value),
+                    (Sign::Positive, Sign::Negative) => Ordering::Greater,
+                    (Sign::Negative, Sign::Positive) => Ordering::Less,
+                }
+            }
         }
-    }
 
-    pub fn is_negative(&self) -> bool {
-        match self.sign {
-            Sign::Positive => false,
-            Sign::Negative => true,
+        impl PartialOrd for $name {
+            fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+                Some(self.cmp(other))
+            }
         }
-    }
 
-    pub fn is_zero(&self) -> bool {
-        self.value.is_zero()
-    }
+        impl Neg for $name {
+            type Output = Self;
 
-    pub fn zero() -> Self {
-        Self {
-            value: Decimal::zero(),
-            sign: Sign::Positive,
+            fn neg(self) -> Self::Output {
+                Self::from_decimal(self.value, self.sign.flip())
+            }
         }
-    }
 
-    pub fn to_decimal(&self) -> StdResult<Decimal> {
-        if self.is_negative() {
-            Err(StdError::generic_err("negative decimal"))
-        } else {
-            Ok(self.value)
+        impl Add for $name {
+            type Output = Self;
+
+            fn add(self, rhs: Self) -> Self::Output {
+                self.checked_add(rhs).unwrap()
+            }
         }
-    }
-}
 
-impl Add for SignedDecimal {
-    type Output = Self;
+        impl Add<$decimal> for $name {
+            type Output = Self;
+            fn add(self, rhs: $decimal) -> Self::Output {
+                self + Self::from(rhs)
+            }
+        }
 
-    fn add(self, rhs: Self) -> Self::Output {
-        if self.is_negative() && rhs.is_negative() {
-            return Self::from_decimal(self.value + rhs.value, Sign::Negative);
+        impl AddAssign for $name {
+            fn add_assign(&mut self, rhs: $name) {
+                *self = *self + rhs;
+            }
         }
-        if self.is_negative() && rhs.is_positive() {
-            if self.value > rhs.value {
-                return Self::from_decimal(self.value - rhs.value, Sign::Negative);
-            } else {
-                return Self::from_decimal(rhs.value - self.value, Sign::Positive);
+
+        impl AddAssign<$decimal> for $name {
+            fn add_assign(&mut self, rhs: $decimal) {
+                *self = *self + rhs;
             }
         }
-        if self.is_positive() && rhs.is_negative() {
-            if self.value > rhs.value {
-                return Self::from_decimal(self.value - rhs.value, Sign::Positive);
-            } else {
-                return Self::from_decimal(rhs.value - self.value, Sign::Negative);
+
+        impl Sub for $name {
+            type Output = Self;
+
+            fn sub(self, rhs: Self) -> Self::Output {
+                self.checked_sub(rhs).unwrap()
             }
         }
-        Self::from_decimal(self.value + rhs.value, Sign::Positive)
-    }
-}
 
-impl Add<Decimal> for SignedDecimal {
-    type Output = Self;
-    fn add(self, rhs: Decimal) -> Self::Output {
-        self + Self::from_decimal(rhs, Sign::Positive)
-    }
-}
+        impl Sub<$decimal> for $name {
+            type Output = Self;
+            fn sub(self, rhs: $decimal) -> Self::Output {
+                self - Self::from(rhs)
+            }
+        }
 
-impl Sub<Decimal> for SignedDecimal {
-    type Output = Self;
-    fn sub(self, rhs: Decimal) -> Self::Output {
-        self - Self::from_decimal(rhs, Sign::Positive)
-    }
-}
+        impl SubAssign for $name {
+            fn sub_assign(&mut self, rhs: $name) {
+                *self = *self - rhs;
+            }
+        }
 
-impl AddAssign for SignedDecimal {
-    fn add_assign(&mut self, rhs: SignedDecimal) {
-        *self = *self + rhs;
-    }
-}
+        impl SubAssign<$decimal> for $name {
+            fn sub_assign(&mut self, rhs: $decimal) {
+                *self = *self - rhs;
+            }
+        }
 
-impl AddAssign<Decimal> for SignedDecimal {
-    fn add_assign(&mut self, rhs: Decimal) {
-        *self = *self + rhs;
-    }
-}
+        impl Mul for $name {
+            type Output = Self;
 
-impl Sub for SignedDecimal {
-    type Output = Self;
+            fn mul(self, rhs: Self) -> Self::Output {
+                self.checked_mul(rhs).unwrap()
+            }
+        }
 
-    fn sub(self, rhs: Self) -> Self::Output {
-        if self.is_negative() && rhs.is_negative() {
-            if self.value > rhs.value {
-                return Self::from_decimal(self.value - rhs.value, Sign::Negative);
-            } else {
-                return Self::from_decimal(rhs.value - self.value, Sign::Positive);
+        impl Mul<$decimal> for $name {
+            type Output = Self;
+            fn mul(self, rhs: $decimal) -> Self::Output {
+                self * Self::from(rhs)
             }
         }
-        if self.is_negative() && rhs.is_positive() {
-            return Self::from_decimal(self.value + rhs.value, Sign::Negative);
+
+        impl MulAssign for $name {
+            fn mul_assign(&mut self, rhs: $name) {
+                *self = *self * rhs;
+            }
         }
-        if self.is_positive() && rhs.is_negative() {
-            return Self::from_decimal(self.value + rhs.value, Sign::Positive);
+
+        impl MulAssign<$decimal> for $name {
+            fn mul_assign(&mut self, rhs: $decimal) {
+                *self = *self * rhs;
+            }
         }
-        if self.value > rhs.value {
-            Self::from_decimal(self.value - rhs.value, Sign::Positive)
-        } else {
-            Self::from_decimal(rhs.value - self.value, Sign::Negative)
+
+        impl Div for $name {
+            type Output = Self;
+
+            fn div(self, rhs: Self) -> Self::Output {
+                self.checked_div(rhs).unwrap()
+            }
         }
-    }
-}
 
-impl Mul for SignedDecimal {
-    type Output = Self;
+        impl Div<$decimal> for $name {
+            type Output = Self;
+            fn div(self, rhs: $decimal) -> Self::Output {
+                self / Self::from(rhs)
+            }
+        }
 
-    fn mul(self, rhs: Self) -> Self::Output {
-        if self.is_negative() && rhs.is_negative() {
-            return Self::from_decimal(self.value * rhs.value, Sign::Positive);
+        impl DivAssign for $name {
+            fn div_assign(&mut self, rhs: $name) {
+                *self = *self / rhs;
+            }
         }
-        if self.is_negative() && rhs.is_positive() {
-            return Self::from_decimal(self.value * rhs.value, Sign::Negative);
+
+        impl DivAssign<$decimal> for $name {
+            fn div_assign(&mut self, rhs: $decimal) {
+                *self = *self / rhs;
+            }
         }
-        if self.is_positive() && rhs.is_negative() {
-            return Self::from_decimal(self.value * rhs.value, Sign::Negative);
+
+        impl Sum for $name {
+            fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
+                iter.fold(Self::zero(), Add::add)
+            }
         }
-        Self::from_decimal(self.value * rhs.value, Sign::Positive)
-    }
-}
 
-impl Mul<Decimal> for SignedDecimal {
-    type Output = Self;
-    fn mul(self, rhs: Decimal) -> Self::Output {
-        self * Self::from_decimal(rhs, Sign::Positive)
-    }
-}
+        impl<'a> Sum<&'a $name> for $name {
+            fn sum<I: Iterator<Item = &'a Self>>(iter: I) -> Self {
+                iter.fold(Self::zero(), |acc, d| acc + *d)
+            }
+        }
 
-impl Div for SignedDecimal {
-    type Output = Self;
+        impl fmt::Debug for $name {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "{}({})", stringify!($name), self)
+            }
+        }
 
-    fn div(self, rhs: Self) -> Self::Output {
-        if self.is_negative() && rhs.is_negative() {
-            return Self::from_decimal(self.value / rhs.value, Sign::Positive);
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                if self.is_negative() && self.value != $decimal::zero() {
+                    f.write_char('-')?;
+                }
+                let _ = f.write_str(&self.value.to_string());
+                Ok(())
+            }
         }
-        if self.is_negative() && rhs.is_positive() {
-            return Self::from_decimal(self.value / rhs.value, Sign::Negative);
+
+        impl FromStr for $name {
+            type Err = StdError;
+
+            fn from_str(s: &str) -> Result<Self, Self::Err> {
+                let (sign, value) = match s.strip_prefix('-') {
+                    Some(value) => (Sign::Negative, value),
+                    None => (Sign::Positive, s),
+                };
+                Ok(Self::from_decimal($decimal::from_str(value)?, sign))
+            }
         }
-        if self.is_positive() && rhs.is_negative() {
-            return Self::from_decimal(self.value / rhs.value, Sign::Negative);
+
+        impl Serialize for $name {
+            fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+            where
+                S: ser::Serializer,
+            {
+                serializer.serialize_str(&self.to_string())
+            }
         }
-        Self::from_decimal(self.value / rhs.value, Sign::Positive)
-    }
-}
 
-impl Div<Decimal> for SignedDecimal {
-    type Output = Self;
-    fn div(self, rhs: Decimal) -> Self::Output {
-        self / Self::from_decimal(rhs, Sign::Positive)
-    }
-}
+        impl<'de> Deserialize<'de> for $name {
+            fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+            where
+                D: Deserializer<'de>,
+            {
+                deserializer.deserialize_str(DecimalVisitor)
+            }
+        }
 
-impl fmt::Debug for SignedDecimal {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "SignedDecimal({})", self)
-    }
-}
+        struct DecimalVisitor;
 
-impl fmt::Display for SignedDecimal {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        if self.is_negative() && self.value != Decimal::zero() {
-            f.write_char('-')?;
+        impl<'de> de::Visitor<'de> for DecimalVisitor {
+            type Value = $name;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("string-encoded decimal")
+            }
+
+            fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
+            where
+                E: de::Error,
+            {
+                match $name::from_str(v) {
+                    Ok(d) => Ok(d),
+                    Err(e) => Err(E::custom(format!("Error parsing decimal '{}': {}", v, e))),
+                }
+            }
         }
-        let _ = f.write_str(&self.value.to_string());
-        Ok(())
-    }
+    };
 }
 
-impl FromStr for SignedDecimal {
-    type Err = StdError;
+pub(crate) use impl_signed_decimal;
 
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let sign = if s.starts_with('-') {
-            Sign::Negative
-        } else {
-            Sign::Positive
-        };
-        let value = Decimal::from_str(s.trim_start_matches('-'))?;
-        Ok(Self { value, sign })
-    }
+pub(crate) fn overflow(
+    operation: OverflowOperation,
+    operand1: impl ToString,
+    operand2: impl ToString,
+) -> StdError {
+    StdError::overflow(OverflowError::new(operation, operand1, operand2))
 }
 
-impl Serialize for SignedDecimal {
-    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
-    where
-        S: ser::Serializer,
-    {
-        serializer.serialize_str(&self.to_string())
-    }
-}
+impl_signed_decimal!(
+    SignedDecimal,
+    Decimal,
+    DecimalRangeExceeded,
+    Uint128,
+    Int256::from
+);
 
-impl<'de> Deserialize<'de> for SignedDecimal {
-    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
-    where
-        D: Deserializer<'de>,
-    {
-        deserializer.deserialize_str(DecimalVisitor)
-    }
-}
+// Generates the tests shared by the signed decimals in a module named $mod.
+// $int holds the signed atomics of $name and is used as the reference for
+// the operations, $bits is the size of $uint.
+#[cfg(test)]
+macro_rules! impl_signed_decimal_tests {
+    ($mod:ident, $name:ident, $decimal:ident, $uint:ident, $int:ident, $bits:expr) => {
+        pub(crate) mod $mod {
+            use super::$name;
+            use crate::signed_decimal::Sign;
+            use cosmwasm_std::$int as SignedAtomics;
+            use cosmwasm_std::{
+                $decimal, $uint, ConversionOverflowError, Int256, OverflowError, OverflowOperation,
+                StdError,
+            };
+            use std::cmp::Ordering;
+            use std::convert::TryFrom;
+            use std::str::FromStr;
 
-struct DecimalVisitor;
+            const BITS: u32 = $bits;
+            const SAMPLES: usize = 2000;
 
-impl<'de> de::Visitor<'de> for DecimalVisitor {
-    type Value = SignedDecimal;
+            // Small deterministic generator, so failures can be reproduced
+            struct Lcg(u64);
 
-    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
-        formatter.write_str("string-encoded decimal")
-    }
+            impl Lcg {
+                fn next_u64(&mut self) -> u64 {
+                    self.0 = self
+                        .0
+                        .wrapping_mul(6364136223846793005)
+                        .wrapping_add(1442695040888963407);
+                    self.0
+                }
 
-    fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
-    where
-        E: de::Error,
-    {
-        match SignedDecimal::from_str(v) {
-            Ok(d) => Ok(d),
-            Err(e) => Err(E::custom(format!("Error parsing decimal '{}': {}", v, e))),
-        }
-    }
-}
+                // Random magnitudes across the whole range, from dust to $decimal::MAX
+                fn next_decimal(&mut self) -> $name {
+                    let mut atomics = $uint::zero();
+                    for _ in 0..BITS / 64 {
+                        atomics = (atomics << 64) + $uint::from(self.next_u64());
+                    }
+                    let atomics = atomics >> (self.next_u64() % BITS as u64) as u32;
+                    let sign = if self.next_u64() % 2 == 1 {
+                        Sign::Negative
+                    } else {
+                        Sign::Positive
+                    };
+                    $name::from_atomics(atomics, 18, sign).unwrap()
+                }
+            }
 
-#[cfg(test)]
-mod test {
-    use crate::signed_decimal::{Sign, SignedDecimal};
-    use cosmwasm_std::Decimal;
-    use std::str::FromStr;
-
-    #[test]
-    fn test_from_to_str() {
-        let test_cases = [
-            (
-                SignedDecimal::from_decimal(Decimal::zero(), Sign::Positive),
-                "0",
-            ),
-            (
-                SignedDecimal::from_decimal(Decimal::from_str("1.1").unwrap(), Sign::Negative),
-                "-1.1",
-            ),
-            (
-                SignedDecimal::from_decimal(Decimal::from_str("1.1").unwrap(), Sign::Positive),
-                "1.1",
-            ),
-        ];
-        for (input, expected) in test_cases.iter() {
-            assert_eq!(input.to_string(), *expected);
-            assert_eq!(SignedDecimal::from_str(expected).unwrap(), *input);
-        }
-    }
+            pub(crate) fn samples() -> Vec<($name, $name)> {
+                let mut rng = Lcg(42);
+                let mut samples: Vec<($name, $name)> = (0..SAMPLES)
+                    .map(|_| (rng.next_decimal(), rng.next_decimal()))
+                    .collect();
+                let edges = [
+                    $name::zero(),
+                    $name::one(),
+                    -$name::one(),
+                    $name::MAX,
+                    $name::MIN,
+                ];
+                for a in edges {
+                    for b in edges {
+                        samples.push((a, b));
+                    }
+                }
+                samples
+            }
 
-    #[test]
-    fn test_add_operations() {
-        let test_cases = vec![
-            ("1.0", "1.0", "2.0"),
-            ("1.0", "-1.0", "0.0"),
-            ("-1.0", "1.0", "0.0"),
-            ("-1.0", "-1.0", "-2.0"),
-            ("1.1", "1.1", "2.2"),
-        ];
-        for test_case in test_cases {
-            assert_eq!(
-                SignedDecimal::from_str(test_case.0).unwrap()
-                    + SignedDecimal::from_str(test_case.1).unwrap(),
-                SignedDecimal::from_str(test_case.2).unwrap()
-            );
-        }
-
-        let test_cases_for_decimal = vec![
-            ("1.0", "1.0", "2.0"),
-            ("-1.0", "1.0", "0.0"),
-            ("1.1", "1.1", "2.2"),
-        ];
-        for test_case in test_cases_for_decimal {
-            assert_eq!(
-                SignedDecimal::from_str(test_case.0).unwrap()
-                    + Decimal::from_str(test_case.1).unwrap(),
-                SignedDecimal::from_str(test_case.2).unwrap()
-            );
-        }
-    }
+            // Reference representation: the signed amount of atomics
+            fn to_atomics(d: $name) -> SignedAtomics {
+                let atomics = SignedAtomics::from(d.unsigned_abs().atomics());
+                if d.is_negative() {
+                    -atomics
+                } else {
+                    atomics
+                }
+            }
 
-    #[test]
-    fn test_sub_operations() {
-        let test_cases = vec![
-            ("1.0", "1.0", "0.0"),
-            ("1.0", "-1.0", "2.0"),
-            ("-1.0", "1.0", "-2.0"),
-            ("-1.0", "-1.0", "0.0"),
-            ("1.1", "1.1", "0.0"),
-        ];
-        for test_case in test_cases {
-            assert_eq!(
-                SignedDecimal::from_str(test_case.0).unwrap()
-                    - SignedDecimal::from_str(test_case.1).unwrap(),
-                SignedDecimal::from_str(test_case.2).unwrap()
-            );
-        }
-
-        let test_cases_for_decimal = vec![
-            ("1.0", "1.0", "0.0"),
-            ("-1.0", "1.0", "-2.0"),
-            ("1.1", "1.1", "0.0"),
-        ];
-        for test_case in test_cases_for_decimal {
-            assert_eq!(
-                SignedDecimal::from_str(test_case.0).unwrap()
-                    - Decimal::from_str(test_case.1).unwrap(),
-                SignedDecimal::from_str(test_case.2).unwrap()
-            );
-        }
-    }
+            fn from_atomics(atomics: SignedAtomics) -> Option<$name> {
+                let abs = $uint::try_from(atomics.unsigned_abs()).ok()?;
+                let sign = if atomics.is_negative() {
+                    Sign::Negative
+                } else {
+                    Sign::Positive
+                };
+                Some($name::from_atomics(abs, 18, sign).unwrap())
+            }
 
-    #[test]
-    fn test_mul_operations() {
-        let test_cases = vec![
-            ("1.0", "1.0", "1.0"),
-            ("1.0", "-1.0", "-1.0"),
-            ("-1.0", "1.0", "-1.0"),
-            ("-1.0", "-1.0", "1.0"),
-            ("1.1", "1.1", "1.21"),
-        ];
-        for test_case in test_cases {
-            assert_eq!(
-                SignedDecimal::from_str(test_case.0).unwrap()
-                    * SignedDecimal::from_str(test_case.1).unwrap(),
-                SignedDecimal::from_str(test_case.2).unwrap()
-            );
-        }
-
-        let test_cases_for_decimal = vec![
-            ("1.0", "1.0", "1.0"),
-            ("-1.0", "1.0", "-1.0"),
-            ("1.1", "1.1", "1.21"),
-        ];
-        for test_case in test_cases_for_decimal {
-            assert_eq!(
-                SignedDecimal::from_str(test_case.0).unwrap()
-                    * Decimal::from_str(test_case.1).unwrap(),
-                SignedDecimal::from_str(test_case.2).unwrap()
-            );
-        }
-    }
+            fn fractional() -> SignedAtomics {
+                SignedAtomics::from($decimal::one().atomics())
+            }
+
+            #[test]
+            fn test_ordering_by_value() {
+                let neg_five = $name::from_str("-5").unwrap();
+                let three = $name::from_str("3").unwrap();
+                assert!(neg_five < three);
+                assert!($name::from_str("-5").unwrap() < $name::from_str("-3").unwrap());
+                assert_eq!(
+                    $name::from_str("-0").unwrap().cmp(&$name::zero()),
+                    Ordering::Equal
+                );
+                assert_eq!(
+                    vec![three, neg_five, $name::zero()].into_iter().max(),
+                    Some(three)
+                );
+
+                for (a, b) in samples() {
+                    assert_eq!(a.cmp(&b), to_atomics(a).cmp(&to_atomics(b)), "{} {}", a, b);
+                }
+            }
+
+            #[test]
+            fn test_negative_zero_is_normalized() {
+                let zero = $name::zero();
+                assert_eq!($name::from_str("-0").unwrap(), zero);
+                assert_eq!($name::from_str("-0.000").unwrap(), zero);
+                assert_eq!(
+                    $name::from_atomics(0u128, 18, Sign::Negative).unwrap(),
+                    zero
+                );
+                assert_eq!(-zero, zero);
+                assert!(!(-zero).is_negative());
+                assert!($name::from_str("--1").is_err());
+            }
+
+            #[test]
+            fn test_checked_add_sub_properties() {
+                for (a, b) in samples() {
+                    let expected = from_atomics(to_atomics(a) + to_atomics(b));
+                    assert_eq!(a.checked_add(b).ok(), expected, "{} + {}", a, b);
+                    assert_eq!(b.checked_add(a).ok(), expected, "{} + {}", b, a);
 
-    #[test]
-    fn test_div_operations() {
-        let test_cases = vec![
-            ("1.0", "1.0", "1.0"),
-            ("1.0", "-1.0", "-1.0"),
-            ("-1.0", "1.0", "-1.0"),
-            ("-1.0", "-1.0", "1.0"),
-            ("1.1", "1.1", "1.0"),
-        ];
-        for test_case in test_cases {
-            assert_eq!(
-                SignedDecimal::from_str(test_case.0).unwrap()
-                    / SignedDecimal::from_str(test_case.1).unwrap(),
-                SignedDecimal::from_str(test_case.2).unwrap()
-            );
+                    let expected = from_atomics(to_atomics(a) - to_atomics(b));
+                    assert_eq!(a.checked_sub(b).ok(), expected, "{} - {}", a, b);
+
+                    assert_eq!(a.checked_sub(a).unwrap(), $name::zero());
+                    assert_eq!(a.checked_add(-a).unwrap(), $name::zero());
+                }
+
+                assert_eq!(
+                    $name::MAX.checked_add($name::one()),
+                    Err(StdError::overflow(OverflowError::new(
+                        OverflowOperation::Add,
+                        $name::MAX,
+                        $name::one()
+                    )))
+                );
+                assert_eq!(
+                    $name::MIN.checked_sub($name::one()),
+                    Err(StdError::overflow(OverflowError::new(
+                        OverflowOperation::Sub,
+                        $name::MIN,
+                        $name::one()
+                    )))
+                );
+            }
+
+            #[test]
+            fn test_checked_mul_properties() {
+                for (a, b) in samples() {
+                    // $decimal multiplication truncates the fractional part, a product
+                    // that does not fit in the atomics is far beyond the maximum
+                    let expected = to_atomics(a)
+                        .checked_mul(to_atomics(b))
+                        .ok()
+                        .and_then(|product| from_atomics(product / fractional()));
+                    assert_eq!(a.checked_mul(b).ok(), expected, "{} * {}", a, b);
+                    assert_eq!(b.checked_mul(a).ok(), expected, "{} * {}", b, a);
+                    assert_eq!(a.checked_mul($name::one()).unwrap(), a);
+                    assert_eq!(a.checked_mul(-$name::one()).unwrap(), -a);
+                }
+
+                assert_eq!(
+                    $name::MIN.checked_mul($name::MAX),
+                    Err(StdError::overflow(OverflowError::new(
+                        OverflowOperation::Mul,
+                        $name::MIN,
+                        $name::MAX
+                    )))
+                );
+            }
+
+            #[test]
+            fn test_checked_div_properties() {
+                for (a, b) in samples() {
+                    if b.is_zero() {
+                        assert!(matches!(
+                            a.checked_div(b),
+                            Err(StdError::DivideByZero { .. })
+                        ));
+                        continue;
+                    }
+                    let expected = from_atomics(to_atomics(a) * fractional() / to_atomics(b));
+                    assert_eq!(a.checked_div(b).ok(), expected, "{} / {}", a, b);
+                    assert_eq!(a.checked_div($name::one()).unwrap(), a);
+                }
+            }
+
+            #[test]
+            fn test_checked_pow_properties() {
+                let mut rng = Lcg(7);
+                for (a, _) in samples() {
+                    let exp = (rng.next_u64() % 4) as u32;
+                    // Up to the third power squaring and repeated multiplication
+                    // round the same way
+                    let expected = (0..exp).try_fold($name::one(), |acc, _| acc.checked_mul(a));
+                    assert_eq!(a.checked_pow(exp).ok(), expected.ok(), "{} ^ {}", a, exp);
+                    assert_eq!(a.checked_pow(0).unwrap(), $name::one());
+                    assert_eq!(a.checked_pow(1).unwrap(), a);
+                    if let Ok(res) = a.checked_pow(2) {
+                        assert!(!res.is_negative());
+                    }
+                }
+
+                let neg_two = $name::from_str("-2").unwrap();
+                assert_eq!(neg_two.pow(3), $name::from_str("-8").unwrap());
+                assert_eq!(neg_two.pow(4), $name::from_str("16").unwrap());
+                assert_eq!(neg_two.pow(67), -$name::from_str("2").unwrap().pow(67));
+                assert_eq!(
+                    $name::MIN.checked_pow(3),
+                    Err(StdError::overflow(OverflowError::new(
+                        OverflowOperation::Pow,
+                        $name::MIN,
+                        3
+                    )))
+                );
+            }
+
+            #[test]
+            fn test_saturating_properties() {
+                for (a, b) in samples() {
+                    let saturate = |res: Option<$name>, negative: bool| match res {
+                        Some(res) => res,
+                        None if negative => $name::MIN,
+                        None => $name::MAX,
+                    };
+                    assert_eq!(
+                        a.saturating_add(b),
+                        saturate(a.checked_add(b).ok(), a.is_negative())
+                    );
+                    assert_eq!(
+                        a.saturating_sub(b),
+                        saturate(a.checked_sub(b).ok(), a.is_negative())
+                    );
+                    assert_eq!(
+                        a.saturating_mul(b),
+                        saturate(a.checked_mul(b).ok(), a.is_negative() != b.is_negative())
+                    );
+                    assert_eq!(
+                        a.saturating_pow(3),
+                        saturate(a.checked_pow(3).ok(), a.is_negative())
+                    );
+                    assert_eq!(a.saturating_pow(2), saturate(a.checked_pow(2).ok(), false));
+                }
+            }
+
+            #[test]
+            fn test_neg_and_abs_properties() {
+                for (a, _) in samples() {
+                    assert_eq!(-(-a), a);
+                    assert_eq!(to_atomics(-a), -to_atomics(a));
+                    assert_eq!(a.abs(), if a.is_negative() { -a } else { a });
+                    assert!(!a.abs().is_negative());
+                    assert_eq!(a.abs().unsigned_abs(), a.unsigned_abs());
+                }
+            }
+
+            #[test]
+            fn test_floor_ceil_properties() {
+                let cases = [
+                    ("1.5", "1", "2"),
+                    ("-1.5", "-2", "-1"),
+                    ("-1", "-1", "-1"),
+                    ("0.1", "0", "1"),
+                    ("-0.1", "-1", "0"),
+                    ("0", "0", "0"),
+                ];
+                for (value, floor, ceil) in cases {
+                    let value = $name::from_str(value).unwrap();
+                    assert_eq!(value.floor(), $name::from_str(floor).unwrap());
+                    assert_eq!(value.ceil(), $name::from_str(ceil).unwrap());
+                }
+
+                for (a, _) in samples() {
+                    let expected_floor = to_atomics(a).checked_div_euclid(fractional()).unwrap();
+                    assert_eq!(
+                        SignedAtomics::from(a.to_int_floor()),
+                        expected_floor,
+                        "{}",
+                        a
+                    );
+                    assert_eq!(
+                        SignedAtomics::from(a.to_int_trunc()),
+                        to_atomics(a) / fractional(),
+                        "{}",
+                        a
+                    );
+                    let expected_ceil = if to_atomics(a) % fractional() == SignedAtomics::zero() {
+                        expected_floor
+                    } else {
+                        expected_floor + SignedAtomics::one()
+                    };
+                    assert_eq!(SignedAtomics::from(a.to_int_ceil()), expected_ceil, "{}", a);
+
+                    match a.checked_floor() {
+                        Ok(floor) => {
+                            assert!(floor <= a);
+                            assert_eq!(floor, $name::try_from(a.to_int_floor()).unwrap());
+                        }
+                        Err(_) => assert!(a < $name::MIN.ceil()),
+                    }
+                    match a.checked_ceil() {
+                        Ok(ceil) => {
+                            assert!(ceil >= a);
+                            assert_eq!(ceil, $name::try_from(a.to_int_ceil()).unwrap());
+                        }
+                        Err(_) => assert!(a > $name::MAX.floor()),
+                    }
+                }
+            }
+
+            #[test]
+            fn test_int256_conversions() {
+                assert_eq!(
+                    $name::try_from(Int256::from(-42i128)).unwrap(),
+                    $name::from_str("-42").unwrap()
+                );
+                assert_eq!($name::try_from(Int256::zero()).unwrap(), $name::zero());
+                let too_big = $name::MAX.to_int_ceil();
+                assert_eq!(
+                    $name::try_from(too_big),
+                    Err(ConversionOverflowError::new(
+                        "Int256",
+                        stringify!($name),
+                        too_big.to_string()
+                    ))
+                );
+                assert!($name::try_from(-too_big).is_err());
+
+                let mut rng = Lcg(1);
+                for _ in 0..SAMPLES {
+                    let int = Int256::from((rng.next_u64() as i64) as i128);
+                    assert_eq!($name::try_from(int).unwrap().to_int_trunc(), int);
+                }
+            }
+
+            #[test]
+            fn test_sum() {
+                let values: Vec<$name> = ["1.5", "-3", "0.25", "-0.75"]
+                    .iter()
+                    .map(|v| $name::from_str(v).unwrap())
+                    .collect();
+                let expected = $name::from_str("-2").unwrap();
+                assert_eq!(values.iter().sum::<$name>(), expected);
+                assert_eq!(values.into_iter().sum::<$name>(), expected);
+                assert_eq!(
+                    Vec::<$name>::new().into_iter().sum::<$name>(),
+                    $name::zero()
+                );
+            }
+
+            #[test]
+            fn test_string_round_trip() {
+                for (a, _) in samples() {
+                    assert_eq!($name::from_str(&a.to_string()).unwrap(), a);
+                }
+            }
+
+            #[test]
+            fn test_from_to_str() {
+                let test_cases = [
+                    ($name::from_decimal($decimal::zero(), Sign::Positive), "0"),
+                    (
+                        $name::from_decimal($decimal::from_str("1.1").unwrap(), Sign::Negative),
+                        "-1.1",
+                    ),
+                    (
+                        $name::from_decimal($decimal::from_str("1.1").unwrap(), Sign::Positive),
+                        "1.1",
+                    ),
+                ];
+                for (input, expected) in test_cases.iter() {
+                    assert_eq!(input.to_string(), *expected);
+                    assert_eq!($name::from_str(expected).unwrap(), *input);
+                }
+            }
+
+            #[test]
+            fn test_add_operations() {
+                let test_cases = vec![
+                    ("1.0", "1.0", "2.0"),
+                    ("1.0", "-1.0", "0.0"),
+                    ("-1.0", "1.0", "0.0"),
+                    ("-1.0", "-1.0", "-2.0"),
+                    ("1.1", "1.1", "2.2"),
+                ];
+                for test_case in test_cases {
+                    assert_eq!(
+                        $name::from_str(test_case.0).unwrap()
+                            + $name::from_str(test_case.1).unwrap(),
+                        $name::from_str(test_case.2).unwrap()
+                    );
+                }
+
+                let test_cases_for_decimal = vec![
+                    ("1.0", "1.0", "2.0"),
+                    ("-1.0", "1.0", "0.0"),
+                    ("1.1", "1.1", "2.2"),
+                ];
+                for test_case in test_cases_for_decimal {
+                    assert_eq!(
+                        $name::from_str(test_case.0).unwrap()
+                            + $decimal::from_str(test_case.1).unwrap(),
+                        $name::from_str(test_case.2).unwrap()
+                    );
+                }
+            }
+
+            #[test]
+            fn test_sub_operations() {
+                let test_cases = vec![
+                    ("1.0", "1.0", "0.0"),
+                    ("1.0", "-1.0", "2.0"),
+                    ("-1.0", "1.0", "-2.0"),
+                    ("-1.0", "-1.0", "0.0"),
+                    ("1.1", "1.1", "0.0"),
+                ];
+                for test_case in test_cases {
+                    assert_eq!(
+                        $name::from_str(test_case.0).unwrap()
+                            - $name::from_str(test_case.1).unwrap(),
+                        $name::from_str(test_case.2).unwrap()
+                    );
+                }
+
+                let test_cases_for_decimal = vec![
+                    ("1.0", "1.0", "0.0"),
+                    ("-1.0", "1.0", "-2.0"),
+                    ("1.1", "1.1", "0.0"),
+                ];
+                for test_case in test_cases_for_decimal {
+                    assert_eq!(
+                        $name::from_str(test_case.0).unwrap()
+                            - $decimal::from_str(test_case.1).unwrap(),
+                        $name::from_str(test_case.2).unwrap()
+                    );
+                }
+            }
+
+            #[test]
+            fn test_mul_operations() {
+                let test_cases = vec![
+                    ("1.0", "1.0", "1.0"),
+                    ("1.0", "-1.0", "-1.0"),
+                    ("-1.0", "1.0", "-1.0"),
+                    ("-1.0", "-1.0", "1.0"),
+                    ("1.1", "1.1", "1.21"),
+                ];
+                for test_case in test_cases {
+                    assert_eq!(
+                        $name::from_str(test_case.0).unwrap()
+                            * $name::from_str(test_case.1).unwrap(),
+                        $name::from_str(test_case.2).unwrap()
+                    );
+                }
+
+                let test_cases_for_decimal = vec![
+                    ("1.0", "1.0", "1.0"),
+                    ("-1.0", "1.0", "-1.0"),
+                    ("1.1", "1.1", "1.21"),
+                ];
+                for test_case in test_cases_for_decimal {
+                    assert_eq!(
+                        $name::from_str(test_case.0).unwrap()
+                            * $decimal::from_str(test_case.1).unwrap(),
+                        $name::from_str(test_case.2).unwrap()
+                    );
+                }
+            }
+
+            #[test]
+            fn test_div_operations() {
+                let test_cases = vec![
+                    ("1.0", "1.0", "1.0"),
+                    ("1.0", "-1.0", "-1.0"),
+                    ("-1.0", "1.0", "-1.0"),
+                    ("-1.0", "-1.0", "1.0"),
+                    ("1.1", "1.1", "1.0"),
+                ];
+                for test_case in test_cases {
+                    assert_eq!(
+                        $name::from_str(test_case.0).unwrap()
+                            / $name::from_str(test_case.1).unwrap(),
+                        $name::from_str(test_case.2).unwrap()
+                    );
+                }
+            }
         }
-    }
+    };
 }
+
+#[cfg(test)]
+pub(crate) use impl_signed_decimal_tests;
+
+#[cfg(test)]
+impl_signed_decimal_tests!(test, SignedDecimal, Decimal, Uint128, Int256, 128);
@@ -0,0 +1,167 @@
+use cosmwasm_std::{
+    ConversionOverflowError, Decimal, Decimal256, Decimal256RangeExceeded, DivideByZeroError,
+    Int256, OverflowOperation, StdError, StdResult, Uint256,
+};
+use schemars::JsonSchema;
+use serde::{de, ser, Deserialize, Deserializer, Serialize};
+use std::cmp::Ordering;
+use std::convert::TryFrom;
+use std::fmt;
+use std::fmt::Write;
+use std::iter::Sum;
+use std::ops::{Add, AddAssign, Div, DivAssign, Mul, MulAssign, Neg, Sub, SubAssign};
+use std::str::FromStr;
+
+use crate::signed_decimal::{impl_signed_decimal, overflow, Sign, SignedDecimal};
+
+// The integer part of a Decimal256 is at most 1.2e59 so it always fits in an Int256
+fn to_int256(value: Uint256) -> Int256 {
+    Int256::from_be_bytes(value.to_be_bytes())
+}
+
+impl_signed_decimal!(
+    SignedDecimal256,
+    Decimal256,
+    Decimal256RangeExceeded,
+    Uint256,
+    to_int256
+);
+
+impl From<SignedDecimal> for SignedDecimal256 {
+    fn from(value: SignedDecimal) -> Self {
+        Self::from_decimal(value.unsigned_abs().into(), value.sign())
+    }
+}
+
+impl TryFrom<SignedDecimal256> for SignedDecimal {
+    type Error = ConversionOverflowError;
+
+    fn try_from(value: SignedDecimal256) -> Result<Self, Self::Error> {
+        let value_abs = Decimal::try_from(value.value).map_err(|_| {
+            ConversionOverflowError::new("SignedDecimal256", "SignedDecimal", value.to_string())
+        })?;
+        Ok(SignedDecimal::from_decimal(value_abs, value.sign))
+    }
+}
+
+impl From<Decimal> for SignedDecimal256 {
+    fn from(value: Decimal) -> Self {
+        Self::from_decimal(value.into(), Sign::Positive)
+    }
+}
+
+#[cfg(test)]
+crate::signed_decimal::impl_signed_decimal_tests!(
+    test,
+    SignedDecimal256,
+    Decimal256,
+    Uint256,
+    Int512,
+    256
+);
+
+#[cfg(test)]
+mod conversion_test {
+    use crate::signed_decimal::test::samples;
+    use crate::signed_decimal::SignedDecimal;
+    use crate::signed_decimal_256::SignedDecimal256;
+    use cosmwasm_std::{
+        ConversionOverflowError, Int256, OverflowError, OverflowOperation, StdError, StdResult,
+    };
+    use std::convert::TryFrom;
+    use std::str::FromStr;
+
+    type Op<T> = fn(T, T) -> StdResult<T>;
+
+    fn dec(value: &str) -> SignedDecimal256 {
+        SignedDecimal256::from_str(value).unwrap()
+    }
+
+    // Whenever the 128 bits operation succeeds the 256 bits
+    // operation must give exactly the same result
+    #[test]
+    fn test_matches_signed_decimal() {
+        for (a, b) in samples() {
+            let (a256, b256) = (SignedDecimal256::from(a), SignedDecimal256::from(b));
+            assert_eq!(a.cmp(&b), a256.cmp(&b256));
+            assert_eq!(SignedDecimal::try_from(-a256).unwrap(), -a);
+            assert_eq!(a.to_int_floor(), a256.to_int_floor());
+            assert_eq!(a.to_int_ceil(), a256.to_int_ceil());
+            assert_eq!(a.to_int_trunc(), a256.to_int_trunc());
+
+            let ops: [(Op<SignedDecimal>, Op<SignedDecimal256>); 4] = [
+                (SignedDecimal::checked_add, SignedDecimal256::checked_add),
+                (SignedDecimal::checked_sub, SignedDecimal256::checked_sub),
+                (SignedDecimal::checked_mul, SignedDecimal256::checked_mul),
+                (SignedDecimal::checked_div, SignedDecimal256::checked_div),
+            ];
+            for (op, op256) in ops {
+                if let Ok(res) = op(a, b) {
+                    assert_eq!(op256(a256, b256).unwrap(), SignedDecimal256::from(res));
+                }
+            }
+            if let Ok(res) = a.checked_pow(3) {
+                assert_eq!(a256.checked_pow(3).unwrap(), SignedDecimal256::from(res));
+            }
+        }
+    }
+
+    #[test]
+    fn test_large_magnitudes() {
+        // 1e25 * 1e25 overflows SignedDecimal but not SignedDecimal256
+        let a = dec("-10000000000000000000000000");
+        let res = a.checked_mul(a).unwrap();
+        assert_eq!(
+            res,
+            dec("100000000000000000000000000000000000000000000000000")
+        );
+        assert_eq!(
+            SignedDecimal::try_from(res),
+            Err(ConversionOverflowError::new(
+                "SignedDecimal256",
+                "SignedDecimal",
+                res.to_string()
+            ))
+        );
+        assert_eq!(
+            SignedDecimal::try_from(res / a / a).unwrap(),
+            SignedDecimal::one()
+        );
+        assert!(a.checked_pow(3).is_err());
+
+        assert_eq!(
+            SignedDecimal256::MAX.checked_mul(dec("-2")),
+            Err(StdError::overflow(OverflowError::new(
+                OverflowOperation::Mul,
+                SignedDecimal256::MAX,
+                dec("-2")
+            )))
+        );
+        assert_eq!(
+            SignedDecimal256::MIN.saturating_sub(SignedDecimal256::one()),
+            SignedDecimal256::MIN
+        );
+        assert_eq!(
+            SignedDecimal256::MAX.saturating_pow(2),
+            SignedDecimal256::MAX
+        );
+        assert!(matches!(
+            a.checked_div(SignedDecimal256::zero()),
+            Err(StdError::DivideByZero { .. })
+        ));
+    }
+
+    #[test]
+    fn test_int256_bounds() {
+        let max = SignedDecimal256::MAX.to_int_floor();
+        assert_eq!(
+            SignedDecimal256::try_from(-max).unwrap().to_int_trunc(),
+            -max
+        );
+        assert!(SignedDecimal256::try_from(max + Int256::one()).is_err());
+        assert_eq!(
+            SignedDecimal256::MIN.to_int_floor(),
+            -SignedDecimal256::MAX.to_int_ceil()
+        );
+    }
+}